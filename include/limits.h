@@ -0,0 +1,24 @@
+/* <limits.h>: sizes of integer types (C17 7.10, 5.2.4.2.1).
+ *
+ * Only the limits of char and int are defined, the integer types there
+ * are; char is signed. The limits of unsigned char are int constants, as
+ * the type promotes to int. A hosted C library's <limits.h> is not
+ * included, as it needs the wider types. */
+
+#ifndef __RCC_LIMITS_H
+#define __RCC_LIMITS_H
+
+#define CHAR_BIT __CHAR_BIT__
+#define MB_LEN_MAX 1
+
+#define SCHAR_MIN (-SCHAR_MAX - 1)
+#define SCHAR_MAX __SCHAR_MAX__
+#define UCHAR_MAX (SCHAR_MAX * 2 + 1)
+
+#define CHAR_MIN SCHAR_MIN
+#define CHAR_MAX SCHAR_MAX
+
+#define INT_MIN (-INT_MAX - 1)
+#define INT_MAX __INT_MAX__
+
+#endif
@@ -0,0 +1,26 @@
+/* <stdarg.h>: variable arguments (C17 7.16).
+ *
+ * There is no typedef, so the types are macros for the builtin type. The
+ * C library defines __need___va_list to get only __gnuc_va_list, the name
+ * it declares vprintf and friends with. */
+
+#ifndef __gnuc_va_list
+#define __gnuc_va_list __builtin_va_list
+#endif
+
+#ifdef __need___va_list
+#undef __need___va_list
+#else
+
+#ifndef __RCC_STDARG_H
+#define __RCC_STDARG_H
+
+#define va_list __builtin_va_list
+
+#define va_start(ap, param) __builtin_va_start(ap, param)
+#define va_arg(ap, type) __builtin_va_arg(ap, type)
+#define va_end(ap) __builtin_va_end(ap)
+#define va_copy(dest, src) __builtin_va_copy(dest, src)
+
+#endif
+#endif
@@ -0,0 +1,42 @@
+/* <stddef.h>: common definitions (C17 7.19).
+ *
+ * There is no typedef, and no long, unsigned or struct types, so only the
+ * definitions those are not needed for are made: ptrdiff_t and wchar_t are
+ * macros for __PTRDIFF_TYPE__ and __WCHAR_TYPE__, the types the compiler
+ * gives a pointer difference and a wide character constant, both int for
+ * now; size_t, wint_t, max_align_t and offsetof are left out. NULL is a
+ * plain 0, as there are no casts.
+ *
+ * Being macros, ptrdiff_t and wchar_t are replaced wherever they appear,
+ * where a typedef name would not be: a member or parameter named wchar_t
+ * becomes int, and #ifdef ptrdiff_t is true once this header is included.
+ *
+ * The C library defines __need_ptrdiff_t, __need_NULL and the like to get
+ * only some of the definitions. */
+
+#if !defined __need_size_t && !defined __need_ptrdiff_t \
+    && !defined __need_wchar_t && !defined __need_wint_t \
+    && !defined __need_NULL && !defined __need_offsetof
+#define __RCC_STDDEF_ALL
+#endif
+
+#if (defined __RCC_STDDEF_ALL || defined __need_ptrdiff_t) && !defined ptrdiff_t
+#define ptrdiff_t __PTRDIFF_TYPE__
+#endif
+
+#if (defined __RCC_STDDEF_ALL || defined __need_wchar_t) && !defined wchar_t
+#define wchar_t __WCHAR_TYPE__
+#endif
+
+#if defined __RCC_STDDEF_ALL || defined __need_NULL
+#undef NULL
+#define NULL 0
+#endif
+
+#undef __RCC_STDDEF_ALL
+#undef __need_size_t
+#undef __need_ptrdiff_t
+#undef __need_wchar_t
+#undef __need_wint_t
+#undef __need_NULL
+#undef __need_offsetof
@@ -0,0 +1,47 @@
+/* <stdint.h>: integer types (C17 7.20).
+ *
+ * There is no typedef, and char and int are the only integer types, so the
+ * types are macros for those and only the ones they can be are defined:
+ * the signed 8- and 32-bit ones, with int the smallest type of at least 16
+ * bits. A hosted C library's <stdint.h> is not included, as it needs the
+ * wider types.
+ *
+ * As macros, the type names are replaced wherever they appear, even where
+ * a typedef name would not be, as in #ifdef int32_t, and a program that
+ * declares one of them itself gets a declaration of char or int instead. */
+
+#ifndef __RCC_STDINT_H
+#define __RCC_STDINT_H
+
+#define int8_t char
+#define int32_t int
+
+#define int_least8_t char
+#define int_least16_t int
+#define int_least32_t int
+
+#define int_fast8_t char
+
+#define INT8_MIN (-128)
+#define INT32_MIN (-2147483647 - 1)
+#define INT8_MAX 127
+#define INT32_MAX 2147483647
+
+#define INT_LEAST8_MIN INT8_MIN
+#define INT_LEAST16_MIN INT32_MIN
+#define INT_LEAST32_MIN INT32_MIN
+#define INT_LEAST8_MAX INT8_MAX
+#define INT_LEAST16_MAX INT32_MAX
+#define INT_LEAST32_MAX INT32_MAX
+
+#define INT_FAST8_MIN INT8_MIN
+#define INT_FAST8_MAX INT8_MAX
+
+#define WCHAR_MIN (-__WCHAR_MAX__ - 1)
+#define WCHAR_MAX __WCHAR_MAX__
+
+#define INT8_C(c) c
+#define INT16_C(c) c
+#define INT32_C(c) c
+
+#endif
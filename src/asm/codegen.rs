@@ -1,47 +1,812 @@
-use crate::asm::{Function, Instr, InstrSeq, Operand, Program};
+use crate::asm::{
+    AsmType, BinaryOp, CondCode, Function, Instr, InstrSeq, Operand, Program, Reg, StaticString,
+    UnaryOp,
+};
 use crate::ast;
+use crate::types::{Type, TypeKind};
+use std::collections::HashMap;
+
+const ARG_REGISTERS: [Reg; 6] = [Reg::Di, Reg::Si, Reg::Dx, Reg::Cx, Reg::R8, Reg::R9];
+
+/// `fp_offset` once every vector register is consumed; floating-point
+/// arguments are not supported, so none are saved.
+const VA_FP_OFFSET_END: i64 = 176;
 
 impl From<ast::Program> for Program {
     fn from(prog: ast::Program) -> Self {
         match prog {
-            ast::Program::FunctionDefinition(func) => Self::Program(Function::from(func)),
+            ast::Program::Program(functions, _) => {
+                let mut strings = Vec::new();
+                let functions = functions
+                    .into_iter()
+                    .filter_map(|func| FunctionGen::new(&mut strings).generate(func))
+                    .collect();
+
+                Self::Program { functions, strings }
+            }
         }
     }
 }
 
-impl From<ast::Function> for Function {
-    fn from(func: ast::Function) -> Self {
-        match func {
-            ast::Function::Function { name, body } => {
-                let instructions = InstrSeq::from(body);
+fn asm_type(ty: &Type) -> AsmType {
+    match ty.kind {
+        TypeKind::Int => AsmType::Longword,
+        TypeKind::Char => AsmType::Byte,
+        _ => AsmType::Quadword,
+    }
+}
+
+/// The element type of a pointer or array, if `ty` is one.
+fn element_type(ty: &Type) -> Option<&Type> {
+    match &ty.kind {
+        TypeKind::Pointer(element) | TypeKind::Array { element, .. } => Some(element),
+        _ => None,
+    }
+}
+
+/// The function type called through a callee of type `ty`, which is either
+/// a function designator or a pointer to a function.
+fn called_type(ty: &Type) -> &Type {
+    match &ty.kind {
+        TypeKind::Pointer(pointee) if pointee.is_function() => pointee,
+        _ => ty,
+    }
+}
+
+/// Where `va_start` finds the variadic arguments of the current function.
+#[derive(Clone, Copy)]
+struct VarArgs {
+    /// `%rbp` offset of the six saved argument registers.
+    reg_save_area: i64,
+    /// Bytes of the register save area taken by named parameters.
+    gp_offset: i64,
+    /// `%rbp` offset of the first variadic argument passed on the stack.
+    overflow_arg_area: i64,
+}
+
+/// Generates a function body as a stack machine: every expression leaves
+/// its value in `%rax`, sign-extended to 64 bits, and intermediate values
+/// are pushed.
+struct FunctionGen<'a> {
+    /// String literals of the whole program, shared by every function.
+    strings: &'a mut Vec<StaticString>,
+    name: String,
+    labels: usize,
+    var_args: Option<VarArgs>,
+    /// The frame slots of the locals, by their unique names.
+    vars: HashMap<String, Operand>,
+    instructions: Vec<Instr>,
+    frame_size: i64,
+    /// Eight-byte values pushed below the frame, tracked to align calls.
+    depth: i64,
+}
+
+impl<'a> FunctionGen<'a> {
+    fn new(strings: &'a mut Vec<StaticString>) -> Self {
+        Self {
+            strings,
+            name: String::new(),
+            labels: 0,
+            var_args: None,
+            vars: HashMap::new(),
+            instructions: Vec::new(),
+            frame_size: 0,
+            depth: 0,
+        }
+    }
+
+    fn generate(mut self, func: ast::Function) -> Option<Function> {
+        let ast::Function::Function {
+            name,
+            params,
+            variadic,
+            body,
+            ..
+        } = func;
+        let body = body?;
+        self.name = name.clone();
+
+        self.emit(Instr::Push(Operand::Reg(Reg::Bp)));
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Sp),
+            dst: Operand::Reg(Reg::Bp),
+        });
+        self.emit(Instr::AllocateStack(0));
+
+        if variadic {
+            self.gen_register_save_area(params.len() as i64);
+        }
+
+        for (i, param) in params.into_iter().enumerate() {
+            let name = param.name.expect("definitions have named parameters");
+            let slot = match ARG_REGISTERS.get(i) {
+                Some(reg) => {
+                    let slot = self.allocate(&param.ty);
+                    self.emit(Instr::Mov {
+                        ty: asm_type(&param.ty),
+                        src: Operand::Reg(*reg),
+                        dst: slot.clone(),
+                    });
+                    slot
+                }
+                None => Operand::Memory(Reg::Bp, 16 + 8 * (i - ARG_REGISTERS.len()) as i64),
+            };
+            self.vars.insert(name, slot);
+        }
+
+        for item in body.0 {
+            self.gen_block_item(item);
+        }
+        // Reaching the end of `main` returns 0; reaching the end of any
+        // other function leaves its value undefined.
+        if self.name == "main" {
+            self.emit(Instr::Mov {
+                ty: AsmType::Longword,
+                src: Operand::Imm(0),
+                dst: Operand::Reg(Reg::Ax),
+            });
+        }
+        self.gen_epilogue();
+
+        self.instructions[2] = Instr::AllocateStack((self.frame_size + 15) / 16 * 16);
+
+        Some(Function::Function {
+            name,
+            instructions: InstrSeq(self.instructions),
+        })
+    }
+
+    fn emit(&mut self, instr: Instr) {
+        self.instructions.push(instr);
+    }
+
+    fn make_label(&mut self, kind: &str) -> String {
+        self.labels += 1;
+        format!("{}.{}.{}", self.name, kind, self.labels)
+    }
+
+    /// The label of a string literal with these bytes. Identical literals
+    /// share one copy, which C permits since modifying them is undefined.
+    fn string_label(&mut self, bytes: &[u8]) -> String {
+        if let Some(string) = self.strings.iter().find(|s| s.bytes == bytes) {
+            return string.label.clone();
+        }
+
+        let label = format!("str.{}", self.strings.len());
+        self.strings.push(StaticString {
+            label: label.clone(),
+            bytes: bytes.to_vec(),
+        });
+        label
+    }
+
+    /// Spills every argument register so `va_arg` can walk past the named
+    /// parameters into the variadic ones.
+    fn gen_register_save_area(&mut self, named: i64) {
+        let reg_save_area = self.allocate_bytes(8 * ARG_REGISTERS.len() as i64);
+
+        for (i, reg) in ARG_REGISTERS.iter().enumerate() {
+            self.emit(Instr::Mov {
+                ty: AsmType::Quadword,
+                src: Operand::Reg(*reg),
+                dst: Operand::Memory(Reg::Bp, reg_save_area + 8 * i as i64),
+            });
+        }
+
+        let registers = ARG_REGISTERS.len() as i64;
+        self.var_args = Some(VarArgs {
+            reg_save_area,
+            gp_offset: 8 * named.min(registers),
+            overflow_arg_area: 16 + 8 * (named - registers).max(0),
+        });
+    }
+
+    fn allocate(&mut self, ty: &Type) -> Operand {
+        Operand::Memory(Reg::Bp, self.allocate_bytes(ty.size()))
+    }
+
+    /// Reserves `size` bytes in the frame and returns their `%rbp` offset.
+    fn allocate_bytes(&mut self, size: i64) -> i64 {
+        let align = if size >= 8 { 8 } else { 4 };
+        self.frame_size = (self.frame_size + size + align - 1) / align * align;
+
+        -self.frame_size
+    }
+
+    fn push(&mut self, reg: Reg) {
+        self.emit(Instr::Push(Operand::Reg(reg)));
+        self.depth += 1;
+    }
+
+    fn pop(&mut self, reg: Reg) {
+        self.emit(Instr::Pop(reg));
+        self.depth -= 1;
+    }
 
-                Self::Function { name, instructions }
+    fn gen_epilogue(&mut self) {
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Bp),
+            dst: Operand::Reg(Reg::Sp),
+        });
+        self.emit(Instr::Pop(Reg::Bp));
+        self.emit(Instr::Ret);
+    }
+
+    fn gen_block_item(&mut self, item: ast::BlockItem) {
+        match item {
+            ast::BlockItem::Declaration(ast::Declaration::Variable { name, ty, init, .. }) => {
+                let slot = self.allocate(&ty);
+                if let (TypeKind::Array { size, .. }, Some(ast::ExpKind::String(bytes))) =
+                    (&ty.kind, init.as_ref().map(|init| &init.kind))
+                {
+                    self.gen_string_init(&slot, bytes, size.unwrap_or(0));
+                } else if let Some(init) = init {
+                    self.gen_exp(&init);
+                    self.emit(Instr::Lea {
+                        src: slot.clone(),
+                        dst: Operand::Reg(Reg::Cx),
+                    });
+                    self.store(&ty);
+                }
+                self.vars.insert(name, slot);
             }
+            ast::BlockItem::Declaration(ast::Declaration::Function(_)) => {}
+            ast::BlockItem::Statement(stmt) => self.gen_statement(stmt),
         }
     }
-}
 
-impl From<ast::Statement> for InstrSeq {
-    fn from(func: ast::Statement) -> Self {
-        match func {
-            ast::Statement::Return(exp) => {
-                let mut instructions: Vec<Instr> = Vec::<Instr>::new();
-                instructions.push(Instr::Mov {
-                    src: Operand::Register,
-                    dst: Operand::from(exp),
+    /// Copies a string literal into a `char` array of `size` elements,
+    /// zero-filling the rest.
+    fn gen_string_init(&mut self, slot: &Operand, bytes: &[u8], size: i64) {
+        let Operand::Memory(reg, offset) = *slot else {
+            unreachable!("locals live in the frame")
+        };
+        for i in 0..size {
+            let byte = bytes.get(i as usize).copied().unwrap_or(0);
+            self.emit(Instr::Mov {
+                ty: AsmType::Byte,
+                src: Operand::Imm(byte.into()),
+                dst: Operand::Memory(reg, offset + i),
+            });
+        }
+    }
+
+    fn gen_statement(&mut self, stmt: ast::Statement) {
+        match stmt.kind {
+            ast::StatementKind::Return(exp) => {
+                self.gen_exp(&exp);
+                self.gen_epilogue();
+            }
+            ast::StatementKind::Expression(exp) => self.gen_exp(&exp),
+            ast::StatementKind::Null => (),
+        }
+    }
+
+    /// Orders the operands of `a[b]` as (pointer, index); C allows `2[a]`.
+    fn subscript_operands<'e>(a: &'e ast::Exp, b: &'e ast::Exp) -> (&'e ast::Exp, &'e ast::Exp) {
+        if element_type(a.ty()).is_some() {
+            (a, b)
+        } else {
+            (b, a)
+        }
+    }
+
+    fn gen_exp(&mut self, exp: &ast::Exp) {
+        match &exp.kind {
+            ast::ExpKind::ConstantInt(n) | ast::ExpKind::ConstantChar(n, _) => {
+                self.emit(Instr::Mov {
+                    ty: AsmType::Quadword,
+                    src: Operand::Imm(*n),
+                    dst: Operand::Reg(Reg::Ax),
+                })
+            }
+            ast::ExpKind::String(_)
+            | ast::ExpKind::Var(_)
+            | ast::ExpKind::Dereference(_)
+            | ast::ExpKind::Subscript(..) => {
+                let ty = exp.ty();
+                self.gen_address(exp);
+                // Arrays and functions decay to their address.
+                if !ty.is_array() && !ty.is_function() {
+                    self.load(ty);
+                }
+            }
+            ast::ExpKind::AddressOf(exp) => self.gen_address(exp),
+            ast::ExpKind::Unary(op, operand) => self.gen_unary(*op, operand, exp.ty()),
+            ast::ExpKind::Binary(ast::BinaryOp::And | ast::BinaryOp::Or, lhs, rhs) => {
+                let ast::ExpKind::Binary(op, ..) = exp.kind else {
+                    unreachable!()
+                };
+                self.gen_logical(op, lhs, rhs)
+            }
+            ast::ExpKind::Binary(op, lhs, rhs) => self.gen_binary(*op, lhs, rhs, exp.ty()),
+            ast::ExpKind::Conditional(condition, then, otherwise) => {
+                let otherwise_label = self.make_label("cond_else");
+                let end = self.make_label("cond_end");
+                self.gen_exp(condition);
+                self.compare_zero();
+                self.emit(Instr::JmpCC(CondCode::E, otherwise_label.clone()));
+                self.gen_exp(then);
+                self.emit(Instr::Jmp(end.clone()));
+                self.emit(Instr::Label(otherwise_label));
+                self.gen_exp(otherwise);
+                self.emit(Instr::Label(end));
+            }
+            ast::ExpKind::Assignment(lhs, rhs) => {
+                self.gen_address(lhs);
+                self.push(Reg::Ax);
+                self.gen_exp(rhs);
+                self.pop(Reg::Cx);
+                self.store(lhs.ty());
+            }
+            ast::ExpKind::FunctionCall(callee, args) => self.gen_call(callee, args),
+            ast::ExpKind::VaStart(ap) => self.gen_va_start(ap),
+            ast::ExpKind::VaArg(ap, ty) => self.gen_va_arg(ap, ty),
+            ast::ExpKind::VaEnd(ap) => self.gen_exp(ap),
+            ast::ExpKind::Convert(inner) => {
+                self.gen_exp(inner);
+                // Only narrowing to `char` changes the value in `%rax`; the
+                // other conversions allowed keep its bits.
+                if exp.ty().kind == TypeKind::Char && inner.ty().kind != TypeKind::Char {
+                    self.emit(Instr::Movsx {
+                        ty: AsmType::Byte,
+                        src: Operand::Reg(Reg::Ax),
+                        dst: Operand::Reg(Reg::Ax),
+                    });
+                }
+            }
+            ast::ExpKind::VaCopy(dst, src) => {
+                self.gen_exp(dst);
+                self.push(Reg::Ax);
+                self.gen_exp(src);
+                self.pop(Reg::Cx);
+                for offset in [0, 8, 16] {
+                    self.emit(Instr::Mov {
+                        ty: AsmType::Quadword,
+                        src: Operand::Memory(Reg::Ax, offset),
+                        dst: Operand::Reg(Reg::Dx),
+                    });
+                    self.emit(Instr::Mov {
+                        ty: AsmType::Quadword,
+                        src: Operand::Reg(Reg::Dx),
+                        dst: Operand::Memory(Reg::Cx, offset),
+                    });
+                }
+            }
+        }
+    }
+
+    fn compare_zero(&mut self) {
+        self.emit(Instr::Cmp {
+            ty: AsmType::Quadword,
+            src: Operand::Imm(0),
+            dst: Operand::Reg(Reg::Ax),
+        });
+    }
+
+    /// Sets `%rax` to 1 if the flags satisfy `cond`, and to 0 if not.
+    fn set_from_flags(&mut self, cond: CondCode) {
+        self.emit(Instr::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Imm(0),
+            dst: Operand::Reg(Reg::Ax),
+        });
+        self.emit(Instr::SetCC(cond, Operand::Reg(Reg::Ax)));
+    }
+
+    /// Sign-extends the low bits of `%rax` that hold a value of type `ty`
+    /// after quadword arithmetic, which wraps around at its width.
+    fn sign_extend(&mut self, ty: &Type) {
+        if let ty @ (AsmType::Byte | AsmType::Longword) = asm_type(ty) {
+            self.emit(Instr::Movsx {
+                ty,
+                src: Operand::Reg(Reg::Ax),
+                dst: Operand::Reg(Reg::Ax),
+            });
+        }
+    }
+
+    fn gen_unary(&mut self, op: ast::UnaryOp, operand: &ast::Exp, ty: &Type) {
+        self.gen_exp(operand);
+        let op = match op {
+            ast::UnaryOp::Plus => return,
+            ast::UnaryOp::Not => {
+                self.compare_zero();
+                return self.set_from_flags(CondCode::E);
+            }
+            ast::UnaryOp::Negate => UnaryOp::Neg,
+            ast::UnaryOp::Complement => UnaryOp::Not,
+        };
+        self.emit(Instr::Unary {
+            op,
+            ty: AsmType::Quadword,
+            dst: Operand::Reg(Reg::Ax),
+        });
+        self.sign_extend(ty);
+    }
+
+    /// `&&` and `||`, which evaluate their right operand only if the left
+    /// one does not decide the result.
+    fn gen_logical(&mut self, op: ast::BinaryOp, lhs: &ast::Exp, rhs: &ast::Exp) {
+        let decided = self.make_label("logical_decided");
+        let end = self.make_label("logical_end");
+        // `&&` is decided by a false operand, `||` by a true one.
+        let (cond, decided_value) = match op {
+            ast::BinaryOp::And => (CondCode::E, 0),
+            _ => (CondCode::NE, 1),
+        };
+
+        for operand in [lhs, rhs] {
+            self.gen_exp(operand);
+            self.compare_zero();
+            self.emit(Instr::JmpCC(cond, decided.clone()));
+        }
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Imm(1 - decided_value),
+            dst: Operand::Reg(Reg::Ax),
+        });
+        self.emit(Instr::Jmp(end.clone()));
+        self.emit(Instr::Label(decided));
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Imm(decided_value),
+            dst: Operand::Reg(Reg::Ax),
+        });
+        self.emit(Instr::Label(end));
+    }
+
+    /// Multiplies `%rax` by the size of what a pointer of type `ty` points
+    /// to, turning an index into a byte offset.
+    fn scale(&mut self, ty: &Type) {
+        let size = element_type(ty).map_or(1, Type::size);
+        if size != 1 {
+            self.emit(Instr::Binary {
+                op: BinaryOp::Mult,
+                ty: AsmType::Quadword,
+                src: Operand::Imm(size),
+                dst: Operand::Reg(Reg::Ax),
+            });
+        }
+    }
+
+    /// Computes `lhs op rhs` with the left operand in `%rax` and the right
+    /// one in `%rcx`.
+    fn gen_binary(&mut self, op: ast::BinaryOp, lhs: &ast::Exp, rhs: &ast::Exp, ty: &Type) {
+        let (lhs_ty, rhs_ty) = (lhs.ty().decay(), rhs.ty().decay());
+        let pointer_arithmetic = matches!(op, ast::BinaryOp::Add | ast::BinaryOp::Subtract);
+
+        self.gen_exp(lhs);
+        if pointer_arithmetic && rhs_ty.is_pointer() && !lhs_ty.is_pointer() {
+            self.scale(&rhs_ty);
+        }
+        self.push(Reg::Ax);
+        self.gen_exp(rhs);
+        if pointer_arithmetic && lhs_ty.is_pointer() && !rhs_ty.is_pointer() {
+            self.scale(&lhs_ty);
+        }
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Ax),
+            dst: Operand::Reg(Reg::Cx),
+        });
+        self.pop(Reg::Ax);
+
+        let arithmetic = |op| Instr::Binary {
+            op,
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Cx),
+            dst: Operand::Reg(Reg::Ax),
+        };
+        // Pointers compare as unsigned addresses.
+        let unsigned = lhs_ty.is_pointer();
+        match op {
+            ast::BinaryOp::Add => self.emit(arithmetic(BinaryOp::Add)),
+            ast::BinaryOp::Subtract => {
+                self.emit(arithmetic(BinaryOp::Sub));
+                if lhs_ty.is_pointer() && rhs_ty.is_pointer() {
+                    self.emit(Instr::Mov {
+                        ty: AsmType::Quadword,
+                        src: Operand::Imm(element_type(&lhs_ty).map_or(1, Type::size)),
+                        dst: Operand::Reg(Reg::Cx),
+                    });
+                    self.gen_division(false);
+                }
+            }
+            ast::BinaryOp::Multiply => self.emit(arithmetic(BinaryOp::Mult)),
+            ast::BinaryOp::Divide => self.gen_division(false),
+            ast::BinaryOp::Remainder => self.gen_division(true),
+            ast::BinaryOp::ShiftLeft => self.emit(arithmetic(BinaryOp::Shl)),
+            ast::BinaryOp::ShiftRight => self.emit(arithmetic(BinaryOp::Sar)),
+            ast::BinaryOp::BitAnd => self.emit(arithmetic(BinaryOp::And)),
+            ast::BinaryOp::BitXor => self.emit(arithmetic(BinaryOp::Xor)),
+            ast::BinaryOp::BitOr => self.emit(arithmetic(BinaryOp::Or)),
+            ast::BinaryOp::Less
+            | ast::BinaryOp::Greater
+            | ast::BinaryOp::LessEqual
+            | ast::BinaryOp::GreaterEqual
+            | ast::BinaryOp::Equal
+            | ast::BinaryOp::NotEqual => {
+                self.emit(Instr::Cmp {
+                    ty: AsmType::Quadword,
+                    src: Operand::Reg(Reg::Cx),
+                    dst: Operand::Reg(Reg::Ax),
                 });
-                instructions.push(Instr::Ret);
+                let cond = match (op, unsigned) {
+                    (ast::BinaryOp::Less, false) => CondCode::L,
+                    (ast::BinaryOp::Greater, false) => CondCode::G,
+                    (ast::BinaryOp::LessEqual, false) => CondCode::LE,
+                    (ast::BinaryOp::GreaterEqual, false) => CondCode::GE,
+                    (ast::BinaryOp::Less, true) => CondCode::B,
+                    (ast::BinaryOp::Greater, true) => CondCode::A,
+                    (ast::BinaryOp::LessEqual, true) => CondCode::BE,
+                    (ast::BinaryOp::GreaterEqual, true) => CondCode::AE,
+                    (ast::BinaryOp::Equal, _) => CondCode::E,
+                    _ => CondCode::NE,
+                };
+                return self.set_from_flags(cond);
+            }
+            ast::BinaryOp::And | ast::BinaryOp::Or => {
+                unreachable!("logical operators short-circuit")
+            }
+        }
+        self.sign_extend(ty);
+    }
+
+    /// Divides `%rax` by `%rcx`, leaving the quotient or the remainder in
+    /// `%rax`.
+    fn gen_division(&mut self, remainder: bool) {
+        self.emit(Instr::Cqo);
+        self.emit(Instr::Idiv {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Cx),
+        });
+        if remainder {
+            self.emit(Instr::Mov {
+                ty: AsmType::Quadword,
+                src: Operand::Reg(Reg::Dx),
+                dst: Operand::Reg(Reg::Ax),
+            });
+        }
+    }
 
-                Self(instructions)
+    /// Initializes the `__va_list_tag` that `ap` points to:
+    /// `{ gp_offset, fp_offset, overflow_arg_area, reg_save_area }`.
+    fn gen_va_start(&mut self, ap: &ast::Exp) {
+        let var_args = self
+            .var_args
+            .expect("the parser only accepts va_start in variadic functions");
+
+        self.gen_exp(ap);
+        self.emit(Instr::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Imm(var_args.gp_offset),
+            dst: Operand::Memory(Reg::Ax, 0),
+        });
+        self.emit(Instr::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Imm(VA_FP_OFFSET_END),
+            dst: Operand::Memory(Reg::Ax, 4),
+        });
+        for (area, offset) in [
+            (var_args.overflow_arg_area, 8),
+            (var_args.reg_save_area, 16),
+        ] {
+            self.emit(Instr::Lea {
+                src: Operand::Memory(Reg::Bp, area),
+                dst: Operand::Reg(Reg::Cx),
+            });
+            self.emit(Instr::Mov {
+                ty: AsmType::Quadword,
+                src: Operand::Reg(Reg::Cx),
+                dst: Operand::Memory(Reg::Ax, offset),
+            });
+        }
+    }
+
+    /// Fetches the next integer-class argument: from the register save area
+    /// while `gp_offset` is below 48, and from the overflow area after that.
+    fn gen_va_arg(&mut self, ap: &ast::Exp, ty: &Type) {
+        let overflow = self.make_label("va_overflow");
+        let done = self.make_label("va_done");
+
+        self.gen_exp(ap);
+        self.emit(Instr::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Memory(Reg::Ax, 0),
+            dst: Operand::Reg(Reg::Cx),
+        });
+        self.emit(Instr::Cmp {
+            ty: AsmType::Longword,
+            src: Operand::Imm(8 * ARG_REGISTERS.len() as i64),
+            dst: Operand::Reg(Reg::Cx),
+        });
+        self.emit(Instr::JmpCC(CondCode::AE, overflow.clone()));
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Memory(Reg::Ax, 16),
+            dst: Operand::Reg(Reg::Dx),
+        });
+        self.emit(Instr::Binary {
+            op: BinaryOp::Add,
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Cx),
+            dst: Operand::Reg(Reg::Dx),
+        });
+        self.emit(Instr::Binary {
+            op: BinaryOp::Add,
+            ty: AsmType::Longword,
+            src: Operand::Imm(8),
+            dst: Operand::Reg(Reg::Cx),
+        });
+        self.emit(Instr::Mov {
+            ty: AsmType::Longword,
+            src: Operand::Reg(Reg::Cx),
+            dst: Operand::Memory(Reg::Ax, 0),
+        });
+        self.emit(Instr::Jmp(done.clone()));
+
+        self.emit(Instr::Label(overflow));
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Memory(Reg::Ax, 8),
+            dst: Operand::Reg(Reg::Dx),
+        });
+        self.emit(Instr::Lea {
+            src: Operand::Memory(Reg::Dx, 8),
+            dst: Operand::Reg(Reg::Cx),
+        });
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Cx),
+            dst: Operand::Memory(Reg::Ax, 8),
+        });
+
+        self.emit(Instr::Label(done));
+        self.emit(Instr::Mov {
+            ty: AsmType::Quadword,
+            src: Operand::Reg(Reg::Dx),
+            dst: Operand::Reg(Reg::Ax),
+        });
+        self.load(ty);
+    }
+
+    /// Leaves the address of an lvalue or function designator in `%rax`.
+    fn gen_address(&mut self, exp: &ast::Exp) {
+        match &exp.kind {
+            ast::ExpKind::String(bytes) => {
+                let label = self.string_label(bytes);
+                self.emit(Instr::Lea {
+                    src: Operand::Data(label),
+                    dst: Operand::Reg(Reg::Ax),
+                });
+            }
+            ast::ExpKind::Var(name) => match self.vars.get(name) {
+                Some(slot) => {
+                    let src = slot.clone();
+                    self.emit(Instr::Lea {
+                        src,
+                        dst: Operand::Reg(Reg::Ax),
+                    });
+                }
+                None => self.emit(Instr::Mov {
+                    ty: AsmType::Quadword,
+                    src: Operand::GotEntry(name.clone()),
+                    dst: Operand::Reg(Reg::Ax),
+                }),
+            },
+            ast::ExpKind::Dereference(exp) => self.gen_exp(exp),
+            ast::ExpKind::Subscript(a, b) => {
+                let (base, index) = Self::subscript_operands(a, b);
+                let element = element_type(base.ty()).map_or(1, Type::size);
+                self.gen_exp(base);
+                self.push(Reg::Ax);
+                self.gen_exp(index);
+                self.emit(Instr::Binary {
+                    op: BinaryOp::Mult,
+                    ty: AsmType::Quadword,
+                    src: Operand::Imm(element),
+                    dst: Operand::Reg(Reg::Ax),
+                });
+                self.pop(Reg::Cx);
+                self.emit(Instr::Binary {
+                    op: BinaryOp::Add,
+                    ty: AsmType::Quadword,
+                    src: Operand::Reg(Reg::Cx),
+                    dst: Operand::Reg(Reg::Ax),
+                });
             }
+            // Not an lvalue; the value itself is the best we can do.
+            _ => self.gen_exp(exp),
         }
     }
-}
 
-impl From<ast::Exp> for Operand {
-    fn from(exp: ast::Exp) -> Self {
-        match exp {
-            ast::Exp::ConstantInt(n) => Self::Imm(n),
+    /// Replaces the address in `%rax` with the value of type `ty` stored there.
+    fn load(&mut self, ty: &Type) {
+        let src = Operand::Memory(Reg::Ax, 0);
+        let dst = Operand::Reg(Reg::Ax);
+        match asm_type(ty) {
+            ty @ (AsmType::Byte | AsmType::Longword) => self.emit(Instr::Movsx { ty, src, dst }),
+            AsmType::Quadword => self.emit(Instr::Mov {
+                ty: AsmType::Quadword,
+                src,
+                dst,
+            }),
+        }
+    }
+
+    /// Stores the value in `%rax` to the address in `%rcx`.
+    fn store(&mut self, ty: &Type) {
+        self.emit(Instr::Mov {
+            ty: asm_type(ty),
+            src: Operand::Reg(Reg::Ax),
+            dst: Operand::Memory(Reg::Cx, 0),
+        });
+    }
+
+    fn gen_call(&mut self, callee: &ast::Exp, args: &[ast::Exp]) {
+        let TypeKind::Function { ret, variadic, .. } = &called_type(callee.ty()).kind else {
+            unreachable!("type checking only allows calls of functions")
+        };
+
+        let stack_args = args.len().saturating_sub(ARG_REGISTERS.len()) as i64;
+        let padding = (self.depth + stack_args) % 2;
+        if padding == 1 {
+            self.emit(Instr::AllocateStack(8));
+            self.depth += 1;
+        }
+
+        for arg in args.iter().rev() {
+            self.gen_exp(arg);
+            self.push(Reg::Ax);
+        }
+
+        // Calls through anything but a function name go through a register.
+        let direct = match &callee.kind {
+            ast::ExpKind::Var(name) if !self.vars.contains_key(name) => Some(name.clone()),
+            _ => None,
+        };
+        if direct.is_none() {
+            self.gen_exp(callee);
+            self.emit(Instr::Mov {
+                ty: AsmType::Quadword,
+                src: Operand::Reg(Reg::Ax),
+                dst: Operand::Reg(Reg::R11),
+            });
+        }
+
+        for reg in ARG_REGISTERS.iter().take(args.len()) {
+            self.pop(*reg);
+        }
+
+        // `%al` holds the number of vector registers used by the arguments.
+        if *variadic {
+            self.emit(Instr::Mov {
+                ty: AsmType::Longword,
+                src: Operand::Imm(0),
+                dst: Operand::Reg(Reg::Ax),
+            });
+        }
+
+        match direct {
+            Some(name) => self.emit(Instr::Call(name)),
+            None => self.emit(Instr::IndirectCall(Reg::R11)),
+        }
+
+        let cleanup = stack_args + padding;
+        if cleanup > 0 {
+            self.emit(Instr::DeallocateStack(8 * cleanup));
+            self.depth -= cleanup;
+        }
+
+        if matches!(ret.kind, TypeKind::Int | TypeKind::Char) {
+            self.emit(Instr::Movsx {
+                ty: asm_type(ret),
+                src: Operand::Reg(Reg::Ax),
+                dst: Operand::Reg(Reg::Ax),
+            });
         }
     }
 }
@@ -2,9 +2,9 @@ use crate::asm::Function;
 use crate::pretty::Pretty;
 use std::fmt;
 
-fn function_name(name: &str) -> String {
+pub fn function_name(name: &str) -> String {
     if cfg!(target_os = "macos") {
-        return format!("_{}", name);
+        format!("_{}", name)
     } else {
         name.to_string()
     }
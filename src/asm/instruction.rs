@@ -1,4 +1,5 @@
-use crate::asm::Instr;
+use crate::asm::function::function_name;
+use crate::asm::{AsmType, BinaryOp, CondCode, Instr, Operand, UnaryOp};
 use crate::pretty::{self, Pretty};
 use std::fmt;
 
@@ -8,12 +9,141 @@ impl fmt::Display for Instr {
     }
 }
 
+fn suffix(ty: AsmType) -> &'static str {
+    match ty {
+        AsmType::Byte => "b",
+        AsmType::Longword => "l",
+        AsmType::Quadword => "q",
+    }
+}
+
+impl fmt::Display for CondCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let cond = match self {
+            CondCode::E => "e",
+            CondCode::NE => "ne",
+            CondCode::L => "l",
+            CondCode::LE => "le",
+            CondCode::G => "g",
+            CondCode::GE => "ge",
+            CondCode::B => "b",
+            CondCode::BE => "be",
+            CondCode::A => "a",
+            CondCode::AE => "ae",
+        };
+        write!(f, "{cond}")
+    }
+}
+
+/// Local labels must not reach the object file's symbol table.
+pub fn local_label(name: &str) -> String {
+    if cfg!(target_os = "macos") {
+        format!("L{name}")
+    } else {
+        format!(".L{name}")
+    }
+}
+
+fn operands(
+    f: &mut fmt::Formatter<'_>,
+    mnemonic: &str,
+    src: (&Operand, AsmType),
+    dst: (&Operand, AsmType),
+) -> fmt::Result {
+    write!(f, "{}{}", mnemonic, " ".repeat(2))?;
+    src.0.fmt_as(f, src.1)?;
+    write!(f, ", ")?;
+    dst.0.fmt_as(f, dst.1)?;
+    writeln!(f)
+}
+
 impl Pretty for Instr {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        if let Instr::Label(name) = self {
+            return writeln!(f, "{}:", local_label(name));
+        }
         pretty::indent(f, depth)?;
 
         match self {
-            Instr::Mov { src, dst } => writeln!(f, "movl{}{}, {}", " ".repeat(2), dst, src),
+            Instr::Mov { ty, src, dst } => {
+                operands(f, &format!("mov{}", suffix(*ty)), (src, *ty), (dst, *ty))
+            }
+            Instr::Movsx { ty, src, dst } => operands(
+                f,
+                &format!("movs{}q", suffix(*ty)),
+                (src, *ty),
+                (dst, AsmType::Quadword),
+            ),
+            Instr::Lea { src, dst } => operands(
+                f,
+                "leaq",
+                (src, AsmType::Quadword),
+                (dst, AsmType::Quadword),
+            ),
+            Instr::Unary { op, ty, dst } => {
+                let name = match op {
+                    UnaryOp::Neg => "neg",
+                    UnaryOp::Not => "not",
+                };
+                write!(f, "{name}{}{}", suffix(*ty), " ".repeat(2))?;
+                dst.fmt_as(f, *ty)?;
+                writeln!(f)
+            }
+            Instr::Binary { op, ty, src, dst } => {
+                let name = match op {
+                    BinaryOp::Add => "add",
+                    BinaryOp::Sub => "sub",
+                    BinaryOp::Mult => "imul",
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Xor => "xor",
+                    BinaryOp::Shl => "shl",
+                    BinaryOp::Sar => "sar",
+                };
+                // The shift count register is always `%cl`.
+                let src_ty = match op {
+                    BinaryOp::Shl | BinaryOp::Sar => AsmType::Byte,
+                    _ => *ty,
+                };
+                operands(
+                    f,
+                    &format!("{name}{}", suffix(*ty)),
+                    (src, src_ty),
+                    (dst, *ty),
+                )
+            }
+            Instr::Cqo => writeln!(f, "cqo"),
+            Instr::Idiv { ty, src } => {
+                write!(f, "idiv{}{}", suffix(*ty), " ".repeat(2))?;
+                src.fmt_as(f, *ty)?;
+                writeln!(f)
+            }
+            Instr::Cmp { ty, src, dst } => {
+                operands(f, &format!("cmp{}", suffix(*ty)), (src, *ty), (dst, *ty))
+            }
+            Instr::Jmp(label) => writeln!(f, "jmp{}{}", " ".repeat(2), local_label(label)),
+            Instr::JmpCC(cond, label) => {
+                writeln!(f, "j{}{}{}", cond, " ".repeat(2), local_label(label))
+            }
+            Instr::SetCC(cond, dst) => {
+                write!(f, "set{}{}", cond, " ".repeat(2))?;
+                dst.fmt_as(f, AsmType::Byte)?;
+                writeln!(f)
+            }
+            Instr::Label(_) => unreachable!("labels are written without indentation"),
+            Instr::Push(operand) => writeln!(f, "pushq{}{}", " ".repeat(2), operand),
+            Instr::Pop(reg) => writeln!(f, "popq{}{}", " ".repeat(2), reg),
+            Instr::AllocateStack(n) => writeln!(f, "subq{}${}, %rsp", " ".repeat(2), n),
+            Instr::DeallocateStack(n) => writeln!(f, "addq{}${}, %rsp", " ".repeat(2), n),
+            Instr::Call(name) => {
+                let name = function_name(name);
+                if cfg!(target_os = "linux") {
+                    writeln!(f, "call{}{}@PLT", " ".repeat(2), name)
+                } else {
+                    writeln!(f, "call{}{}", " ".repeat(2), name)
+                }
+            }
+            Instr::IndirectCall(reg) => writeln!(f, "call{}*{}", " ".repeat(2), reg),
             Instr::Ret => writeln!(f, "ret"),
         }
     }
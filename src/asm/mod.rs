@@ -13,16 +13,134 @@ pub enum Function {
 
 pub struct InstrSeq(pub Vec<Instr>);
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AsmType {
+    Byte,
+    Longword,
+    Quadword,
+}
+
 pub enum Instr {
-    Mov { src: Operand, dst: Operand },
+    Mov {
+        ty: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sign-extends a byte or longword source into a quadword destination.
+    Movsx {
+        ty: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    Lea {
+        src: Operand,
+        dst: Operand,
+    },
+    Unary {
+        op: UnaryOp,
+        ty: AsmType,
+        dst: Operand,
+    },
+    Binary {
+        op: BinaryOp,
+        ty: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    /// Sign-extends `%rax` into `%rdx` for a quadword `Idiv`.
+    Cqo,
+    /// Divides `%rdx:%rax` by the operand, leaving the quotient in `%rax`
+    /// and the remainder in `%rdx`.
+    Idiv {
+        ty: AsmType,
+        src: Operand,
+    },
+    Cmp {
+        ty: AsmType,
+        src: Operand,
+        dst: Operand,
+    },
+    Jmp(String),
+    JmpCC(CondCode, String),
+    /// Sets the byte operand to 1 if the condition holds, and to 0 if not.
+    SetCC(CondCode, Operand),
+    Label(String),
+    Push(Operand),
+    Pop(Reg),
+    AllocateStack(i64),
+    DeallocateStack(i64),
+    Call(String),
+    IndirectCall(Reg),
     Ret,
 }
 
+#[derive(Clone, Copy)]
+pub enum CondCode {
+    E,
+    NE,
+    L,
+    LE,
+    G,
+    GE,
+    /// The unsigned comparisons, for addresses.
+    B,
+    BE,
+    A,
+    AE,
+}
+
+pub enum UnaryOp {
+    Neg,
+    Not,
+}
+
+pub enum BinaryOp {
+    Add,
+    Sub,
+    Mult,
+    And,
+    Or,
+    Xor,
+    /// Shifts by `%cl`, or by an immediate.
+    Shl,
+    Sar,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Reg {
+    Ax,
+    Cx,
+    Dx,
+    Di,
+    Si,
+    R8,
+    R9,
+    R11,
+    Sp,
+    Bp,
+}
+
+#[derive(Clone)]
 pub enum Operand {
     Imm(i64),
-    Register,
+    Reg(Reg),
+    /// `offset(%reg)`
+    Memory(Reg, i64),
+    /// The GOT slot holding the address of a function.
+    GotEntry(String),
+    /// Read-only data at a local label, addressed relative to `%rip`.
+    Data(String),
+}
+
+/// A null-terminated string literal in read-only data.
+pub struct StaticString {
+    pub label: String,
+    pub bytes: Vec<u8>,
 }
 
 pub enum Program {
-    Program(Function),
+    Program {
+        functions: Vec<Function>,
+        strings: Vec<StaticString>,
+    },
 }
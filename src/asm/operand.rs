@@ -1,7 +1,62 @@
-use crate::asm::Operand;
+use crate::asm::function::function_name;
+use crate::asm::instruction::local_label;
+use crate::asm::{AsmType, Operand, Reg};
 use crate::pretty::Pretty;
 use std::fmt;
 
+impl Reg {
+    fn name(&self, ty: AsmType) -> &'static str {
+        match (self, ty) {
+            (Reg::Ax, AsmType::Byte) => "%al",
+            (Reg::Cx, AsmType::Byte) => "%cl",
+            (Reg::Dx, AsmType::Byte) => "%dl",
+            (Reg::Di, AsmType::Byte) => "%dil",
+            (Reg::Si, AsmType::Byte) => "%sil",
+            (Reg::R8, AsmType::Byte) => "%r8b",
+            (Reg::R9, AsmType::Byte) => "%r9b",
+            (Reg::R11, AsmType::Byte) => "%r11b",
+            (Reg::Ax, AsmType::Longword) => "%eax",
+            (Reg::Ax, AsmType::Quadword) => "%rax",
+            (Reg::Cx, AsmType::Longword) => "%ecx",
+            (Reg::Cx, AsmType::Quadword) => "%rcx",
+            (Reg::Dx, AsmType::Longword) => "%edx",
+            (Reg::Dx, AsmType::Quadword) => "%rdx",
+            (Reg::Di, AsmType::Longword) => "%edi",
+            (Reg::Di, AsmType::Quadword) => "%rdi",
+            (Reg::Si, AsmType::Longword) => "%esi",
+            (Reg::Si, AsmType::Quadword) => "%rsi",
+            (Reg::R8, AsmType::Longword) => "%r8d",
+            (Reg::R8, AsmType::Quadword) => "%r8",
+            (Reg::R9, AsmType::Longword) => "%r9d",
+            (Reg::R9, AsmType::Quadword) => "%r9",
+            (Reg::R11, AsmType::Longword) => "%r11d",
+            (Reg::R11, AsmType::Quadword) => "%r11",
+            (Reg::Sp, _) => "%rsp",
+            (Reg::Bp, _) => "%rbp",
+        }
+    }
+}
+
+impl fmt::Display for Reg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name(AsmType::Quadword))
+    }
+}
+
+impl Operand {
+    /// Formats the operand with register names sized for `ty`.
+    pub fn fmt_as(&self, f: &mut fmt::Formatter<'_>, ty: AsmType) -> fmt::Result {
+        match self {
+            Operand::Imm(int) => write!(f, "${int}"),
+            Operand::Reg(reg) => write!(f, "{}", reg.name(ty)),
+            Operand::Memory(reg, 0) => write!(f, "({reg})"),
+            Operand::Memory(reg, offset) => write!(f, "{offset}({reg})"),
+            Operand::GotEntry(name) => write!(f, "{}@GOTPCREL(%rip)", function_name(name)),
+            Operand::Data(label) => write!(f, "{}(%rip)", local_label(label)),
+        }
+    }
+}
+
 impl fmt::Display for Operand {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.fmt_with(f, 0)
@@ -10,9 +65,6 @@ impl fmt::Display for Operand {
 
 impl Pretty for Operand {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
-        match self {
-            Operand::Imm(int) => write!(f, "${int}"),
-            Operand::Register => write!(f, "%eax"),
-        }
+        self.fmt_as(f, AsmType::Quadword)
     }
 }
@@ -1,4 +1,5 @@
-use crate::asm::Program;
+use crate::asm::instruction::local_label;
+use crate::asm::{Program, StaticString};
 use crate::pretty::Pretty;
 use std::fmt;
 
@@ -12,6 +13,30 @@ fn prolog(_f: &mut fmt::Formatter<'_>) -> fmt::Result {
     Ok(())
 }
 
+fn rodata_section() -> &'static str {
+    if cfg!(target_os = "macos") {
+        ".cstring"
+    } else {
+        ".section .rodata"
+    }
+}
+
+impl Pretty for StaticString {
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
+        writeln!(f, "{}:", local_label(&self.label))?;
+        write!(f, "{}.asciz \"", " ".repeat(4))?;
+        // Octal escapes, unlike `\x`, stop after three digits.
+        for &byte in &self.bytes {
+            match byte {
+                b'"' | b'\\' => write!(f, "\\{}", byte as char)?,
+                b' '..=b'~' => write!(f, "{}", byte as char)?,
+                _ => write!(f, "\\{byte:03o}")?,
+            }
+        }
+        writeln!(f, "\"")
+    }
+}
+
 impl fmt::Display for Program {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.fmt_with(f, 0)
@@ -21,7 +46,18 @@ impl fmt::Display for Program {
 impl Pretty for Program {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
         match self {
-            Program::Program(func) => func.fmt_with(f, 0)?,
+            Program::Program { functions, strings } => {
+                for func in functions {
+                    func.fmt_with(f, 0)?;
+                }
+
+                if !strings.is_empty() {
+                    writeln!(f, "{}{}", " ".repeat(4), rodata_section())?;
+                }
+                for string in strings {
+                    string.fmt_with(f, 0)?;
+                }
+            }
         }
 
         prolog(f)
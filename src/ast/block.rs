@@ -0,0 +1,46 @@
+use crate::ast::{Block, BlockItem};
+use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
+use std::fmt;
+
+impl fmt::Display for Block {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, 0)
+    }
+}
+
+impl HasSpan for Block {
+    fn span(&self) -> &Span {
+        &self.1
+    }
+}
+
+impl HasSpan for BlockItem {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Declaration(decl) => decl.span(),
+            Self::Statement(stmt) => stmt.span(),
+        }
+    }
+}
+
+impl Pretty for Block {
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        writeln!(f, "Block(")?;
+        for item in self.0.iter() {
+            pretty::indent(f, depth + 1)?;
+            item.fmt_with(f, depth + 1)?;
+        }
+        pretty::indent(f, depth)?;
+        writeln!(f, ")")
+    }
+}
+
+impl Pretty for BlockItem {
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match self {
+            Self::Declaration(decl) => decl.fmt_with(f, depth),
+            Self::Statement(stmt) => stmt.fmt_with(f, depth),
+        }
+    }
+}
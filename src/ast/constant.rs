@@ -0,0 +1,120 @@
+use crate::ast::{BinaryOp, Exp, ExpKind, UnaryOp};
+use crate::error::ConstantError;
+use crate::types::TypeKind;
+
+/// Evaluates an integer constant expression (C17 6.6p6), as needed for
+/// array sizes and `_Static_assert`. Every integer operand is promoted to
+/// `int`, so arithmetic wraps around at 32 bits as on the target, and an
+/// overflow, a division by zero or a bad shift count is an error unless
+/// it is in an operand that is not evaluated, as in `0 && 1 / 0`, as is a
+/// constant too large for `int`. The
+/// expression may be typed or not; a conversion to `char` truncates.
+pub fn evaluate(exp: &Exp) -> Result<i64, ConstantError> {
+    eval(exp, true).map(i64::from)
+}
+
+/// Evaluates `exp`, where `live` is false in an operand that is skipped
+/// at run time: its value does not matter, but it must still be constant.
+fn eval(exp: &Exp, live: bool) -> Result<i32, ConstantError> {
+    match &exp.kind {
+        ExpKind::ConstantInt(value) | ExpKind::ConstantChar(value, _) => {
+            match i32::try_from(*value) {
+                Ok(value) => Ok(value),
+                Err(_) => fail(live, ConstantError::TooLarge(*value, exp.span)),
+            }
+        }
+        ExpKind::Convert(inner) => {
+            let value = eval(inner, live)?;
+            Ok(match exp.ty.as_ref().map(|ty| &ty.kind) {
+                Some(TypeKind::Char) => i32::from(value as i8),
+                _ => value,
+            })
+        }
+        ExpKind::Unary(op, operand) => {
+            let value = eval(operand, live)?;
+            match op {
+                UnaryOp::Plus => Ok(value),
+                UnaryOp::Negate => checked(value.checked_neg(), value.wrapping_neg(), exp, live),
+                UnaryOp::Complement => Ok(!value),
+                UnaryOp::Not => Ok(i32::from(value == 0)),
+            }
+        }
+        ExpKind::Binary(op @ (BinaryOp::And | BinaryOp::Or), lhs, rhs) => {
+            let lhs = eval(lhs, live)? != 0;
+            // `&&` skips its right operand when the left is false, `||`
+            // when it is true.
+            let decided = lhs == (*op == BinaryOp::Or);
+            let rhs = eval(rhs, live && !decided)? != 0;
+            Ok(i32::from(if decided { lhs } else { rhs }))
+        }
+        ExpKind::Binary(op, lhs, rhs) => {
+            let (a, b) = (eval(lhs, live)?, eval(rhs, live)?);
+            binary(*op, a, b, exp, live)
+        }
+        ExpKind::Conditional(condition, then, otherwise) => {
+            let condition = eval(condition, live)? != 0;
+            let then = eval(then, live && condition)?;
+            let otherwise = eval(otherwise, live && !condition)?;
+            Ok(if condition { then } else { otherwise })
+        }
+        _ => Err(ConstantError::NotConstant(exp.span)),
+    }
+}
+
+fn binary(op: BinaryOp, a: i32, b: i32, exp: &Exp, live: bool) -> Result<i32, ConstantError> {
+    match op {
+        BinaryOp::Multiply => checked(a.checked_mul(b), a.wrapping_mul(b), exp, live),
+        BinaryOp::Add => checked(a.checked_add(b), a.wrapping_add(b), exp, live),
+        BinaryOp::Subtract => checked(a.checked_sub(b), a.wrapping_sub(b), exp, live),
+        BinaryOp::Divide | BinaryOp::Remainder => {
+            let remainder = op == BinaryOp::Remainder;
+            if b == 0 {
+                return fail(live, ConstantError::DivisionByZero(remainder, exp.span));
+            }
+            if remainder {
+                checked(a.checked_rem(b), a.wrapping_rem(b), exp, live)
+            } else {
+                checked(a.checked_div(b), a.wrapping_div(b), exp, live)
+            }
+        }
+        BinaryOp::ShiftLeft | BinaryOp::ShiftRight => {
+            if !(0..i32::BITS as i32).contains(&b) {
+                return fail(live, ConstantError::ShiftCount(i64::from(b), exp.span));
+            }
+            if op == BinaryOp::ShiftRight {
+                return Ok(a >> b);
+            }
+            // The result must be representable, which also rules out
+            // shifting a negative value.
+            let wide = i64::from(a) << b;
+            let shifted = a.wrapping_shl(b as u32);
+            let fits = a >= 0 && i32::try_from(wide).is_ok();
+            checked(fits.then_some(shifted), shifted, exp, live)
+        }
+        BinaryOp::Less => Ok(i32::from(a < b)),
+        BinaryOp::Greater => Ok(i32::from(a > b)),
+        BinaryOp::LessEqual => Ok(i32::from(a <= b)),
+        BinaryOp::GreaterEqual => Ok(i32::from(a >= b)),
+        BinaryOp::Equal => Ok(i32::from(a == b)),
+        BinaryOp::NotEqual => Ok(i32::from(a != b)),
+        BinaryOp::BitAnd => Ok(a & b),
+        BinaryOp::BitXor => Ok(a ^ b),
+        BinaryOp::BitOr => Ok(a | b),
+        BinaryOp::And | BinaryOp::Or => unreachable!("logical operators short-circuit"),
+    }
+}
+
+/// Fails with `error`, unless the operation is skipped and any value will
+/// do.
+fn fail(live: bool, error: ConstantError) -> Result<i32, ConstantError> {
+    if live { Err(error) } else { Ok(0) }
+}
+
+/// The result of an operation that may overflow: `result` if it did not,
+/// and otherwise an error reporting `wrapped`, unless `exp` is not live.
+fn checked(result: Option<i32>, wrapped: i32, exp: &Exp, live: bool) -> Result<i32, ConstantError> {
+    match result {
+        Some(value) => Ok(value),
+        None => fail(live, ConstantError::Overflow(i64::from(wrapped), exp.span)),
+    }
+}
@@ -0,0 +1,42 @@
+use crate::ast::Declaration;
+use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
+use std::fmt;
+
+impl fmt::Display for Declaration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_with(f, 0)
+    }
+}
+
+impl HasSpan for Declaration {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Variable { span, .. } => span,
+            Self::Function(func) => func.span(),
+        }
+    }
+}
+
+impl Pretty for Declaration {
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match self {
+            Self::Variable { name, ty, init, .. } => {
+                writeln!(f, "Variable(")?;
+                pretty::indent(f, depth + 1)?;
+                writeln!(f, "name={},", name)?;
+                pretty::indent(f, depth + 1)?;
+                writeln!(f, "type={},", ty)?;
+                pretty::indent(f, depth + 1)?;
+                write!(f, "init=")?;
+                match init {
+                    Some(init) => init.fmt_with(f, depth + 1)?,
+                    None => writeln!(f, "None")?,
+                }
+                pretty::indent(f, depth)?;
+                writeln!(f, ")")
+            }
+            Self::Function(func) => func.fmt_with(f, depth),
+        }
+    }
+}
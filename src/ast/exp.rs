@@ -1,17 +1,141 @@
-use crate::ast::{self, Exp};
-use crate::pretty::Pretty;
+use crate::ast::{self, BinaryOp, Exp, ExpKind, UnaryOp};
+use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
+use crate::types::Type;
 use std::fmt;
 
+impl fmt::Display for UnaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Self::Negate => "-",
+            Self::Plus => "+",
+            Self::Complement => "~",
+            Self::Not => "!",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl fmt::Display for BinaryOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let op = match self {
+            Self::Multiply => "*",
+            Self::Divide => "/",
+            Self::Remainder => "%",
+            Self::Add => "+",
+            Self::Subtract => "-",
+            Self::ShiftLeft => "<<",
+            Self::ShiftRight => ">>",
+            Self::Less => "<",
+            Self::Greater => ">",
+            Self::LessEqual => "<=",
+            Self::GreaterEqual => ">=",
+            Self::Equal => "==",
+            Self::NotEqual => "!=",
+            Self::BitAnd => "&",
+            Self::BitXor => "^",
+            Self::BitOr => "|",
+            Self::And => "&&",
+            Self::Or => "||",
+        };
+        write!(f, "{op}")
+    }
+}
+
+impl BinaryOp {
+    /// Whether the operator compares its operands, giving an `int` 0 or 1.
+    pub fn is_comparison(&self) -> bool {
+        matches!(
+            self,
+            Self::Less
+                | Self::Greater
+                | Self::LessEqual
+                | Self::GreaterEqual
+                | Self::Equal
+                | Self::NotEqual
+        )
+    }
+}
+
 impl fmt::Display for Exp {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         self.fmt_with(f, 0)
     }
 }
 
+fn fmt_node(
+    f: &mut fmt::Formatter<'_>,
+    depth: usize,
+    name: &str,
+    children: &[&Exp],
+) -> fmt::Result {
+    writeln!(f, "{name}(")?;
+    for child in children {
+        pretty::indent(f, depth + 1)?;
+        child.fmt_with(f, depth + 1)?;
+    }
+    pretty::indent(f, depth)?;
+    writeln!(f, ")")
+}
+
+impl Exp {
+    /// The type of a checked expression.
+    pub fn ty(&self) -> &Type {
+        self.ty
+            .as_ref()
+            .expect("expressions are type checked before use")
+    }
+}
+
+impl HasSpan for Exp {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl Pretty for ast::Exp {
-    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, _: usize) -> fmt::Result {
-        match self {
-            Self::ConstantInt(n) => writeln!(f, "ConstantInt({n})"),
+    fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
+        match &self.kind {
+            ExpKind::ConstantInt(n) => writeln!(f, "ConstantInt({n})"),
+            ExpKind::ConstantChar(n, prefix) => writeln!(f, "ConstantChar({prefix}{n})"),
+            ExpKind::String(bytes) => writeln!(f, "String(\"{}\")", bytes.escape_ascii()),
+            ExpKind::Var(name) => writeln!(f, "Var({name})"),
+            ExpKind::Unary(op, exp) => fmt_node(f, depth, &format!("{op:?}"), &[exp]),
+            ExpKind::Binary(op, lhs, rhs) => fmt_node(f, depth, &format!("{op:?}"), &[lhs, rhs]),
+            ExpKind::Conditional(condition, then, otherwise) => {
+                fmt_node(f, depth, "Conditional", &[condition, then, otherwise])
+            }
+            ExpKind::Assignment(lhs, rhs) => fmt_node(f, depth, "Assignment", &[lhs, rhs]),
+            ExpKind::AddressOf(exp) => fmt_node(f, depth, "AddressOf", &[exp]),
+            ExpKind::Dereference(exp) => fmt_node(f, depth, "Dereference", &[exp]),
+            ExpKind::Subscript(exp, index) => fmt_node(f, depth, "Subscript", &[exp, index]),
+            ExpKind::FunctionCall(callee, args) => {
+                let children = std::iter::once(callee.as_ref())
+                    .chain(args.iter())
+                    .collect::<Vec<_>>();
+                fmt_node(f, depth, "FunctionCall", &children)
+            }
+            ExpKind::VaStart(ap) => fmt_node(f, depth, "VaStart", &[ap]),
+            ExpKind::VaArg(ap, ty) => {
+                writeln!(f, "VaArg(")?;
+                pretty::indent(f, depth + 1)?;
+                ap.fmt_with(f, depth + 1)?;
+                pretty::indent(f, depth + 1)?;
+                writeln!(f, "type={ty}")?;
+                pretty::indent(f, depth)?;
+                writeln!(f, ")")
+            }
+            ExpKind::VaEnd(ap) => fmt_node(f, depth, "VaEnd", &[ap]),
+            ExpKind::VaCopy(dst, src) => fmt_node(f, depth, "VaCopy", &[dst, src]),
+            ExpKind::Convert(exp) => {
+                writeln!(f, "Convert(")?;
+                pretty::indent(f, depth + 1)?;
+                exp.fmt_with(f, depth + 1)?;
+                pretty::indent(f, depth + 1)?;
+                writeln!(f, "type={}", self.ty())?;
+                pretty::indent(f, depth)?;
+                writeln!(f, ")")
+            }
         }
     }
 }
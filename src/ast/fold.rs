@@ -0,0 +1,188 @@
+use crate::ast::{
+    Block, BlockItem, Declaration, Exp, ExpKind, Function, Param, Program, Statement, StatementKind,
+};
+use crate::types::Type;
+
+/// A traversal that consumes the AST and builds a new one, for passes that
+/// replace nodes outright. Each `fold_*` method defaults to the matching
+/// `walk_*` function, which folds the children and rebuilds the node with
+/// its span unchanged.
+pub trait Fold {
+    fn fold_program(&mut self, program: Program) -> Program {
+        walk_program(self, program)
+    }
+
+    fn fold_function(&mut self, function: Function) -> Function {
+        walk_function(self, function)
+    }
+
+    fn fold_param(&mut self, param: Param) -> Param {
+        walk_param(self, param)
+    }
+
+    fn fold_block(&mut self, block: Block) -> Block {
+        walk_block(self, block)
+    }
+
+    fn fold_block_item(&mut self, item: BlockItem) -> BlockItem {
+        walk_block_item(self, item)
+    }
+
+    fn fold_declaration(&mut self, declaration: Declaration) -> Declaration {
+        walk_declaration(self, declaration)
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        walk_statement(self, statement)
+    }
+
+    fn fold_exp(&mut self, exp: Exp) -> Exp {
+        walk_exp(self, exp)
+    }
+
+    fn fold_type(&mut self, ty: Type) -> Type {
+        ty
+    }
+}
+
+pub fn walk_program<F: Fold + ?Sized>(f: &mut F, program: Program) -> Program {
+    let Program::Program(functions, span) = program;
+    let functions = functions
+        .into_iter()
+        .map(|function| f.fold_function(function))
+        .collect();
+
+    Program::Program(functions, span)
+}
+
+pub fn walk_function<F: Fold + ?Sized>(f: &mut F, function: Function) -> Function {
+    let Function::Function {
+        return_type,
+        name,
+        params,
+        variadic,
+        format,
+        body,
+        span,
+    } = function;
+
+    Function::Function {
+        return_type: f.fold_type(return_type),
+        name,
+        params: params
+            .into_iter()
+            .map(|param| f.fold_param(param))
+            .collect(),
+        variadic,
+        format,
+        body: body.map(|body| f.fold_block(body)),
+        span,
+    }
+}
+
+pub fn walk_param<F: Fold + ?Sized>(f: &mut F, param: Param) -> Param {
+    Param {
+        ty: f.fold_type(param.ty),
+        ..param
+    }
+}
+
+pub fn walk_block<F: Fold + ?Sized>(f: &mut F, block: Block) -> Block {
+    let Block(items, span) = block;
+    let items = items
+        .into_iter()
+        .map(|item| f.fold_block_item(item))
+        .collect();
+
+    Block(items, span)
+}
+
+pub fn walk_block_item<F: Fold + ?Sized>(f: &mut F, item: BlockItem) -> BlockItem {
+    match item {
+        BlockItem::Declaration(declaration) => {
+            BlockItem::Declaration(f.fold_declaration(declaration))
+        }
+        BlockItem::Statement(statement) => BlockItem::Statement(f.fold_statement(statement)),
+    }
+}
+
+pub fn walk_declaration<F: Fold + ?Sized>(f: &mut F, declaration: Declaration) -> Declaration {
+    match declaration {
+        Declaration::Variable {
+            name,
+            ty,
+            init,
+            span,
+        } => Declaration::Variable {
+            name,
+            ty: f.fold_type(ty),
+            init: init.map(|init| f.fold_exp(init)),
+            span,
+        },
+        Declaration::Function(function) => Declaration::Function(f.fold_function(function)),
+    }
+}
+
+pub fn walk_statement<F: Fold + ?Sized>(f: &mut F, statement: Statement) -> Statement {
+    let kind = match statement.kind {
+        StatementKind::Return(exp) => StatementKind::Return(f.fold_exp(exp)),
+        StatementKind::Expression(exp) => StatementKind::Expression(f.fold_exp(exp)),
+        StatementKind::Null => StatementKind::Null,
+    };
+
+    Statement {
+        kind,
+        span: statement.span,
+    }
+}
+
+pub fn walk_exp<F: Fold + ?Sized>(f: &mut F, exp: Exp) -> Exp {
+    let kind = match exp.kind {
+        kind @ (ExpKind::ConstantInt(_)
+        | ExpKind::ConstantChar(..)
+        | ExpKind::String(_)
+        | ExpKind::Var(_)) => kind,
+        ExpKind::Unary(op, exp) => ExpKind::Unary(op, fold_boxed(f, exp)),
+        ExpKind::Binary(op, lhs, rhs) => {
+            ExpKind::Binary(op, fold_boxed(f, lhs), fold_boxed(f, rhs))
+        }
+        ExpKind::Conditional(condition, then, otherwise) => {
+            let condition = fold_boxed(f, condition);
+            let then = fold_boxed(f, then);
+            ExpKind::Conditional(condition, then, fold_boxed(f, otherwise))
+        }
+        ExpKind::Assignment(lhs, rhs) => {
+            ExpKind::Assignment(fold_boxed(f, lhs), fold_boxed(f, rhs))
+        }
+        ExpKind::AddressOf(exp) => ExpKind::AddressOf(fold_boxed(f, exp)),
+        ExpKind::Dereference(exp) => ExpKind::Dereference(fold_boxed(f, exp)),
+        ExpKind::Subscript(exp, index) => {
+            ExpKind::Subscript(fold_boxed(f, exp), fold_boxed(f, index))
+        }
+        ExpKind::FunctionCall(callee, args) => {
+            let callee = fold_boxed(f, callee);
+            let args = args.into_iter().map(|arg| f.fold_exp(arg)).collect();
+            ExpKind::FunctionCall(callee, args)
+        }
+        ExpKind::VaStart(ap) => ExpKind::VaStart(fold_boxed(f, ap)),
+        ExpKind::VaArg(ap, ty) => {
+            let ap = fold_boxed(f, ap);
+            ExpKind::VaArg(ap, f.fold_type(ty))
+        }
+        ExpKind::VaEnd(ap) => ExpKind::VaEnd(fold_boxed(f, ap)),
+        ExpKind::VaCopy(dst, src) => ExpKind::VaCopy(fold_boxed(f, dst), fold_boxed(f, src)),
+        ExpKind::Convert(exp) => ExpKind::Convert(fold_boxed(f, exp)),
+    };
+
+    Exp {
+        kind,
+        span: exp.span,
+        ty: exp.ty,
+    }
+}
+
+/// Folds a boxed child, reusing its allocation.
+fn fold_boxed<F: Fold + ?Sized>(f: &mut F, mut exp: Box<Exp>) -> Box<Exp> {
+    *exp = f.fold_exp(*exp);
+    exp
+}
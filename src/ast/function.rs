@@ -1,5 +1,7 @@
-use crate::ast::Function;
+use crate::ast::{Function, Param};
 use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
+use crate::types::{Type, TypeKind};
 use std::fmt;
 
 impl fmt::Display for Function {
@@ -8,16 +10,78 @@ impl fmt::Display for Function {
     }
 }
 
+impl Function {
+    /// The type of the function, from its return type and parameters.
+    pub fn ty(&self) -> Type {
+        let Self::Function {
+            return_type,
+            params,
+            variadic,
+            ..
+        } = self;
+
+        Type::new(TypeKind::Function {
+            params: params.iter().map(|p| p.ty.clone()).collect(),
+            variadic: *variadic,
+            ret: Box::new(return_type.clone()),
+        })
+    }
+}
+
+impl fmt::Display for Param {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}",
+            self.ty.declaration(self.name.as_deref().unwrap_or(""))
+        )
+    }
+}
+
+impl HasSpan for Function {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Function { span, .. } => span,
+        }
+    }
+}
+
+impl HasSpan for Param {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl Pretty for Function {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
         match self {
-            Self::Function { name, body } => {
+            Self::Function {
+                return_type,
+                name,
+                params,
+                variadic,
+                body,
+                ..
+            } => {
+                let mut params = params.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                if *variadic {
+                    params.push(String::from("..."));
+                }
+                let params = params.join(", ");
+
                 writeln!(f, "Function(")?;
                 pretty::indent(f, depth + 1)?;
+                writeln!(f, "return_type={},", return_type)?;
+                pretty::indent(f, depth + 1)?;
                 writeln!(f, "name={},", name)?;
                 pretty::indent(f, depth + 1)?;
+                writeln!(f, "params=[{}],", params)?;
+                pretty::indent(f, depth + 1)?;
                 write!(f, "body=")?;
-                body.fmt_with(f, depth + 1)?;
+                match body {
+                    Some(body) => body.fmt_with(f, depth + 1)?,
+                    None => writeln!(f, "None")?,
+                }
                 pretty::indent(f, depth)?;
                 writeln!(f, ")")
             }
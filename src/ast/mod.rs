@@ -1,20 +1,152 @@
+pub mod block;
+pub mod constant;
+pub mod declaration;
 pub mod exp;
+// The traversals are for the passes between parsing and code generation.
+pub mod fold;
 pub mod function;
 pub mod program;
 pub mod statement;
+pub mod visit;
+pub mod visit_mut;
+
+use crate::span::Span;
+use crate::token::CharPrefix;
+use crate::types::Type;
 
 pub enum Program {
-    FunctionDefinition(Function),
+    Program(Vec<Function>, Span),
 }
 
+/// A function declaration, or a definition when `body` is present.
 pub enum Function {
-    Function { name: String, body: Statement },
+    Function {
+        return_type: Type,
+        name: String,
+        params: Vec<Param>,
+        variadic: bool,
+        /// Its `format` attribute, if it has one.
+        format: Option<Format>,
+        body: Option<Block>,
+        span: Span,
+    },
+}
+
+/// `__attribute__((format(archetype, string, first)))`, which has calls
+/// to a function checked like calls to `printf` or `scanf`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Format {
+    pub archetype: Archetype,
+    /// The position of the format string among the parameters, from 1.
+    pub string: usize,
+    /// The position of the first argument the format string describes,
+    /// or 0 if they are not passed directly, as to `vprintf`.
+    pub first: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Archetype {
+    Printf,
+    Scanf,
+}
+
+pub struct Param {
+    pub name: Option<String>,
+    pub ty: Type,
+    pub span: Span,
+}
+
+pub struct Block(pub Vec<BlockItem>, pub Span);
+
+pub enum BlockItem {
+    Declaration(Declaration),
+    Statement(Statement),
 }
 
-pub enum Statement {
+pub enum Declaration {
+    /// A variable; its span is that of its declarator and initializer,
+    /// since one declaration may declare several.
+    Variable {
+        name: String,
+        ty: Type,
+        init: Option<Exp>,
+        span: Span,
+    },
+    Function(Function),
+}
+
+pub struct Statement {
+    pub kind: StatementKind,
+    pub span: Span,
+}
+
+pub enum StatementKind {
     Return(Exp),
+    Expression(Exp),
+    Null,
+}
+
+pub struct Exp {
+    pub kind: ExpKind,
+    pub span: Span,
+    /// The type of the expression, which type checking fills in.
+    pub ty: Option<Type>,
 }
 
-pub enum Exp {
+pub enum ExpKind {
     ConstantInt(i64),
+    /// A character constant; its value is already converted to the type the
+    /// prefix selects, `int` or `wchar_t`, the only prefixes parsed.
+    ConstantChar(i64, CharPrefix),
+    /// A string literal after concatenation, without its terminating null.
+    String(Vec<u8>),
+    Var(String),
+    Unary(UnaryOp, Box<Exp>),
+    Binary(BinaryOp, Box<Exp>, Box<Exp>),
+    /// `condition ? then : otherwise`
+    Conditional(Box<Exp>, Box<Exp>, Box<Exp>),
+    Assignment(Box<Exp>, Box<Exp>),
+    AddressOf(Box<Exp>),
+    Dereference(Box<Exp>),
+    Subscript(Box<Exp>, Box<Exp>),
+    FunctionCall(Box<Exp>, Vec<Exp>),
+    /// `__builtin_va_start(ap, last)`; `last` is not needed to find the
+    /// variadic arguments and is dropped.
+    VaStart(Box<Exp>),
+    VaArg(Box<Exp>, Type),
+    VaEnd(Box<Exp>),
+    VaCopy(Box<Exp>, Box<Exp>),
+    /// An implicit conversion of the operand to the type of this expression,
+    /// inserted by type checking.
+    Convert(Box<Exp>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnaryOp {
+    Negate,
+    Plus,
+    Complement,
+    Not,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BinaryOp {
+    Multiply,
+    Divide,
+    Remainder,
+    Add,
+    Subtract,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    BitAnd,
+    BitXor,
+    BitOr,
+    And,
+    Or,
 }
@@ -1,5 +1,6 @@
 use crate::ast::Program;
 use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
 use std::fmt;
 
 impl fmt::Display for Program {
@@ -8,13 +9,23 @@ impl fmt::Display for Program {
     }
 }
 
+impl HasSpan for Program {
+    fn span(&self) -> &Span {
+        match self {
+            Self::Program(_, span) => span,
+        }
+    }
+}
+
 impl Pretty for Program {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
         match self {
-            Self::FunctionDefinition(func) => {
+            Self::Program(functions, _) => {
                 writeln!(f, "Program(")?;
-                pretty::indent(f, depth + 1)?;
-                func.fmt_with(f, depth + 1)?;
+                for func in functions {
+                    pretty::indent(f, depth + 1)?;
+                    func.fmt_with(f, depth + 1)?;
+                }
                 pretty::indent(f, depth)?;
                 writeln!(f, ")")
             }
@@ -1,5 +1,6 @@
-use crate::ast::Statement;
+use crate::ast::{Statement, StatementKind};
 use crate::pretty::{self, Pretty};
+use crate::span::{HasSpan, Span};
 use std::fmt;
 
 impl fmt::Display for Statement {
@@ -8,16 +9,30 @@ impl fmt::Display for Statement {
     }
 }
 
+impl HasSpan for Statement {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
 impl Pretty for Statement {
     fn fmt_with(&self, f: &mut fmt::Formatter<'_>, depth: usize) -> fmt::Result {
-        match self {
-            Self::Return(exp) => {
+        match &self.kind {
+            StatementKind::Return(exp) => {
                 writeln!(f, "Return(")?;
                 pretty::indent(f, depth + 1)?;
                 exp.fmt_with(f, depth + 1)?;
                 pretty::indent(f, depth)?;
                 writeln!(f, ")")
             }
+            StatementKind::Expression(exp) => {
+                writeln!(f, "Expression(")?;
+                pretty::indent(f, depth + 1)?;
+                exp.fmt_with(f, depth + 1)?;
+                pretty::indent(f, depth)?;
+                writeln!(f, ")")
+            }
+            StatementKind::Null => writeln!(f, "Null"),
         }
     }
 }
@@ -0,0 +1,141 @@
+use crate::ast::{
+    Block, BlockItem, Declaration, Exp, ExpKind, Function, Param, Program, Statement, StatementKind,
+};
+use crate::types::Type;
+
+/// A traversal of the AST by shared reference. Each `visit_*` method
+/// defaults to the matching `walk_*` function, which visits the children
+/// of the node in source order, so an analysis overrides only the nodes it
+/// cares about and calls `walk_*` to keep descending.
+pub trait Visit {
+    fn visit_program(&mut self, program: &Program) {
+        walk_program(self, program);
+    }
+
+    fn visit_function(&mut self, function: &Function) {
+        walk_function(self, function);
+    }
+
+    fn visit_param(&mut self, param: &Param) {
+        walk_param(self, param);
+    }
+
+    fn visit_block(&mut self, block: &Block) {
+        walk_block(self, block);
+    }
+
+    fn visit_block_item(&mut self, item: &BlockItem) {
+        walk_block_item(self, item);
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        walk_declaration(self, declaration);
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        walk_statement(self, statement);
+    }
+
+    fn visit_exp(&mut self, exp: &Exp) {
+        walk_exp(self, exp);
+    }
+
+    /// Types have no child nodes; this is called wherever one is written.
+    fn visit_type(&mut self, _ty: &Type) {}
+}
+
+pub fn walk_program<V: Visit + ?Sized>(v: &mut V, program: &Program) {
+    let Program::Program(functions, _) = program;
+    for function in functions {
+        v.visit_function(function);
+    }
+}
+
+pub fn walk_function<V: Visit + ?Sized>(v: &mut V, function: &Function) {
+    let Function::Function {
+        return_type,
+        params,
+        body,
+        ..
+    } = function;
+    v.visit_type(return_type);
+    for param in params {
+        v.visit_param(param);
+    }
+    if let Some(body) = body {
+        v.visit_block(body);
+    }
+}
+
+pub fn walk_param<V: Visit + ?Sized>(v: &mut V, param: &Param) {
+    v.visit_type(&param.ty);
+}
+
+pub fn walk_block<V: Visit + ?Sized>(v: &mut V, block: &Block) {
+    for item in &block.0 {
+        v.visit_block_item(item);
+    }
+}
+
+pub fn walk_block_item<V: Visit + ?Sized>(v: &mut V, item: &BlockItem) {
+    match item {
+        BlockItem::Declaration(declaration) => v.visit_declaration(declaration),
+        BlockItem::Statement(statement) => v.visit_statement(statement),
+    }
+}
+
+pub fn walk_declaration<V: Visit + ?Sized>(v: &mut V, declaration: &Declaration) {
+    match declaration {
+        Declaration::Variable { ty, init, .. } => {
+            v.visit_type(ty);
+            if let Some(init) = init {
+                v.visit_exp(init);
+            }
+        }
+        Declaration::Function(function) => v.visit_function(function),
+    }
+}
+
+pub fn walk_statement<V: Visit + ?Sized>(v: &mut V, statement: &Statement) {
+    match &statement.kind {
+        StatementKind::Return(exp) | StatementKind::Expression(exp) => v.visit_exp(exp),
+        StatementKind::Null => {}
+    }
+}
+
+pub fn walk_exp<V: Visit + ?Sized>(v: &mut V, exp: &Exp) {
+    match &exp.kind {
+        ExpKind::ConstantInt(_)
+        | ExpKind::ConstantChar(..)
+        | ExpKind::String(_)
+        | ExpKind::Var(_) => {}
+        ExpKind::AddressOf(exp)
+        | ExpKind::Dereference(exp)
+        | ExpKind::VaStart(exp)
+        | ExpKind::VaEnd(exp)
+        | ExpKind::Convert(exp)
+        | ExpKind::Unary(_, exp) => v.visit_exp(exp),
+        ExpKind::Assignment(a, b)
+        | ExpKind::Binary(_, a, b)
+        | ExpKind::Subscript(a, b)
+        | ExpKind::VaCopy(a, b) => {
+            v.visit_exp(a);
+            v.visit_exp(b);
+        }
+        ExpKind::Conditional(condition, then, otherwise) => {
+            v.visit_exp(condition);
+            v.visit_exp(then);
+            v.visit_exp(otherwise);
+        }
+        ExpKind::FunctionCall(callee, args) => {
+            v.visit_exp(callee);
+            for arg in args {
+                v.visit_exp(arg);
+            }
+        }
+        ExpKind::VaArg(ap, ty) => {
+            v.visit_exp(ap);
+            v.visit_type(ty);
+        }
+    }
+}
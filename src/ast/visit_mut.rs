@@ -0,0 +1,139 @@
+use crate::ast::{
+    Block, BlockItem, Declaration, Exp, ExpKind, Function, Param, Program, Statement, StatementKind,
+};
+use crate::types::Type;
+
+/// A traversal of the AST by mutable reference, for passes that rewrite
+/// nodes in place. It mirrors `Visit`: each `visit_*_mut` method defaults
+/// to the matching `walk_*_mut` function.
+pub trait VisitMut {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        walk_program_mut(self, program);
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        walk_function_mut(self, function);
+    }
+
+    fn visit_param_mut(&mut self, param: &mut Param) {
+        walk_param_mut(self, param);
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        walk_block_mut(self, block);
+    }
+
+    fn visit_block_item_mut(&mut self, item: &mut BlockItem) {
+        walk_block_item_mut(self, item);
+    }
+
+    fn visit_declaration_mut(&mut self, declaration: &mut Declaration) {
+        walk_declaration_mut(self, declaration);
+    }
+
+    fn visit_statement_mut(&mut self, statement: &mut Statement) {
+        walk_statement_mut(self, statement);
+    }
+
+    fn visit_exp_mut(&mut self, exp: &mut Exp) {
+        walk_exp_mut(self, exp);
+    }
+
+    fn visit_type_mut(&mut self, _ty: &mut Type) {}
+}
+
+pub fn walk_program_mut<V: VisitMut + ?Sized>(v: &mut V, program: &mut Program) {
+    let Program::Program(functions, _) = program;
+    for function in functions {
+        v.visit_function_mut(function);
+    }
+}
+
+pub fn walk_function_mut<V: VisitMut + ?Sized>(v: &mut V, function: &mut Function) {
+    let Function::Function {
+        return_type,
+        params,
+        body,
+        ..
+    } = function;
+    v.visit_type_mut(return_type);
+    for param in params {
+        v.visit_param_mut(param);
+    }
+    if let Some(body) = body {
+        v.visit_block_mut(body);
+    }
+}
+
+pub fn walk_param_mut<V: VisitMut + ?Sized>(v: &mut V, param: &mut Param) {
+    v.visit_type_mut(&mut param.ty);
+}
+
+pub fn walk_block_mut<V: VisitMut + ?Sized>(v: &mut V, block: &mut Block) {
+    for item in &mut block.0 {
+        v.visit_block_item_mut(item);
+    }
+}
+
+pub fn walk_block_item_mut<V: VisitMut + ?Sized>(v: &mut V, item: &mut BlockItem) {
+    match item {
+        BlockItem::Declaration(declaration) => v.visit_declaration_mut(declaration),
+        BlockItem::Statement(statement) => v.visit_statement_mut(statement),
+    }
+}
+
+pub fn walk_declaration_mut<V: VisitMut + ?Sized>(v: &mut V, declaration: &mut Declaration) {
+    match declaration {
+        Declaration::Variable { ty, init, .. } => {
+            v.visit_type_mut(ty);
+            if let Some(init) = init {
+                v.visit_exp_mut(init);
+            }
+        }
+        Declaration::Function(function) => v.visit_function_mut(function),
+    }
+}
+
+pub fn walk_statement_mut<V: VisitMut + ?Sized>(v: &mut V, statement: &mut Statement) {
+    match &mut statement.kind {
+        StatementKind::Return(exp) | StatementKind::Expression(exp) => v.visit_exp_mut(exp),
+        StatementKind::Null => {}
+    }
+}
+
+pub fn walk_exp_mut<V: VisitMut + ?Sized>(v: &mut V, exp: &mut Exp) {
+    match &mut exp.kind {
+        ExpKind::ConstantInt(_)
+        | ExpKind::ConstantChar(..)
+        | ExpKind::String(_)
+        | ExpKind::Var(_) => {}
+        ExpKind::AddressOf(exp)
+        | ExpKind::Dereference(exp)
+        | ExpKind::VaStart(exp)
+        | ExpKind::VaEnd(exp)
+        | ExpKind::Convert(exp)
+        | ExpKind::Unary(_, exp) => v.visit_exp_mut(exp),
+        ExpKind::Assignment(a, b)
+        | ExpKind::Binary(_, a, b)
+        | ExpKind::Subscript(a, b)
+        | ExpKind::VaCopy(a, b) => {
+            v.visit_exp_mut(a);
+            v.visit_exp_mut(b);
+        }
+        ExpKind::Conditional(condition, then, otherwise) => {
+            v.visit_exp_mut(condition);
+            v.visit_exp_mut(then);
+            v.visit_exp_mut(otherwise);
+        }
+        ExpKind::FunctionCall(callee, args) => {
+            v.visit_exp_mut(callee);
+            for arg in args {
+                v.visit_exp_mut(arg);
+            }
+        }
+        ExpKind::VaArg(ap, ty) => {
+            v.visit_exp_mut(ap);
+            v.visit_type_mut(ty);
+        }
+    }
+}
@@ -0,0 +1,396 @@
+use crate::error::format_diagnostic;
+use crate::source_map::SourceMap;
+use crate::span::{HasSpan, Span};
+use std::collections::HashMap;
+use std::fmt;
+
+/// A warning that options and pragmas can enable, disable or make an
+/// error by name, as `-Wunused-variable` does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Warning {
+    Multichar,
+    DirectiveMessage,
+    MacroRedefined,
+    ExtraTokens,
+    IncludeNextOutsideHeader,
+    UnknownWarningOption,
+    DiscardsQualifiers,
+    ImplicitFunctionDeclaration,
+    ReturnType,
+    UnusedVariable,
+    UnusedParameter,
+    UnreachableCode,
+    Uninitialized,
+    SometimesUninitialized,
+    Conversion,
+    Overflow,
+    DivisionByZero,
+    // There are no unsigned or 64-bit integer types yet for these to be
+    // about; they are accepted so that build systems passing them work.
+    SignCompare,
+    SignConversion,
+    Shorten64To32,
+    Format,
+    FormatExtraArgs,
+    FormatInvalidSpecifier,
+}
+
+/// Every warning, with the name its options spell.
+const WARNINGS: &[(Warning, &str)] = &[
+    (Warning::Multichar, "multichar"),
+    (Warning::DirectiveMessage, "#warnings"),
+    (Warning::MacroRedefined, "macro-redefined"),
+    (Warning::ExtraTokens, "extra-tokens"),
+    (
+        Warning::IncludeNextOutsideHeader,
+        "include-next-outside-header",
+    ),
+    (Warning::UnknownWarningOption, "unknown-warning-option"),
+    (
+        Warning::DiscardsQualifiers,
+        "incompatible-pointer-types-discards-qualifiers",
+    ),
+    (
+        Warning::ImplicitFunctionDeclaration,
+        "implicit-function-declaration",
+    ),
+    (Warning::ReturnType, "return-type"),
+    (Warning::UnusedVariable, "unused-variable"),
+    (Warning::UnusedParameter, "unused-parameter"),
+    (Warning::UnreachableCode, "unreachable-code"),
+    (Warning::Uninitialized, "uninitialized"),
+    (Warning::SometimesUninitialized, "sometimes-uninitialized"),
+    (Warning::Conversion, "conversion"),
+    (Warning::Overflow, "overflow"),
+    (Warning::DivisionByZero, "division-by-zero"),
+    (Warning::SignCompare, "sign-compare"),
+    (Warning::SignConversion, "sign-conversion"),
+    (Warning::Shorten64To32, "shorten-64-to-32"),
+    (Warning::Format, "format"),
+    (Warning::FormatExtraArgs, "format-extra-args"),
+    (Warning::FormatInvalidSpecifier, "format-invalid-specifier"),
+];
+
+/// The groups of warnings that one option controls together.
+const GROUPS: &[(&str, &[Warning])] = &[
+    (
+        "all",
+        &[
+            Warning::UnusedVariable,
+            Warning::UnreachableCode,
+            Warning::Uninitialized,
+            Warning::SometimesUninitialized,
+        ],
+    ),
+    ("extra", &[Warning::UnusedParameter]),
+    (
+        "format",
+        &[
+            Warning::Format,
+            Warning::FormatExtraArgs,
+            Warning::FormatInvalidSpecifier,
+        ],
+    ),
+    ("unused", &[Warning::UnusedVariable]),
+];
+
+impl Warning {
+    pub fn name(self) -> &'static str {
+        WARNINGS
+            .iter()
+            .find(|(warning, _)| *warning == self)
+            .map(|(_, name)| *name)
+            .expect("every warning has a name")
+    }
+
+    /// The warnings an option names: a group, or one warning. A group may
+    /// share its name with the warning at its head, as `format` does.
+    pub fn named(name: &str) -> Option<Vec<Warning>> {
+        if let Some((_, warnings)) = GROUPS.iter().find(|(group, _)| *group == name) {
+            return Some(warnings.to_vec());
+        }
+        WARNINGS
+            .iter()
+            .find(|(_, n)| *n == name)
+            .map(|(warning, _)| vec![*warning])
+    }
+
+    /// Whether the warning is reported unless an option disables it.
+    fn enabled_by_default(self) -> bool {
+        !matches!(
+            self,
+            Warning::UnusedVariable
+                | Warning::UnusedParameter
+                | Warning::UnreachableCode
+                | Warning::Uninitialized
+                | Warning::SometimesUninitialized
+                | Warning::Conversion
+                | Warning::SignCompare
+                | Warning::SignConversion
+                | Warning::Shorten64To32
+        )
+    }
+}
+
+/// What a warning is reported as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Ignored,
+    Warning,
+    Error,
+}
+
+/// How options have set one warning: whether it is enabled and whether
+/// it is an error, where they say.
+#[derive(Debug, Clone, Copy, Default)]
+struct Setting {
+    enabled: Option<bool>,
+    error: Option<bool>,
+}
+
+/// A `#pragma GCC diagnostic` directive.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Pragma {
+    /// Saves the settings of every warning, for the next `pop`.
+    Push,
+    Pop,
+    /// Sets warnings to be ignored, warnings or errors.
+    Set(Vec<Warning>, Severity),
+}
+
+/// The warning options given on the command line.
+#[derive(Debug, Clone, Default)]
+pub struct WarningOptions {
+    settings: HashMap<Warning, Setting>,
+    /// Whether `-Werror` makes every warning an error.
+    all_errors: bool,
+    /// Whether `-w` suppresses every warning.
+    suppress: bool,
+}
+
+impl WarningOptions {
+    /// Applies the option `-W<option>`: a warning or group name, with
+    /// `no-` to disable it, or `error` to make every warning an error, or
+    /// `error=<name>` to make one an error. Returns false if the name is
+    /// unknown, which leaves the options as they were.
+    pub fn apply(&mut self, option: &str) -> bool {
+        let (negated, option) = match option.strip_prefix("no-") {
+            Some(option) => (true, option),
+            None => (false, option),
+        };
+        if option == "error" {
+            self.all_errors = !negated;
+            return true;
+        }
+        let (error, name) = match option.strip_prefix("error=") {
+            Some(name) => (true, name),
+            None => (false, option),
+        };
+        let Some(warnings) = Warning::named(name) else {
+            return false;
+        };
+        for warning in warnings {
+            let setting = self.settings.entry(warning).or_default();
+            match (error, negated) {
+                // `-Wno-error=<name>` keeps it a warning even with
+                // `-Werror`, but does not enable it.
+                (true, negated) => {
+                    setting.error = Some(!negated);
+                    if !negated {
+                        setting.enabled = Some(true);
+                    }
+                }
+                (false, negated) => setting.enabled = Some(!negated),
+            }
+        }
+
+        true
+    }
+
+    /// Suppresses every warning, as `-w` does.
+    pub fn suppress_all(&mut self) {
+        self.suppress = true;
+    }
+
+    fn severity(&self, settings: &HashMap<Warning, Setting>, warning: Warning) -> Severity {
+        let setting = settings.get(&warning).copied().unwrap_or_default();
+        if self.suppress || !setting.enabled.unwrap_or(warning.enabled_by_default()) {
+            Severity::Ignored
+        } else if setting.error.unwrap_or(self.all_errors) {
+            Severity::Error
+        } else {
+            Severity::Warning
+        }
+    }
+}
+
+/// A diagnostic that does not stop compilation unless an option makes it
+/// an error.
+pub trait Warn: HasSpan {
+    /// The warning that decides how it is reported.
+    fn warning(&self) -> Warning;
+
+    /// What it says, without its severity.
+    fn message(&self) -> String;
+
+    /// Notes pointing to other source that it is about, each reported
+    /// after it if it is.
+    fn notes(&self) -> Vec<(String, Span)> {
+        Vec::new()
+    }
+
+    /// Where it is shown: its span, unless the source is needed to find
+    /// a narrower one.
+    fn locate(&self, _sources: &SourceMap) -> Span {
+        *self.span()
+    }
+}
+
+/// Reports warnings as the command-line options and the diagnostic
+/// pragmas in effect where each is found decide.
+#[derive(Default)]
+pub struct Diagnostics {
+    options: WarningOptions,
+    /// The settings in effect after each diagnostic pragma, by where the
+    /// pragma is in translation order, in that order.
+    states: Vec<(Vec<usize>, HashMap<Warning, Setting>)>,
+    /// The settings and the stack of pushed ones after the last pragma.
+    last: (HashMap<Warning, Setting>, Vec<HashMap<Warning, Setting>>),
+    /// Whether a warning was reported as an error.
+    failed: bool,
+}
+
+impl Diagnostics {
+    pub fn new(options: WarningOptions) -> Self {
+        Self {
+            last: (options.settings.clone(), Vec::new()),
+            options,
+            ..Self::default()
+        }
+    }
+
+    /// Adds the diagnostic pragmas read after any added before, each at the
+    /// offset of its directive and in translation order, recording the
+    /// settings each leaves in effect.
+    pub fn add_pragmas(&mut self, sources: &SourceMap, pragmas: &[(usize, Pragma)]) {
+        let (settings, saved) = &mut self.last;
+        for (at, pragma) in pragmas {
+            match pragma {
+                Pragma::Push => saved.push(settings.clone()),
+                // A `pop` without a `push` does nothing, as in GCC.
+                Pragma::Pop => {
+                    if let Some(popped) = saved.pop() {
+                        *settings = popped;
+                    }
+                }
+                Pragma::Set(warnings, severity) => {
+                    for warning in warnings {
+                        settings.insert(
+                            *warning,
+                            Setting {
+                                enabled: Some(*severity != Severity::Ignored),
+                                error: Some(*severity == Severity::Error),
+                            },
+                        );
+                    }
+                }
+            }
+            self.states
+                .push((sources.translation_order(*at), settings.clone()));
+        }
+    }
+
+    /// What `warning` is reported as at the offset `pos`: the options
+    /// decide, as the pragmas before `pos` have changed them.
+    pub fn severity(&self, sources: &SourceMap, warning: Warning, pos: usize) -> Severity {
+        let key = sources.translation_order(pos);
+        let before = self.states.partition_point(|(at, _)| *at < key);
+        let settings = match before.checked_sub(1) {
+            Some(last) => &self.states[last].1,
+            None => &self.options.settings,
+        };
+
+        self.options.severity(settings, warning)
+    }
+
+    /// Renders `warning` as its severity where it was found, if at all.
+    pub fn report(&mut self, sources: &SourceMap, warning: &impl Warn) {
+        let severity = self.severity(sources, warning.warning(), warning.span().start);
+        let span = warning.locate(sources);
+        if let Some(rendered) = self.rendered(severity, warning.warning(), warning.message(), span)
+        {
+            eprint!("{}", format_diagnostic(sources, &rendered));
+            for (message, span) in warning.notes() {
+                eprint!("{}", format_diagnostic(sources, &Note { message, span }));
+            }
+        }
+    }
+
+    /// The warning as reported, unless it is ignored, noting whether it
+    /// fails the compilation.
+    fn rendered(
+        &mut self,
+        severity: Severity,
+        warning: Warning,
+        message: String,
+        span: Span,
+    ) -> Option<Rendered> {
+        self.failed |= severity == Severity::Error;
+        (severity != Severity::Ignored).then_some(Rendered {
+            severity,
+            warning,
+            message,
+            span,
+        })
+    }
+
+    /// Whether a warning was reported as an error, which fails the
+    /// compilation.
+    pub fn failed(&self) -> bool {
+        self.failed
+    }
+}
+
+/// A warning as reported, with its severity and the option controlling
+/// it, as `warning: unused variable 'x' [-Wunused-variable]`.
+struct Rendered {
+    severity: Severity,
+    warning: Warning,
+    message: String,
+    span: Span,
+}
+
+impl HasSpan for Rendered {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl fmt::Display for Rendered {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = self.warning.name();
+        match self.severity {
+            Severity::Error => write!(f, "error: {} [-Werror,-W{name}]", self.message),
+            _ => write!(f, "warning: {} [-W{name}]", self.message),
+        }
+    }
+}
+
+/// A note reported after a warning, as `note: variable 'x' is declared
+/// here`.
+struct Note {
+    message: String,
+    span: Span,
+}
+
+impl HasSpan for Note {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl fmt::Display for Note {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "note: {}", self.message)
+    }
+}
@@ -1,12 +1,33 @@
+use crate::diagnostics::{Warn, Warning};
+use crate::lexer::{Lexer, string_literal_offsets};
+use crate::source_map::SourceMap;
 use crate::span::{HasSpan, Span};
 use crate::token::{Token, TokenKind};
+use crate::types::Type;
 use std::fmt;
+use std::ops::Range;
 
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum LexerErrorKind {
     UnexpectedCharacter(char),
     InvalidConstSuffix,
     InvalidIntegerLiteral,
+    UnknownEscape(char),
+    EscapeOutOfRange,
+    MissingHexDigits,
+    EmptyCharConstant,
+    UnterminatedCharConstant,
+    UnterminatedString,
+    UnsupportedStringPrefix,
+    UnterminatedComment,
+    UnterminatedHeaderName(char),
+    CharConstantTooLong,
+    CharacterTooLarge,
+}
+
+#[derive(Debug, Clone)]
+pub enum LexerWarningKind {
+    MultiCharConstant,
 }
 
 #[derive(Debug)]
@@ -28,7 +49,7 @@ pub struct LexerError {
 }
 
 impl HasSpan for LexerError {
-    fn span<'a>(&'a self) -> &'a Span {
+    fn span(&self) -> &Span {
         &self.span
     }
 }
@@ -37,28 +58,132 @@ impl fmt::Display for LexerError {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match &self.kind {
             LexerErrorKind::UnexpectedCharacter(found) => write!(f, "unexpected token '{found}'"),
-            LexerErrorKind::InvalidConstSuffix => write!(f, "invalid const suffic"),
+            LexerErrorKind::InvalidConstSuffix => write!(f, "invalid suffix on integer constant"),
             LexerErrorKind::InvalidIntegerLiteral => {
                 write!(f, "unable to convert integer literal value to int")
             }
+            LexerErrorKind::UnknownEscape(ch) => write!(f, "unknown escape sequence '\\{ch}'"),
+            LexerErrorKind::EscapeOutOfRange => write!(f, "escape sequence out of range"),
+            LexerErrorKind::MissingHexDigits => write!(f, "\\x used with no following hex digits"),
+            LexerErrorKind::EmptyCharConstant => write!(f, "empty character constant"),
+            LexerErrorKind::UnterminatedCharConstant => {
+                write!(f, "missing terminating ' character")
+            }
+            LexerErrorKind::UnterminatedString => write!(f, "missing terminating \" character"),
+            LexerErrorKind::UnsupportedStringPrefix => {
+                write!(f, "only plain and u8 string literals are supported")
+            }
+            LexerErrorKind::UnterminatedComment => write!(f, "unterminated comment"),
+            LexerErrorKind::UnterminatedHeaderName(close) => {
+                write!(f, "missing terminating {close} character")
+            }
+            LexerErrorKind::CharConstantTooLong => {
+                write!(f, "character constant too long for its type")
+            }
+            LexerErrorKind::CharacterTooLarge => {
+                write!(
+                    f,
+                    "character too large for enclosing character literal type"
+                )
+            }
+        }
+    }
+}
+
+/// A diagnostic from the lexer that does not stop compilation.
+#[derive(Debug)]
+pub struct LexerWarning {
+    pub kind: LexerWarningKind,
+    pub span: Span,
+}
+
+impl HasSpan for LexerWarning {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl fmt::Display for LexerWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LexerWarningKind::MultiCharConstant => write!(f, "multi-character character constant"),
         }
     }
 }
 
+impl fmt::Display for LexerWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning: {}", self.kind)
+    }
+}
+
 #[derive(Debug)]
 pub enum ParseError {
     UnexpectedEof(Span),
     UnexpectedToken(Token, TokenKind),
     UnexpectedTrailing(Token),
+    InvalidTypeSpecifier(Token),
+    InvalidRestrict(Span),
+    QualifiedVoidParameter(Span),
+    InvalidVoidParameter(Span),
+    VariadicWithoutNamedParameter(Span),
+    InvalidReturnType(Span),
+    InvalidArrayElement(Span),
+    InvalidArraySize(Span),
+    VoidVariable(Span),
+    ArraySizeMissing(Span),
+    InvalidArrayInitializer(Span),
+    InitializerStringTooLong(Span),
+    MissingIdentifier(Span),
+    InvalidTypeName(Span),
+    UnsupportedFileScopeVariable(Span),
+    VaStartOutsideVariadic(Span),
+    UnsupportedVaArgType(Span),
+    UnsupportedCharPrefix(Span),
+    UnknownAttribute(String, Span),
+    UnsupportedFormatArchetype(String, Span),
+    /// A `format` attribute argument, by position from 1, that is not the
+    /// position of a parameter it can be.
+    FormatArgumentOutOfBounds(usize, Span),
+    FormatStringNotString(Span),
+    FormatRequiresVariadic(Span),
+    /// A `_Static_assert` whose condition is zero, with its message.
+    StaticAssertionFailed(Vec<u8>, Span),
+    Constant(ConstantError),
     Lexer(LexerError),
 }
 
 impl HasSpan for ParseError {
-    fn span<'a>(&'a self) -> &'a Span {
+    fn span(&self) -> &Span {
         match self {
             Self::UnexpectedEof(span) => span,
             Self::UnexpectedToken(token, _) => &token.span,
             Self::UnexpectedTrailing(token) => &token.span,
+            Self::InvalidTypeSpecifier(token) => &token.span,
+            Self::InvalidRestrict(span) => span,
+            Self::QualifiedVoidParameter(span) => span,
+            Self::InvalidVoidParameter(span) => span,
+            Self::VariadicWithoutNamedParameter(span) => span,
+            Self::InvalidReturnType(span) => span,
+            Self::InvalidArrayElement(span) => span,
+            Self::InvalidArraySize(span) => span,
+            Self::VoidVariable(span) => span,
+            Self::ArraySizeMissing(span) => span,
+            Self::InvalidArrayInitializer(span) => span,
+            Self::InitializerStringTooLong(span) => span,
+            Self::MissingIdentifier(span) => span,
+            Self::InvalidTypeName(span) => span,
+            Self::UnsupportedFileScopeVariable(span) => span,
+            Self::VaStartOutsideVariadic(span) => span,
+            Self::UnsupportedVaArgType(span) => span,
+            Self::UnsupportedCharPrefix(span) => span,
+            Self::UnknownAttribute(_, span) => span,
+            Self::UnsupportedFormatArchetype(_, span) => span,
+            Self::FormatArgumentOutOfBounds(_, span) => span,
+            Self::FormatStringNotString(span) => span,
+            Self::FormatRequiresVariadic(span) => span,
+            Self::StaticAssertionFailed(_, span) => span,
+            Self::Constant(e) => e.span(),
             Self::Lexer(e) => e.span(),
         }
     }
@@ -72,7 +197,76 @@ impl fmt::Display for ParseError {
                 write!(f, "expected '{}' but found '{}'", expected, token.kind)
             }
             ParseError::UnexpectedTrailing(token) => write!(f, "unexpected trailing '{}'", token.kind),
-            ParseError::Lexer(error) => write!(f, "{}", error.to_string()),
+            ParseError::InvalidTypeSpecifier(token) => {
+                write!(
+                    f,
+                    "cannot combine '{}' with previous type specifier",
+                    token.kind
+                )
+            }
+            ParseError::InvalidRestrict(_) => write!(f, "restrict requires a pointer type"),
+            ParseError::QualifiedVoidParameter(_) => {
+                write!(f, "'void' as the only parameter may not be qualified")
+            }
+            ParseError::InvalidVoidParameter(_) => {
+                write!(f, "'void' must be the only parameter and unnamed")
+            }
+            ParseError::VariadicWithoutNamedParameter(_) => {
+                write!(f, "'...' requires a named parameter before it")
+            }
+            ParseError::InvalidReturnType(_) => {
+                write!(f, "function cannot return a function or array type")
+            }
+            ParseError::InvalidArrayElement(_) => {
+                write!(f, "array elements must have complete object type")
+            }
+            ParseError::InvalidArraySize(_) => write!(f, "array size must be a positive integer"),
+            ParseError::VoidVariable(_) => write!(f, "variable has incomplete type 'void'"),
+            ParseError::ArraySizeMissing(_) => write!(f, "array size missing in declaration"),
+            ParseError::InvalidArrayInitializer(_) => {
+                write!(
+                    f,
+                    "only 'char' arrays can be initialized, from a string literal"
+                )
+            }
+            ParseError::InitializerStringTooLong(_) => {
+                write!(f, "initializer-string for array is too long")
+            }
+            ParseError::MissingIdentifier(_) => write!(f, "expected identifier"),
+            ParseError::InvalidTypeName(_) => write!(f, "type name cannot declare an identifier"),
+            ParseError::UnsupportedFileScopeVariable(_) => {
+                write!(f, "file-scope variables are not supported")
+            }
+            ParseError::VaStartOutsideVariadic(_) => {
+                write!(f, "'va_start' used in a function with fixed arguments")
+            }
+            ParseError::UnsupportedVaArgType(_) => {
+                write!(f, "'va_arg' supports only 'int' and pointer types")
+            }
+            ParseError::UnsupportedCharPrefix(_) => {
+                write!(f, "only plain and L character constants are supported")
+            }
+            ParseError::UnknownAttribute(name, _) => write!(f, "unknown attribute '{name}'"),
+            ParseError::UnsupportedFormatArchetype(name, _) => {
+                write!(f, "'{name}' is an unsupported format archetype")
+            }
+            ParseError::FormatArgumentOutOfBounds(position, _) => {
+                write!(
+                    f,
+                    "'format' attribute parameter {position} is out of bounds"
+                )
+            }
+            ParseError::FormatStringNotString(_) => {
+                write!(f, "format argument not a string type")
+            }
+            ParseError::FormatRequiresVariadic(_) => {
+                write!(f, "format attribute requires variadic function")
+            }
+            ParseError::StaticAssertionFailed(message, _) => {
+                write!(f, "static assertion failed: {}", message.escape_ascii())
+            }
+            ParseError::Constant(error) => write!(f, "{}", error),
+            ParseError::Lexer(error) => write!(f, "{}", error),
         }
     }
 }
@@ -83,10 +277,709 @@ impl From<LexerError> for ParseError {
     }
 }
 
+impl From<ConstantError> for ParseError {
+    fn from(e: ConstantError) -> Self {
+        ParseError::Constant(e)
+    }
+}
+
+/// Why an expression that must be an integer constant expression is not
+/// one, at the operand or operation responsible.
+#[derive(Debug)]
+pub enum ConstantError {
+    NotConstant(Span),
+    /// A division, or a remainder when true, by zero.
+    DivisionByZero(bool, Span),
+    /// An operation whose result does not fit its type, with the result
+    /// after wrapping around.
+    Overflow(i64, Span),
+    /// A shift by a negative count or by at least the width of the type.
+    ShiftCount(i64, Span),
+    /// A constant whose value does not fit in `int`.
+    TooLarge(i64, Span),
+}
+
+impl HasSpan for ConstantError {
+    fn span(&self) -> &Span {
+        match self {
+            Self::NotConstant(span) => span,
+            Self::DivisionByZero(_, span) => span,
+            Self::Overflow(_, span) => span,
+            Self::ShiftCount(_, span) => span,
+            Self::TooLarge(_, span) => span,
+        }
+    }
+}
+
+impl fmt::Display for ConstantError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::NotConstant(_) => write!(f, "expression is not an integer constant expression"),
+            Self::DivisionByZero(false, _) => write!(f, "division by zero is undefined"),
+            Self::DivisionByZero(true, _) => write!(f, "remainder by zero is undefined"),
+            Self::Overflow(value, _) => {
+                write!(
+                    f,
+                    "overflow in expression; result is {value} with type 'int'"
+                )
+            }
+            Self::ShiftCount(count, _) if *count < 0 => write!(f, "shift count is negative"),
+            Self::ShiftCount(..) => write!(f, "shift count >= width of type"),
+            Self::TooLarge(value, _) => {
+                write!(f, "integer constant {value} is too large for type 'int'")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessError {
+    InvalidDirective(Span),
+    InvalidMacroName(Span),
+    ExpectedParameter(Span),
+    ExpectedParameterSeparator(Span),
+    DuplicateParameter(String, Span),
+    StringifyNonParameter(Span),
+    PasteAtEdge(Span),
+    VariadicOnly(String, Span),
+    NestedVaOpt(Span),
+    UnterminatedVaOpt(Span),
+    UnterminatedMacroCall(String, Span),
+    /// The macro's name, its number of parameters, and the number of
+    /// arguments it was given.
+    MacroArgumentCount(String, usize, usize, Span),
+    InvalidPaste(String, String, Span),
+    InvalidStringify(Span),
+    /// A conditional directive left open at the end of its file, and the
+    /// directive that opened it.
+    UnterminatedConditional(String, Span),
+    UnmatchedConditional(String, Span),
+    ConditionalAfterElse(String, Span),
+    MissingExpression(String, Span),
+    ExpectedValue(Span),
+    MissingBinaryOperator(String, Span),
+    MissingCloseParen(Span),
+    MissingColon(Span),
+    DivisionByZero(Span),
+    OperatorOperand(String, Span),
+    InvalidInclude(Span),
+    IncludeNotFound(String, Span),
+    UnreadableInclude(String, Span),
+    IncludeTooDeep(Span),
+    ErrorDirective(String, Span),
+    Lexer(LexerError),
+}
+
+impl HasSpan for PreprocessError {
+    fn span(&self) -> &Span {
+        match self {
+            Self::InvalidDirective(span) => span,
+            Self::InvalidMacroName(span) => span,
+            Self::ExpectedParameter(span) => span,
+            Self::ExpectedParameterSeparator(span) => span,
+            Self::DuplicateParameter(_, span) => span,
+            Self::StringifyNonParameter(span) => span,
+            Self::PasteAtEdge(span) => span,
+            Self::VariadicOnly(_, span) => span,
+            Self::NestedVaOpt(span) => span,
+            Self::UnterminatedVaOpt(span) => span,
+            Self::UnterminatedMacroCall(_, span) => span,
+            Self::MacroArgumentCount(.., span) => span,
+            Self::InvalidPaste(_, _, span) => span,
+            Self::InvalidStringify(span) => span,
+            Self::UnterminatedConditional(_, span) => span,
+            Self::UnmatchedConditional(_, span) => span,
+            Self::ConditionalAfterElse(_, span) => span,
+            Self::MissingExpression(_, span) => span,
+            Self::ExpectedValue(span) => span,
+            Self::MissingBinaryOperator(_, span) => span,
+            Self::MissingCloseParen(span) => span,
+            Self::MissingColon(span) => span,
+            Self::DivisionByZero(span) => span,
+            Self::OperatorOperand(_, span) => span,
+            Self::InvalidInclude(span) => span,
+            Self::IncludeNotFound(_, span) => span,
+            Self::UnreadableInclude(_, span) => span,
+            Self::IncludeTooDeep(span) => span,
+            Self::ErrorDirective(_, span) => span,
+            Self::Lexer(err) => err.span(),
+        }
+    }
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessError::InvalidDirective(_) => write!(f, "invalid preprocessing directive"),
+            PreprocessError::InvalidMacroName(_) => write!(f, "macro names must be identifiers"),
+            PreprocessError::ExpectedParameter(_) => write!(f, "expected parameter name"),
+            PreprocessError::ExpectedParameterSeparator(_) => {
+                write!(f, "expected ',' or ')' in macro parameter list")
+            }
+            PreprocessError::DuplicateParameter(name, _) => {
+                write!(f, "duplicate macro parameter '{name}'")
+            }
+            PreprocessError::StringifyNonParameter(_) => {
+                write!(f, "'#' is not followed by a macro parameter")
+            }
+            PreprocessError::PasteAtEdge(_) => {
+                write!(f, "'##' cannot appear at either end of a macro expansion")
+            }
+            PreprocessError::VariadicOnly(name, _) => {
+                write!(
+                    f,
+                    "{name} can only appear in the expansion of a variadic macro"
+                )
+            }
+            PreprocessError::NestedVaOpt(_) => {
+                write!(f, "__VA_OPT__ may not appear in a __VA_OPT__")
+            }
+            PreprocessError::UnterminatedVaOpt(_) => write!(f, "unterminated __VA_OPT__"),
+            PreprocessError::UnterminatedMacroCall(name, _) => {
+                write!(f, "unterminated argument list invoking macro '{name}'")
+            }
+            PreprocessError::MacroArgumentCount(name, expected, found, _) if found < expected => {
+                write!(
+                    f,
+                    "macro '{name}' requires {expected} arguments, but only {found} given"
+                )
+            }
+            PreprocessError::MacroArgumentCount(name, expected, found, _) => {
+                write!(
+                    f,
+                    "macro '{name}' passed {found} arguments, but takes just {expected}"
+                )
+            }
+            PreprocessError::InvalidPaste(left, right, _) => write!(
+                f,
+                "pasting '{left}' and '{right}' does not give a valid preprocessing token"
+            ),
+            PreprocessError::InvalidStringify(_) => {
+                write!(f, "'#' does not give a valid string literal")
+            }
+            PreprocessError::UnterminatedConditional(directive, _) => {
+                write!(f, "unterminated #{directive}")
+            }
+            PreprocessError::UnmatchedConditional(directive, _) => {
+                write!(f, "#{directive} without #if")
+            }
+            PreprocessError::ConditionalAfterElse(directive, _) => {
+                write!(f, "#{directive} after #else")
+            }
+            PreprocessError::MissingExpression(directive, _) => {
+                write!(f, "#{directive} with no expression")
+            }
+            PreprocessError::ExpectedValue(_) => write!(f, "expected value in expression"),
+            PreprocessError::MissingBinaryOperator(token, _) => {
+                write!(f, "missing binary operator before token '{token}'")
+            }
+            PreprocessError::MissingCloseParen(_) => write!(f, "missing ')' in expression"),
+            PreprocessError::MissingColon(_) => write!(f, "'?' without following ':'"),
+            PreprocessError::DivisionByZero(_) => write!(f, "division by zero in #if"),
+            PreprocessError::OperatorOperand(operator, _)
+                if operator.starts_with("__has_include") =>
+            {
+                write!(f, "operator '{operator}' requires a header name")
+            }
+            PreprocessError::OperatorOperand(operator, _) => {
+                write!(f, "operator '{operator}' requires an identifier")
+            }
+            PreprocessError::InvalidInclude(_) => {
+                write!(f, "#include expects \"FILENAME\" or <FILENAME>")
+            }
+            PreprocessError::IncludeNotFound(name, _) => write!(f, "'{name}' file not found"),
+            PreprocessError::UnreadableInclude(name, _) => write!(f, "cannot read '{name}'"),
+            PreprocessError::IncludeTooDeep(_) => write!(f, "#include nested too deeply"),
+            PreprocessError::ErrorDirective(message, _) => write!(f, "#error {message}"),
+            PreprocessError::Lexer(err) => write!(f, "{err}"),
+        }
+    }
+}
+
+impl From<LexerError> for PreprocessError {
+    fn from(e: LexerError) -> Self {
+        PreprocessError::Lexer(e)
+    }
+}
+
+#[derive(Debug)]
+pub enum PreprocessWarningKind {
+    Lexer(LexerWarningKind),
+    WarningDirective(String),
+    MacroRedefined(String),
+    ExtraTokens(String),
+    IncludeNextInPrimarySource,
+    /// A `#pragma GCC diagnostic` option that names no warning.
+    UnknownWarningGroup(String),
+}
+
+/// A diagnostic from the preprocessor that does not stop compilation.
+#[derive(Debug)]
+pub struct PreprocessWarning {
+    pub kind: PreprocessWarningKind,
+    pub span: Span,
+}
+
+impl HasSpan for PreprocessWarning {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl Warn for PreprocessWarning {
+    fn warning(&self) -> Warning {
+        match &self.kind {
+            PreprocessWarningKind::Lexer(LexerWarningKind::MultiCharConstant) => Warning::Multichar,
+            PreprocessWarningKind::WarningDirective(_) => Warning::DirectiveMessage,
+            PreprocessWarningKind::MacroRedefined(_) => Warning::MacroRedefined,
+            PreprocessWarningKind::ExtraTokens(_) => Warning::ExtraTokens,
+            PreprocessWarningKind::IncludeNextInPrimarySource => Warning::IncludeNextOutsideHeader,
+            PreprocessWarningKind::UnknownWarningGroup(_) => Warning::UnknownWarningOption,
+        }
+    }
+
+    fn message(&self) -> String {
+        self.kind.to_string()
+    }
+}
+
+impl fmt::Display for PreprocessWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PreprocessWarningKind::Lexer(kind) => write!(f, "{kind}"),
+            PreprocessWarningKind::WarningDirective(message) => write!(f, "#warning {message}"),
+            PreprocessWarningKind::MacroRedefined(name) => write!(f, "'{name}' macro redefined"),
+            PreprocessWarningKind::ExtraTokens(directive) => {
+                write!(f, "extra tokens at end of #{directive} directive")
+            }
+            PreprocessWarningKind::IncludeNextInPrimarySource => {
+                write!(f, "#include_next in primary source file")
+            }
+            PreprocessWarningKind::UnknownWarningGroup(option) => {
+                write!(f, "unknown warning group '{option}', ignored")
+            }
+        }
+    }
+}
+
+impl fmt::Display for PreprocessWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning: {}", self.kind)
+    }
+}
+
+/// A `-W` option on the command line that names no warning. It is reported
+/// before any source is read, so its offset is in no file and it is shown
+/// without a location.
+#[derive(Debug)]
+pub struct UnknownWarningOption {
+    pub option: String,
+    pub span: Span,
+}
+
+impl UnknownWarningOption {
+    pub fn new(option: String) -> Self {
+        Self {
+            option,
+            span: Span::single(0),
+        }
+    }
+}
+
+impl HasSpan for UnknownWarningOption {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl Warn for UnknownWarningOption {
+    fn warning(&self) -> Warning {
+        Warning::UnknownWarningOption
+    }
+
+    fn message(&self) -> String {
+        format!("unknown warning option '{}'", self.option)
+    }
+}
+
+#[derive(Debug)]
+pub enum SemanticError {
+    UndeclaredIdentifier(String, Span),
+    /// A name declared twice in one scope, where only functions may be
+    /// redeclared, and defined once.
+    Redefinition(String, Span),
+    RedeclaredAsDifferentKind(String, Span),
+    /// A declaration of a function whose type differs from an earlier one.
+    ConflictingTypes(String, Span),
+    /// A value that cannot be converted as if by assignment: the context,
+    /// the type converted from, and the type converted to.
+    IncompatibleTypes(Conversion, Type, Type, Span),
+    /// The number of parameters, the number of arguments given, and whether
+    /// the callee is variadic.
+    ArgumentCount(usize, usize, bool, Span),
+    NotAFunction(Type, Span),
+    InvalidIndirection(Type, Span),
+    InvalidSubscript(Span),
+    NonIntegerSubscript(Span),
+    AddressOfRvalue(Type, Span),
+    NotAssignable(Span),
+    ArrayAssignment(Type, Span),
+    ConstAssignment(Type, Span),
+    VoidFunctionReturnsValue(String, Span),
+    VoidArgument(Span),
+    /// An argument to a `va_*` builtin, named here, that is not a `va_list`.
+    NotVaList(&'static str, Type, Span),
+    InvalidUnaryOperand(Type, Span),
+    InvalidOperands(Type, Type, Span),
+    /// The second and third operands of a conditional expression, which
+    /// have no common type.
+    IncompatibleOperands(Type, Type, Span),
+    NotScalar(Type, Span),
+}
+
+impl HasSpan for SemanticError {
+    fn span(&self) -> &Span {
+        match self {
+            Self::UndeclaredIdentifier(_, span) => span,
+            Self::Redefinition(_, span) => span,
+            Self::RedeclaredAsDifferentKind(_, span) => span,
+            Self::ConflictingTypes(_, span) => span,
+            Self::IncompatibleTypes(.., span) => span,
+            Self::ArgumentCount(.., span) => span,
+            Self::NotAFunction(_, span) => span,
+            Self::InvalidIndirection(_, span) => span,
+            Self::InvalidSubscript(span) => span,
+            Self::NonIntegerSubscript(span) => span,
+            Self::AddressOfRvalue(_, span) => span,
+            Self::NotAssignable(span) => span,
+            Self::ArrayAssignment(_, span) => span,
+            Self::ConstAssignment(_, span) => span,
+            Self::VoidFunctionReturnsValue(_, span) => span,
+            Self::VoidArgument(span) => span,
+            Self::NotVaList(.., span) => span,
+            Self::InvalidUnaryOperand(_, span) => span,
+            Self::InvalidOperands(.., span) => span,
+            Self::IncompatibleOperands(.., span) => span,
+            Self::NotScalar(_, span) => span,
+        }
+    }
+}
+
+impl fmt::Display for SemanticError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticError::UndeclaredIdentifier(name, _) => {
+                write!(f, "use of undeclared identifier '{name}'")
+            }
+            SemanticError::Redefinition(name, _) => write!(f, "redefinition of '{name}'"),
+            SemanticError::RedeclaredAsDifferentKind(name, _) => {
+                write!(f, "'{name}' redeclared as different kind of symbol")
+            }
+            SemanticError::ConflictingTypes(name, _) => write!(f, "conflicting types for '{name}'"),
+            SemanticError::IncompatibleTypes(conversion, from, to, _) => match conversion {
+                Conversion::Assigning => {
+                    write!(f, "assigning to '{to}' from incompatible type '{from}'")
+                }
+                Conversion::Initializing => write!(
+                    f,
+                    "initializing '{to}' with an expression of incompatible type '{from}'"
+                ),
+                Conversion::Returning => write!(
+                    f,
+                    "returning '{from}' from a function with incompatible result type '{to}'"
+                ),
+                Conversion::Passing => {
+                    write!(
+                        f,
+                        "passing '{from}' to parameter of incompatible type '{to}'"
+                    )
+                }
+            },
+            SemanticError::ArgumentCount(expected, given, variadic, _) => {
+                let amount = if given < expected { "few" } else { "many" };
+                let at_least = if *variadic { "at least " } else { "" };
+                write!(
+                    f,
+                    "too {amount} arguments to function call, expected {at_least}{expected}, have {given}"
+                )
+            }
+            SemanticError::NotAFunction(ty, _) => {
+                write!(
+                    f,
+                    "called object type '{ty}' is not a function or function pointer"
+                )
+            }
+            SemanticError::InvalidIndirection(ty, _) => {
+                write!(f, "indirection requires pointer operand ('{ty}' invalid)")
+            }
+            SemanticError::InvalidSubscript(_) => {
+                write!(f, "subscripted value is not an array or pointer")
+            }
+            SemanticError::NonIntegerSubscript(_) => write!(f, "array subscript is not an integer"),
+            SemanticError::AddressOfRvalue(ty, _) => {
+                write!(f, "cannot take the address of an rvalue of type '{ty}'")
+            }
+            SemanticError::NotAssignable(_) => write!(f, "expression is not assignable"),
+            SemanticError::ArrayAssignment(ty, _) => {
+                write!(f, "array type '{ty}' is not assignable")
+            }
+            SemanticError::ConstAssignment(ty, _) => {
+                write!(
+                    f,
+                    "cannot assign to lvalue with const-qualified type '{ty}'"
+                )
+            }
+            SemanticError::VoidFunctionReturnsValue(name, _) => {
+                write!(f, "void function '{name}' should not return a value")
+            }
+            SemanticError::VoidArgument(_) => write!(f, "argument type 'void' is incomplete"),
+            SemanticError::NotVaList(builtin, ty, _) => {
+                write!(
+                    f,
+                    "argument to '{builtin}' is of type '{ty}' and not 'va_list'"
+                )
+            }
+            SemanticError::InvalidUnaryOperand(ty, _) => {
+                write!(f, "invalid argument type '{ty}' to unary expression")
+            }
+            SemanticError::InvalidOperands(a, b, _) => {
+                write!(f, "invalid operands to binary expression ('{a}' and '{b}')")
+            }
+            SemanticError::IncompatibleOperands(a, b, _) => {
+                write!(f, "incompatible operand types ('{a}' and '{b}')")
+            }
+            SemanticError::NotScalar(ty, _) => {
+                write!(
+                    f,
+                    "used type '{ty}' where arithmetic or pointer type is required"
+                )
+            }
+        }
+    }
+}
+
+/// Where a value is converted as if by assignment.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Conversion {
+    Assigning,
+    Initializing,
+    Returning,
+    Passing,
+}
+
+#[derive(Debug)]
+pub enum SemanticWarningKind {
+    /// A pointer conversion that drops qualifiers from the pointed-to type:
+    /// the context, the type converted from, and the type converted to.
+    DiscardsQualifiers(Conversion, Type, Type),
+    /// A call to a function with no declaration in scope.
+    ImplicitFunctionDeclaration(String),
+    /// An implicit conversion to a narrower integer type: the type
+    /// converted from, the type converted to, and for a constant, its
+    /// value and the value it is converted to.
+    ImplicitConversion(Type, Type, Option<(i64, i64)>),
+    /// A constant expression whose evaluation is undefined, as by
+    /// overflowing or dividing by zero, where it need not be constant.
+    UndefinedConstant(ConstantError),
+    /// The end of the body of a non-void function, which it can reach,
+    /// and whether it can return a value elsewhere.
+    MissingReturn(bool),
+    /// The first of a run of statements that control cannot reach.
+    UnreachableCode,
+    UnusedVariable(String),
+    UnusedParameter(String),
+    /// A read of a local variable that no path to it assigns, with the
+    /// variable's declaration.
+    Uninitialized(String, Span),
+    /// A read of a local variable that some path to it does not assign.
+    SometimesUninitialized(String, Span),
+    /// A problem with a string literal passed as a format string, and the
+    /// bytes of its value that it is about.
+    Format(FormatWarning, Range<usize>),
+    /// An argument after a format string that it does not describe.
+    FormatExtraArgument,
+}
+
+#[derive(Debug)]
+pub enum FormatWarning {
+    /// A conversion specification whose argument has the wrong type: the
+    /// type it takes, and the argument's.
+    ArgumentType(String, Type),
+    /// A `*` field width or precision, with the argument's type, which
+    /// should be `int`.
+    FieldWidthType(Type),
+    PrecisionType(Type),
+    /// A conversion specification with no argument left for it.
+    MissingArgument,
+    InvalidSpecifier(String),
+    /// A length modifier that does not apply to the conversion after it.
+    InvalidLength(String, char),
+    Incomplete,
+}
+
+#[derive(Debug)]
+pub struct SemanticWarning {
+    pub kind: SemanticWarningKind,
+    pub span: Span,
+}
+
+impl HasSpan for SemanticWarning {
+    fn span(&self) -> &Span {
+        &self.span
+    }
+}
+
+impl Warn for SemanticWarning {
+    fn warning(&self) -> Warning {
+        match &self.kind {
+            SemanticWarningKind::DiscardsQualifiers(..) => Warning::DiscardsQualifiers,
+            SemanticWarningKind::ImplicitFunctionDeclaration(_) => {
+                Warning::ImplicitFunctionDeclaration
+            }
+            SemanticWarningKind::ImplicitConversion(..) => Warning::Conversion,
+            SemanticWarningKind::UndefinedConstant(ConstantError::DivisionByZero(..)) => {
+                Warning::DivisionByZero
+            }
+            SemanticWarningKind::UndefinedConstant(_) => Warning::Overflow,
+            SemanticWarningKind::MissingReturn(_) => Warning::ReturnType,
+            SemanticWarningKind::UnreachableCode => Warning::UnreachableCode,
+            SemanticWarningKind::UnusedVariable(_) => Warning::UnusedVariable,
+            SemanticWarningKind::UnusedParameter(_) => Warning::UnusedParameter,
+            SemanticWarningKind::Uninitialized(..) => Warning::Uninitialized,
+            SemanticWarningKind::SometimesUninitialized(..) => Warning::SometimesUninitialized,
+            SemanticWarningKind::Format(FormatWarning::InvalidSpecifier(_), _) => {
+                Warning::FormatInvalidSpecifier
+            }
+            SemanticWarningKind::Format(..) => Warning::Format,
+            SemanticWarningKind::FormatExtraArgument => Warning::FormatExtraArgs,
+        }
+    }
+
+    fn message(&self) -> String {
+        self.kind.to_string()
+    }
+
+    fn notes(&self) -> Vec<(String, Span)> {
+        match &self.kind {
+            SemanticWarningKind::Uninitialized(name, declaration)
+            | SemanticWarningKind::SometimesUninitialized(name, declaration) => {
+                vec![(format!("variable '{name}' is declared here"), *declaration)]
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn locate(&self, sources: &SourceMap) -> Span {
+        match &self.kind {
+            SemanticWarningKind::Format(_, bytes) => {
+                literal_span(sources, self.span, bytes.clone()).unwrap_or(self.span)
+            }
+            _ => self.span,
+        }
+    }
+}
+
+impl fmt::Display for SemanticWarningKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SemanticWarningKind::DiscardsQualifiers(conversion, from, to) => match conversion {
+                Conversion::Assigning => {
+                    write!(f, "assigning to '{to}' from '{from}' discards qualifiers")
+                }
+                Conversion::Initializing => write!(
+                    f,
+                    "initializing '{to}' with an expression of type '{from}' discards qualifiers"
+                ),
+                Conversion::Returning => write!(
+                    f,
+                    "returning '{from}' from a function with result type '{to}' discards qualifiers"
+                ),
+                Conversion::Passing => write!(
+                    f,
+                    "passing '{from}' to parameter of type '{to}' discards qualifiers"
+                ),
+            },
+            SemanticWarningKind::ImplicitFunctionDeclaration(name) => write!(
+                f,
+                "call to undeclared function '{name}'; ISO C99 and later do not support implicit function declarations"
+            ),
+            SemanticWarningKind::ImplicitConversion(from, to, None) => write!(
+                f,
+                "implicit conversion loses integer precision: '{from}' to '{to}'"
+            ),
+            SemanticWarningKind::ImplicitConversion(from, to, Some((value, converted))) => write!(
+                f,
+                "implicit conversion from '{from}' to '{to}' changes value from {value} to {converted}"
+            ),
+            SemanticWarningKind::UndefinedConstant(error) => write!(f, "{error}"),
+            SemanticWarningKind::MissingReturn(false) => {
+                write!(f, "non-void function does not return a value")
+            }
+            SemanticWarningKind::MissingReturn(true) => {
+                write!(
+                    f,
+                    "non-void function does not return a value in all control paths"
+                )
+            }
+            SemanticWarningKind::UnreachableCode => write!(f, "code will never be executed"),
+            SemanticWarningKind::UnusedVariable(name) => write!(f, "unused variable '{name}'"),
+            SemanticWarningKind::UnusedParameter(name) => write!(f, "unused parameter '{name}'"),
+            SemanticWarningKind::Uninitialized(name, _) => {
+                write!(f, "variable '{name}' is uninitialized when used here")
+            }
+            SemanticWarningKind::SometimesUninitialized(name, _) => {
+                write!(f, "variable '{name}' may be uninitialized when used here")
+            }
+            SemanticWarningKind::Format(warning, _) => write!(f, "{warning}"),
+            SemanticWarningKind::FormatExtraArgument => {
+                write!(f, "data argument not used by format string")
+            }
+        }
+    }
+}
+
+impl fmt::Display for FormatWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FormatWarning::ArgumentType(expected, ty) => write!(
+                f,
+                "format specifies type '{expected}' but the argument has type '{ty}'"
+            ),
+            FormatWarning::FieldWidthType(ty) => write!(
+                f,
+                "field width should have type 'int', but argument has type '{ty}'"
+            ),
+            FormatWarning::PrecisionType(ty) => write!(
+                f,
+                "field precision should have type 'int', but argument has type '{ty}'"
+            ),
+            FormatWarning::MissingArgument => {
+                write!(f, "more '%' conversions than data arguments")
+            }
+            FormatWarning::InvalidSpecifier(specifier) => {
+                write!(f, "invalid conversion specifier '{specifier}'")
+            }
+            FormatWarning::InvalidLength(length, conversion) => write!(
+                f,
+                "length modifier '{length}' results in undefined behavior or no effect with '{conversion}' conversion specifier"
+            ),
+            FormatWarning::Incomplete => write!(f, "incomplete format specifier"),
+        }
+    }
+}
+
+impl fmt::Display for SemanticWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "warning: {}", self.kind)
+    }
+}
+
 #[derive(Debug)]
 pub enum CompilerError {
     Lexer(LexerError),
+    Preprocessor(PreprocessError),
     Parser(ParseError),
+    Semantic(SemanticError),
 }
 
 impl From<LexerError> for CompilerError {
@@ -95,25 +988,110 @@ impl From<LexerError> for CompilerError {
     }
 }
 
+impl From<PreprocessError> for CompilerError {
+    fn from(err: PreprocessError) -> Self {
+        CompilerError::Preprocessor(err)
+    }
+}
+
 impl From<ParseError> for CompilerError {
     fn from(err: ParseError) -> Self {
         CompilerError::Parser(err)
     }
 }
 
-pub fn render_diagnostic(src: &str, error: &(impl HasSpan + std::fmt::Display)) {
-    let start = error.span().start.min(src.len());
-    let end = error.span().end.min(src.len());
+impl From<SemanticError> for CompilerError {
+    fn from(err: SemanticError) -> Self {
+        CompilerError::Semantic(err)
+    }
+}
+
+pub fn render_diagnostic(sources: &SourceMap, error: &(impl HasSpan + std::fmt::Display)) {
+    eprint!("{}", format_diagnostic(sources, error));
+}
+
+/// Formats a diagnostic as its location, preceded by the includes that led
+/// there, then the source line with the span underlined. A diagnostic on
+/// tokens produced by macro expansions is followed by a note for each
+/// expansion, innermost first, underlining where the macro was invoked.
+pub fn format_diagnostic(
+    sources: &SourceMap,
+    error: &(impl HasSpan + std::fmt::Display),
+) -> String {
+    let span = sources.spelling_span(*error.span());
+    if sources.file(span.start).is_none() {
+        return format!("{error}\n");
+    }
+
+    let mut out = String::new();
+    use std::fmt::Write;
+
+    for (i, (name, line)) in sources.include_stack(span.start).iter().enumerate() {
+        let _ = match i {
+            0 => write!(out, "In file included from {name}:{line}"),
+            _ => write!(out, ",\n                 from {name}:{line}"),
+        };
+    }
+    if !out.is_empty() {
+        out.push_str(":\n");
+    }
+    write_snippet(&mut out, sources, span, &error.to_string());
+    for expansion in sources.expansions(error.span().start) {
+        let message = format!("note: in expansion of macro '{}'", expansion.name);
+        write_snippet(
+            &mut out,
+            sources,
+            sources.spelling_span(expansion.site),
+            &message,
+        );
+    }
+
+    out
+}
+
+/// The span of `bytes` of the value of `literal`, a string literal or
+/// several concatenated, found by lexing it again where it is written.
+fn literal_span(sources: &SourceMap, literal: Span, bytes: Range<usize>) -> Option<Span> {
+    let base = sources.spelling_span(literal).start;
+    let text = sources.snippet(literal);
+    // Where each byte of the value starts and ends in `text`.
+    let mut written = Vec::new();
+    for token in Lexer::new(text) {
+        let Token {
+            kind: TokenKind::StringLiteral(_),
+            span,
+        } = token.ok()?
+        else {
+            return None;
+        };
+        let offsets = string_literal_offsets(&text[span.start..span.end])?;
+        written.extend(
+            offsets
+                .windows(2)
+                .map(|pair| (span.start + pair[0], span.start + pair[1])),
+        );
+    }
+
+    let start = written.get(bytes.start)?.0;
+    let end = written.get(bytes.end.checked_sub(1)?)?.1;
+    Some(Span::new(base + start, base + end))
+}
+
+/// Writes the location of `span`, which lies within a file, then its
+/// source line with the span underlined and followed by `message`.
+fn write_snippet(out: &mut String, sources: &SourceMap, span: Span, message: &str) {
+    let Some(file) = sources.file(span.start) else {
+        return;
+    };
+    let src = file.src.as_str();
+    let start = file.local(span.start);
+    let end = file.local(span.end).max(start);
 
     let line_start = src[..start].rfind('\n').map(|i| i + 1).unwrap_or(0);
     let line_end = src[end..].find('\n').map(|i| end + i).unwrap_or(src.len());
 
     let line_text = &src[line_start..line_end];
 
-    let line_no = 1 + src[..line_start].chars().filter(|&c| c == '\n').count();
-
-    let col_start = src[line_start..start].chars().count().max(1);
-
     let expand_tabs = |s: &str| s.replace('\t', "    ");
     let expanded_line = expand_tabs(line_text);
 
@@ -124,18 +1102,16 @@ pub fn render_diagnostic(src: &str, error: &(impl HasSpan + std::fmt::Display))
         sel.chars().count().max(1)
     };
 
-    let mut out = String::new();
     use std::fmt::Write;
-
-    let _ = writeln!(out, "line {}, col {}", line_no, col_start);
+    if let Some(location) = sources.location(span.start) {
+        let _ = writeln!(out, "{location}");
+    }
     let _ = writeln!(out, "{}", expanded_line);
     let _ = writeln!(
         out,
         "{}{} {}",
         " ".repeat(underline_pad),
         "^".repeat(underline_len),
-        &error.to_string()
+        message
     );
-
-    eprint!("{}", out);
 }
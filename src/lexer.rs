@@ -1,12 +1,28 @@
-use crate::error::{LexerError, LexerErrorKind};
+use crate::error::{LexerError, LexerErrorKind, LexerWarning, LexerWarningKind};
 use crate::span::Span;
-use crate::token::{Const, Keyword, Token, TokenKind};
+use crate::token::{CharPrefix, Const, Keyword, Token, TokenKind};
+use std::borrow::Cow;
+use std::str::CharIndices;
 
 pub struct Lexer<'a> {
     src: &'a str,
-    iter: std::str::CharIndices<'a>,
+    iter: CharIndices<'a>,
     peeked: Option<(usize, char)>,
     pos: usize,
+    warnings: Vec<LexerWarning>,
+    line_start: bool,
+    space_before: bool,
+    /// Whether the whitespace skipped since the last token held a line
+    /// break, or anything at all.
+    gap_newline: bool,
+    gap_space: bool,
+}
+
+/// One element of a character constant: an escape sequence yields a single
+/// code unit, a source character may need several in a narrow constant.
+enum LiteralChar {
+    Escape(u32),
+    Source(char),
 }
 
 impl<'a> Lexer<'a> {
@@ -16,21 +32,84 @@ impl<'a> Lexer<'a> {
             iter: src.char_indices(),
             peeked: None,
             pos: 0,
+            warnings: Vec::new(),
+            line_start: true,
+            space_before: false,
+            gap_newline: true,
+            gap_space: false,
         }
     }
 
-    pub fn pos(&self) -> usize {
-        self.pos
+    pub fn warnings(&self) -> &[LexerWarning] {
+        &self.warnings
+    }
+
+    /// Whether the token last returned is the first on its line.
+    pub fn line_start(&self) -> bool {
+        self.line_start
+    }
+
+    /// Whether whitespace or a comment came before the token last returned.
+    pub fn space_before(&self) -> bool {
+        self.space_before
+    }
+
+    /// Lexes the `<...>` or `"..."` header name of an `#include` directive,
+    /// if one follows on the same line. Inside it, `\` and `//` are not
+    /// special.
+    pub fn header_name(&mut self) -> Option<Result<Token, LexerError>> {
+        if let Err(err) = self.skip_ws() {
+            return Some(Err(err));
+        }
+        let close = match self.peek_char() {
+            _ if self.gap_newline => return None,
+            Some((_, '<')) => '>',
+            Some((_, '"')) => '"',
+            _ => return None,
+        };
+
+        self.start_token();
+        let start = self.pos;
+        self.consume_char();
+        loop {
+            match self.peek_char() {
+                None | Some((_, '\n')) => {
+                    return Some(Err(LexerError {
+                        kind: LexerErrorKind::UnterminatedHeaderName(close),
+                        span: Span::new(start, self.pos),
+                    }));
+                }
+                Some((_, ch)) => {
+                    self.consume_char();
+                    if ch == close {
+                        break;
+                    }
+                }
+            }
+        }
+
+        Some(Ok(Token {
+            kind: TokenKind::HeaderName(self.text(start).into_owned()),
+            span: Span::new(start, self.pos),
+        }))
     }
 
+    /// The next character, after deleting any backslash-newline line
+    /// splices, so tokens and comments may continue onto the next line.
     fn peek_char(&mut self) -> Option<(usize, char)> {
         if self.peeked.is_none() {
-            self.peeked = self.iter.next();
+            self.peeked = spliced_next(&mut self.iter);
         }
 
         self.peeked
     }
 
+    /// The character after the peeked one.
+    fn peek_second(&mut self) -> Option<(usize, char)> {
+        self.peek_char()?;
+        spliced_next(&mut self.iter.clone())
+    }
+
     fn consume_char(&mut self) -> Option<(usize, char)> {
         let out = self.peek_char();
         self.peeked = None;
@@ -41,17 +120,70 @@ impl<'a> Lexer<'a> {
         out
     }
 
-    fn skip_ws(&mut self) {
-        while let Some((_, ch)) = self.peek_char() {
-            if ch.is_whitespace() {
-                self.consume_char();
-            } else {
-                break;
+    /// The source text from `start` to the current position, without the
+    /// line splices in it.
+    fn text(&self, start: usize) -> Cow<'a, str> {
+        let text = &self.src[start..self.pos];
+        if text.contains('\\') {
+            Cow::Owned(text.replace("\\\r\n", "").replace("\\\n", ""))
+        } else {
+            Cow::Borrowed(text)
+        }
+    }
+
+    /// Moves what was skipped before the token about to be lexed into its
+    /// flags.
+    fn start_token(&mut self) {
+        self.line_start = std::mem::take(&mut self.gap_newline);
+        self.space_before = std::mem::take(&mut self.gap_space);
+    }
+
+    /// Skips whitespace and comments, noting whether they contained a line
+    /// break for the token that follows.
+    fn skip_ws(&mut self) -> Result<(), LexerError> {
+        let start = self.pos;
+        while let Some((i, ch)) = self.peek_char() {
+            match (ch, self.peek_second()) {
+                (ch, _) if ch.is_whitespace() => {
+                    self.gap_newline |= ch == '\n';
+                    self.consume_char();
+                }
+                ('/', Some((_, '/'))) => {
+                    while let Some((_, ch)) = self.peek_char()
+                        && ch != '\n'
+                    {
+                        self.consume_char();
+                    }
+                }
+                ('/', Some((_, '*'))) => {
+                    self.consume_char();
+                    self.consume_char();
+                    let open = Span::new(i, self.pos);
+                    loop {
+                        match self.consume_char() {
+                            Some((_, '*')) if matches!(self.peek_char(), Some((_, '/'))) => {
+                                self.consume_char();
+                                break;
+                            }
+                            Some(_) => {}
+                            None => {
+                                return Err(LexerError {
+                                    kind: LexerErrorKind::UnterminatedComment,
+                                    span: open,
+                                });
+                            }
+                        }
+                    }
+                }
+                _ => break,
             }
         }
+        self.gap_space |= self.pos != start;
+
+        Ok(())
     }
 
-    fn identifier(&mut self) -> Token {
+    fn identifier(&mut self) -> Result<Token, LexerError> {
         let start = self.pos;
         self.consume_char();
         while let Some((_, ch)) = self.peek_char() {
@@ -62,16 +194,40 @@ impl<'a> Lexer<'a> {
             }
         }
 
-        let identifier = &self.src[start..self.pos];
+        let text = self.text(start);
+        let identifier = text.as_ref();
+        let prefix = match identifier {
+            "L" => Some(CharPrefix::Wide),
+            "u" => Some(CharPrefix::Utf16),
+            "U" => Some(CharPrefix::Utf32),
+            "u8" => Some(CharPrefix::Utf8),
+            _ => None,
+        };
+        match (prefix, self.peek_char()) {
+            (Some(prefix), Some((_, '\''))) => return self.char_constant(start, prefix),
+            (Some(CharPrefix::Utf8), Some((_, '"'))) => return self.string_literal(start),
+            (Some(_), Some((_, '"'))) => {
+                return Err(LexerError {
+                    kind: LexerErrorKind::UnsupportedStringPrefix,
+                    span: Span::new(start, self.pos),
+                });
+            }
+            _ => {}
+        }
+
         let span = Span {
             start,
             end: self.pos,
         };
-        match identifier {
+        Ok(match identifier {
             "int" => Token {
                 kind: TokenKind::Keyword(Keyword::Int),
                 span,
             },
+            "char" => Token {
+                kind: TokenKind::Keyword(Keyword::Char),
+                span,
+            },
             "void" => Token {
                 kind: TokenKind::Keyword(Keyword::Void),
                 span,
@@ -80,50 +236,364 @@ impl<'a> Lexer<'a> {
                 kind: TokenKind::Keyword(Keyword::Return),
                 span,
             },
+            "const" => Token {
+                kind: TokenKind::Keyword(Keyword::Const),
+                span,
+            },
+            "volatile" => Token {
+                kind: TokenKind::Keyword(Keyword::Volatile),
+                span,
+            },
+            "restrict" => Token {
+                kind: TokenKind::Keyword(Keyword::Restrict),
+                span,
+            },
+            "__builtin_va_list" => Token {
+                kind: TokenKind::Keyword(Keyword::BuiltinVaList),
+                span,
+            },
+            "_Static_assert" => Token {
+                kind: TokenKind::Keyword(Keyword::StaticAssert),
+                span,
+            },
             _ => Token {
                 kind: TokenKind::Identifier(identifier.to_string()),
                 span,
             },
+        })
+    }
+
+    /// Lexes a character constant whose opening quote is next; `start` is
+    /// where its prefix, if any, begins.
+    fn char_constant(&mut self, start: usize, prefix: CharPrefix) -> Result<Token, LexerError> {
+        self.consume_char();
+        let mut units = Vec::new();
+        loop {
+            let char_start = self.pos;
+            match self.literal_char(start, '\'', prefix.max())? {
+                None => break,
+                Some(LiteralChar::Escape(unit)) => units.push(unit),
+                Some(LiteralChar::Source(ch)) if prefix == CharPrefix::None => {
+                    let mut buf = [0; 4];
+                    units.extend(ch.encode_utf8(&mut buf).bytes().map(u32::from));
+                }
+                Some(LiteralChar::Source(ch)) => {
+                    // A `u8` constant must fit in one UTF-8 code unit.
+                    let max = if prefix == CharPrefix::Utf8 {
+                        0x7f
+                    } else {
+                        prefix.max()
+                    };
+                    if ch as u32 > max {
+                        return Err(LexerError {
+                            kind: LexerErrorKind::CharacterTooLarge,
+                            span: Span::new(char_start, self.pos),
+                        });
+                    }
+                    units.push(ch as u32);
+                }
+            }
         }
+
+        let span = Span::new(start, self.pos);
+        let value = match (prefix, units.as_slice()) {
+            (_, []) => {
+                return Err(LexerError {
+                    kind: LexerErrorKind::EmptyCharConstant,
+                    span,
+                });
+            }
+            // Plain `char` is signed, so `'\xff'` is -1.
+            (CharPrefix::None, &[unit]) => unit as u8 as i8 as i64,
+            (CharPrefix::None, units) if units.len() <= 4 => {
+                self.warnings.push(LexerWarning {
+                    kind: LexerWarningKind::MultiCharConstant,
+                    span,
+                });
+                units.iter().fold(0u32, |acc, &unit| acc << 8 | unit) as i32 as i64
+            }
+            (CharPrefix::Wide, &[unit]) => unit as i32 as i64,
+            (_, &[unit]) => unit as i64,
+            _ => {
+                return Err(LexerError {
+                    kind: LexerErrorKind::CharConstantTooLong,
+                    span,
+                });
+            }
+        };
+
+        Ok(Token {
+            kind: TokenKind::Constant(Const::Char(value, prefix)),
+            span,
+        })
     }
 
-    fn constant(&mut self) -> Result<Token, LexerError> {
-        let start = self.pos;
+    /// Lexes a string literal whose opening quote is next; `start` is where
+    /// its `u8` prefix, if any, begins.
+    fn string_literal(&mut self, start: usize) -> Result<Token, LexerError> {
+        let (bytes, _) = self.string_contents(start)?;
+
+        Ok(Token {
+            kind: TokenKind::StringLiteral(bytes),
+            span: Span::new(start, self.pos),
+        })
+    }
+
+    /// Reads the contents of a string literal whose opening quote is next,
+    /// with the offset at which each byte is written and then that of the
+    /// closing quote.
+    fn string_contents(&mut self, start: usize) -> Result<(Vec<u8>, Vec<usize>), LexerError> {
         self.consume_char();
+        let mut bytes = Vec::new();
+        let mut offsets = Vec::new();
         loop {
-            match self.peek_char() {
-                Some((_, ch)) if ch.is_ascii_digit() => {
-                    self.consume_char();
+            let at = self.peek_char().map_or(self.pos, |(i, _)| i);
+            let Some(ch) = self.literal_char(start, '"', 0xff)? else {
+                break;
+            };
+            match ch {
+                LiteralChar::Escape(unit) => bytes.push(unit as u8),
+                LiteralChar::Source(ch) => {
+                    let mut buf = [0; 4];
+                    bytes.extend_from_slice(ch.encode_utf8(&mut buf).as_bytes());
                 }
-                _ => break,
             }
+            offsets.resize(bytes.len(), at);
         }
+        offsets.push(self.pos - 1);
+
+        Ok((bytes, offsets))
+    }
+
+    /// Reads the next character or escape sequence of the literal starting at
+    /// `start`, or `None` at its closing `quote`. Escapes larger than `max`
+    /// are out of range.
+    fn literal_char(
+        &mut self,
+        start: usize,
+        quote: char,
+        max: u32,
+    ) -> Result<Option<LiteralChar>, LexerError> {
+        match self.peek_char() {
+            None | Some((_, '\n')) => {
+                let kind = if quote == '"' {
+                    LexerErrorKind::UnterminatedString
+                } else {
+                    LexerErrorKind::UnterminatedCharConstant
+                };
+                return Err(LexerError {
+                    kind,
+                    span: Span::new(start, self.pos),
+                });
+            }
+            _ => {}
+        }
+
+        match self.consume_char() {
+            None => unreachable!(),
+            Some((_, ch)) if ch == quote => Ok(None),
+            Some((i, '\\')) => self
+                .escape(i, max)
+                .map(|unit| Some(LiteralChar::Escape(unit))),
+            Some((_, ch)) => Ok(Some(LiteralChar::Source(ch))),
+        }
+    }
 
-        if let Some((_, ch)) = self.peek_char() {
-            if ch.is_alphabetic() || ch == '_' {
+    /// Reads an escape sequence whose backslash, at `start`, was consumed.
+    fn escape(&mut self, start: usize, max: u32) -> Result<u32, LexerError> {
+        let value = match self.peek_char() {
+            Some((_, ch @ ('\'' | '"' | '?' | '\\'))) => {
+                self.consume_char();
+                u64::from(ch)
+            }
+            Some((_, ch @ ('a' | 'b' | 'f' | 'n' | 'r' | 't' | 'v'))) => {
+                self.consume_char();
+                match ch {
+                    'a' => 0x07,
+                    'b' => 0x08,
+                    'f' => 0x0c,
+                    'n' => 0x0a,
+                    'r' => 0x0d,
+                    't' => 0x09,
+                    _ => 0x0b,
+                }
+            }
+            Some((_, '0'..='7')) => {
+                let mut value = 0;
+                for _ in 0..3 {
+                    match self.peek_char() {
+                        Some((_, ch @ '0'..='7')) => {
+                            self.consume_char();
+                            value = value * 8 + u64::from(ch.to_digit(8).unwrap());
+                        }
+                        _ => break,
+                    }
+                }
+                value
+            }
+            Some((_, 'x')) => {
+                self.consume_char();
+                let digits = self.pos;
+                let mut value: u64 = 0;
+                while let Some((_, ch)) = self.peek_char()
+                    && let Some(digit) = ch.to_digit(16)
+                {
+                    self.consume_char();
+                    value = value.saturating_mul(16).saturating_add(u64::from(digit));
+                }
+                if self.pos == digits {
+                    return Err(LexerError {
+                        kind: LexerErrorKind::MissingHexDigits,
+                        span: Span::new(start, self.pos),
+                    });
+                }
+                value
+            }
+            Some((_, ch)) if ch != '\n' => {
+                self.consume_char();
                 return Err(LexerError {
-                    kind: LexerErrorKind::InvalidConstSuffix,
-                    span: Span::single(self.pos),
+                    kind: LexerErrorKind::UnknownEscape(ch),
+                    span: Span::new(start, self.pos),
+                });
+            }
+            _ => {
+                return Err(LexerError {
+                    kind: LexerErrorKind::UnterminatedCharConstant,
+                    span: Span::single(start),
                 });
             }
+        };
+
+        if value > u64::from(max) {
+            return Err(LexerError {
+                kind: LexerErrorKind::EscapeOutOfRange,
+                span: Span::new(start, self.pos),
+            });
+        }
+
+        Ok(value as u32)
+    }
+
+    /// Lexes the punctuator at `i` as the first of `spellings`, longest
+    /// first, that the input continues with.
+    fn punctuator(&mut self, i: usize, spellings: &[(&str, TokenKind)]) -> Token {
+        let (spelling, kind) = spellings
+            .iter()
+            .find(|(spelling, _)| self.continues_with(spelling))
+            .expect("the last spelling is the character at `i`");
+        for _ in spelling.chars() {
+            self.consume_char();
+        }
+
+        Token {
+            kind: kind.clone(),
+            span: Span::new(i, self.pos),
+        }
+    }
+
+    /// Whether the input, after line splices are deleted, starts with `text`.
+    fn continues_with(&mut self, text: &str) -> bool {
+        let Some((_, first)) = self.peek_char() else {
+            return false;
+        };
+        let mut iter = self.iter.clone();
+        let mut chars = text.chars();
+        chars.next() == Some(first)
+            && chars.all(|ch| spliced_next(&mut iter).is_some_and(|(_, next)| next == ch))
+    }
+
+    /// Lexes a preprocessing number, which must be an integer constant. It
+    /// runs on through letters, digits, dots and signed exponents (C17
+    /// 6.4.8), so `123abc` is a single invalid token.
+    fn constant(&mut self) -> Result<Token, LexerError> {
+        let start = self.pos;
+        self.consume_char();
+        while let Some((_, ch)) = self.peek_char()
+            && (ch.is_ascii_alphanumeric() || ch == '_' || ch == '.')
+        {
+            self.consume_char();
+            if matches!(ch, 'e' | 'E' | 'p' | 'P')
+                && let Some((_, '+' | '-')) = self.peek_char()
+            {
+                self.consume_char();
+            }
         }
 
         let span = Span {
             start,
             end: self.pos,
         };
-        match self.src[start..self.pos].parse::<i64>() {
+        let error = |kind| LexerError { kind, span };
+        let (value, _) = integer_value(&self.text(start)).map_err(error)?;
+        match i64::try_from(value) {
             Ok(n) => Ok(Token {
                 kind: TokenKind::Constant(Const::Int(n)),
                 span,
             }),
-            Err(_) => {
-                return Err(LexerError {
-                    kind: LexerErrorKind::InvalidIntegerLiteral,
-                    span: Span::new(start, self.pos),
-                });
+            Err(_) => Err(error(LexerErrorKind::InvalidIntegerLiteral)),
+        }
+    }
+}
+
+/// The value of an integer constant as spelled, and whether its suffix
+/// makes it unsigned (C17 6.4.4.1).
+pub fn integer_value(text: &str) -> Result<(u64, bool), LexerErrorKind> {
+    let (radix, digits) = match text.as_bytes() {
+        [b'0', b'x' | b'X', ..] => (16, &text[2..]),
+        [b'0', b'b' | b'B', ..] => (2, &text[2..]),
+        [b'0', ..] => (8, text),
+        _ => (10, text),
+    };
+    let end = digits
+        .find(|ch: char| !ch.is_digit(radix.max(10)))
+        .unwrap_or(digits.len());
+    let (digits, suffix) = digits.split_at(end);
+
+    let unsigned = match suffix {
+        "" | "l" | "L" | "ll" | "LL" => false,
+        "u" | "U" | "ul" | "uL" | "Ul" | "UL" | "ull" | "uLL" | "Ull" | "ULL" | "lu" | "lU"
+        | "Lu" | "LU" | "llu" | "llU" | "LLu" | "LLU" => true,
+        _ => return Err(LexerErrorKind::InvalidConstSuffix),
+    };
+    match u64::from_str_radix(digits, radix) {
+        Ok(value) => Ok((value, unsigned)),
+        Err(_) => Err(LexerErrorKind::InvalidIntegerLiteral),
+    }
+}
+
+/// Where each byte of the value of `spelling`, a string literal as
+/// written, is written in it, followed by where its closing quote is, for
+/// pointing into the literal.
+pub fn string_literal_offsets(spelling: &str) -> Option<Vec<usize>> {
+    let quote = spelling.find('"')?;
+    let mut lexer = Lexer::new(spelling);
+    while lexer.pos < quote {
+        lexer.consume_char();
+    }
+    lexer.string_contents(0).ok().map(|(_, offsets)| offsets)
+}
+
+/// Advances `iter` past any line splices and returns the character after
+/// them.
+fn spliced_next(iter: &mut CharIndices<'_>) -> Option<(usize, char)> {
+    loop {
+        let next = iter.next();
+        if let Some((_, '\\')) = next {
+            let mut ahead = iter.clone();
+            match ahead.next() {
+                Some((_, '\n')) => {
+                    *iter = ahead;
+                    continue;
+                }
+                Some((_, '\r')) if matches!(ahead.next(), Some((_, '\n'))) => {
+                    *iter = ahead;
+                    continue;
+                }
+                _ => {}
             }
         }
+
+        return next;
     }
 }
 
@@ -131,11 +601,16 @@ impl<'a> Iterator for Lexer<'a> {
     type Item = Result<Token, LexerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        self.skip_ws();
+        if let Err(err) = self.skip_ws() {
+            return Some(Err(err));
+        }
+        self.start_token();
 
         match self.peek_char() {
-            Some((_, ch)) if ch == '_' || ch.is_ascii_alphabetic() => Some(Ok(self.identifier())),
+            Some((_, ch)) if ch == '_' || ch.is_ascii_alphabetic() => Some(self.identifier()),
             Some((_, ch)) if ch.is_ascii_digit() => Some(self.constant()),
+            Some((i, '\'')) => Some(self.char_constant(i, CharPrefix::None)),
+            Some((i, '"')) => Some(self.string_literal(i)),
             Some((i, '(')) => {
                 self.consume_char();
                 Some(Ok(Token {
@@ -176,6 +651,26 @@ impl<'a> Iterator for Lexer<'a> {
                     },
                 }))
             }
+            Some((i, '[')) => {
+                self.consume_char();
+                Some(Ok(Token {
+                    kind: TokenKind::OpenBracket,
+                    span: Span {
+                        start: i,
+                        end: i + 1,
+                    },
+                }))
+            }
+            Some((i, ']')) => {
+                self.consume_char();
+                Some(Ok(Token {
+                    kind: TokenKind::CloseBracket,
+                    span: Span {
+                        start: i,
+                        end: i + 1,
+                    },
+                }))
+            }
             Some((i, ';')) => {
                 self.consume_char();
                 Some(Ok(Token {
@@ -186,38 +681,120 @@ impl<'a> Iterator for Lexer<'a> {
                     },
                 }))
             }
-            Some((i, '~')) => {
+            Some((i, ',')) => {
                 self.consume_char();
                 Some(Ok(Token {
-                    kind: TokenKind::Complement,
+                    kind: TokenKind::Comma,
                     span: Span {
                         start: i,
                         end: i + 1,
                     },
                 }))
             }
-            Some((i, '-')) => {
+            Some((i, '~')) => {
                 self.consume_char();
-                let (token_kind, end) = match self.peek_char() {
-                    Some((_, '-')) => {
-                        self.consume_char();
-                        (TokenKind::Complement, i + 1)
-                    },
-                    _ => (TokenKind::Negation, i),
-                };
-
                 Some(Ok(Token {
-                    kind: token_kind,
+                    kind: TokenKind::Complement,
                     span: Span {
                         start: i,
-                        end: end + 1,
+                        end: i + 1,
                     },
                 }))
             }
-            Some((i, c)) => Some(Err(LexerError {
-                kind: LexerErrorKind::UnexpectedCharacter(c),
-                span: Span::single(i),
-            })),
+            Some((i, '.')) => Some(Ok(
+                self.punctuator(i, &[("...", TokenKind::Ellipsis), (".", TokenKind::Dot)])
+            )),
+            Some((i, '-')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("->", TokenKind::Arrow),
+                    ("--", TokenKind::Decrement),
+                    ("-=", TokenKind::SubtractAssign),
+                    ("-", TokenKind::Negation),
+                ],
+            ))),
+            Some((i, '+')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("++", TokenKind::Increment),
+                    ("+=", TokenKind::AddAssign),
+                    ("+", TokenKind::Plus),
+                ],
+            ))),
+            Some((i, '&')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("&&", TokenKind::LogicalAnd),
+                    ("&=", TokenKind::AndAssign),
+                    ("&", TokenKind::Ampersand),
+                ],
+            ))),
+            Some((i, '|')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("||", TokenKind::LogicalOr),
+                    ("|=", TokenKind::OrAssign),
+                    ("|", TokenKind::Pipe),
+                ],
+            ))),
+            Some((i, '<')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("<<=", TokenKind::ShiftLeftAssign),
+                    ("<<", TokenKind::ShiftLeft),
+                    ("<=", TokenKind::LessEqual),
+                    ("<", TokenKind::Less),
+                ],
+            ))),
+            Some((i, '>')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    (">>=", TokenKind::ShiftRightAssign),
+                    (">>", TokenKind::ShiftRight),
+                    (">=", TokenKind::GreaterEqual),
+                    (">", TokenKind::Greater),
+                ],
+            ))),
+            Some((i, '*')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("*=", TokenKind::MultiplyAssign),
+                    ("*", TokenKind::Asterisk),
+                ],
+            ))),
+            Some((i, '/')) => Some(Ok(self.punctuator(
+                i,
+                &[("/=", TokenKind::DivideAssign), ("/", TokenKind::Slash)],
+            ))),
+            Some((i, '%')) => Some(Ok(self.punctuator(
+                i,
+                &[
+                    ("%=", TokenKind::RemainderAssign),
+                    ("%", TokenKind::Percent),
+                ],
+            ))),
+            Some((i, '^')) => Some(Ok(
+                self.punctuator(i, &[("^=", TokenKind::XorAssign), ("^", TokenKind::Caret)])
+            )),
+            Some((i, '=')) => Some(Ok(
+                self.punctuator(i, &[("==", TokenKind::Equal), ("=", TokenKind::Assign)])
+            )),
+            Some((i, '!')) => Some(Ok(self.punctuator(
+                i,
+                &[("!=", TokenKind::NotEqual), ("!", TokenKind::Exclamation)],
+            ))),
+            Some((i, '#')) => Some(Ok(
+                self.punctuator(i, &[("##", TokenKind::HashHash), ("#", TokenKind::Hash)])
+            )),
+            Some((i, '?')) => Some(Ok(self.punctuator(i, &[("?", TokenKind::Question)]))),
+            Some((i, ':')) => Some(Ok(self.punctuator(i, &[(":", TokenKind::Colon)]))),
+            Some((i, c)) => {
+                self.consume_char();
+                Some(Err(LexerError {
+                    kind: LexerErrorKind::UnexpectedCharacter(c),
+                    span: Span::new(i, self.pos),
+                }))
+            }
             None => None,
         }
     }
@@ -259,6 +836,39 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn integer_constants() -> Result<(), LexerError> {
+        let lexer = Lexer::new("0x1F 017 0b11 0 10u 10UL 10llu");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        let values = [31, 15, 3, 0, 10, 10, 10].map(|n| TokenKind::Constant(Const::Int(n)));
+        assert_eq!(tokens, values);
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_integer_constants() {
+        let cases = [
+            ("1.5", LexerErrorKind::InvalidConstSuffix, 3),
+            ("1e+5", LexerErrorKind::InvalidConstSuffix, 4),
+            ("10lL", LexerErrorKind::InvalidConstSuffix, 4),
+            ("09", LexerErrorKind::InvalidIntegerLiteral, 2),
+            (
+                "18446744073709551615",
+                LexerErrorKind::InvalidIntegerLiteral,
+                20,
+            ),
+        ];
+        for (src, kind, end) in cases {
+            let mut lexer = Lexer::new(src);
+            let err = lexer.next().unwrap().unwrap_err();
+            assert_eq!((err.kind, err.span), (kind, Span::new(0, end)), "{src}");
+            assert!(lexer.next().is_none(), "{src}");
+        }
+        assert_eq!(integer_value("18446744073709551615u"), Ok((u64::MAX, true)));
+    }
+
     #[test]
     fn open_parenthesis() -> Result<(), LexerError> {
         let lexer = Lexer::new("(");
@@ -309,6 +919,409 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn asterisk() -> Result<(), LexerError> {
+        let lexer = Lexer::new("*");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(tokens.as_slice(), [TokenKind::Asterisk]));
+        Ok(())
+    }
+
+    #[test]
+    fn brackets() -> Result<(), LexerError> {
+        let lexer = Lexer::new("[]");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [TokenKind::OpenBracket, TokenKind::CloseBracket]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn comma_ampersand_assign() -> Result<(), LexerError> {
+        let lexer = Lexer::new(",& =");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [TokenKind::Comma, TokenKind::Ampersand, TokenKind::Assign]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn ellipsis() -> Result<(), LexerError> {
+        let lexer = Lexer::new("...");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(tokens.as_slice(), [TokenKind::Ellipsis]));
+        Ok(())
+    }
+
+    #[test]
+    fn two_dots() -> Result<(), LexerError> {
+        let lexer = Lexer::new("..");
+        let tokens = lexer.collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                Token {
+                    kind: TokenKind::Dot,
+                    span: Span { start: 0, end: 1 },
+                },
+                Token {
+                    kind: TokenKind::Dot,
+                    span: Span { start: 1, end: 2 },
+                },
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn qualifiers() -> Result<(), LexerError> {
+        let lexer = Lexer::new("const volatile restrict");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Keyword(Keyword::Const),
+                TokenKind::Keyword(Keyword::Volatile),
+                TokenKind::Keyword(Keyword::Restrict),
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn char_constant() -> Result<(), LexerError> {
+        let lexer = Lexer::new("'a'");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [TokenKind::Constant(Const::Char(97, CharPrefix::None))]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn char_escapes() -> Result<(), LexerError> {
+        let lexer = Lexer::new(r"'\n' '\'' '\\' '\0' '\101' '\x41' '\xff' '\?'");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Constant(Const::Char(10, _)),
+                TokenKind::Constant(Const::Char(39, _)),
+                TokenKind::Constant(Const::Char(92, _)),
+                TokenKind::Constant(Const::Char(0, _)),
+                TokenKind::Constant(Const::Char(65, _)),
+                TokenKind::Constant(Const::Char(65, _)),
+                TokenKind::Constant(Const::Char(-1, _)),
+                TokenKind::Constant(Const::Char(63, _)),
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn char_prefixes() -> Result<(), LexerError> {
+        let lexer = Lexer::new(r"L'\xffffffff' u'\xffff' U'€' u8'a' L");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Constant(Const::Char(-1, CharPrefix::Wide)),
+                TokenKind::Constant(Const::Char(0xffff, CharPrefix::Utf16)),
+                TokenKind::Constant(Const::Char(0x20ac, CharPrefix::Utf32)),
+                TokenKind::Constant(Const::Char(97, CharPrefix::Utf8)),
+                TokenKind::Identifier(l),
+            ] if l == "L"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn multi_char_constant() -> Result<(), LexerError> {
+        let mut lexer = Lexer::new("'ab'");
+        let tokens = lexer
+            .by_ref()
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [TokenKind::Constant(Const::Char(0x6162, CharPrefix::None))]
+        ));
+        assert!(matches!(
+            lexer.warnings(),
+            [LexerWarning {
+                kind: LexerWarningKind::MultiCharConstant,
+                span: Span { start: 0, end: 4 },
+            }]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_char_constants() {
+        let cases = [
+            (r"x = '\q';", 5, 7),
+            (r"'\x100'", 1, 6),
+            (r"u8'\x100'", 3, 8),
+            (r"'\x'", 1, 3),
+            ("''", 0, 2),
+            ("'a\n'", 0, 2),
+            ("u'😀'", 2, 6),
+            ("u'ab'", 0, 5),
+            ("'abcde'", 0, 7),
+        ];
+        for (src, start, end) in cases {
+            let err = Lexer::new(src)
+                .collect::<Result<Vec<_>, _>>()
+                .expect_err(src);
+            assert_eq!(err.span, Span::new(start, end), "{src}");
+        }
+    }
+
+    #[test]
+    fn string_literal() -> Result<(), LexerError> {
+        let lexer = Lexer::new(r#""a'\n\x41" u8"é" """#);
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::StringLiteral(a),
+                TokenKind::StringLiteral(b),
+                TokenKind::StringLiteral(c),
+            ] if a == b"a'\nA" && b == "é".as_bytes() && c.is_empty()
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn string_literal_offsets_of_an_escape() {
+        let offsets = string_literal_offsets(r#""a\x25s""#);
+        assert_eq!(offsets.as_deref(), Some(&[1, 2, 6, 7][..]));
+    }
+
+    #[test]
+    fn string_literal_offsets_of_a_multibyte_character() {
+        let offsets = string_literal_offsets("u8\"é\"");
+        assert_eq!(offsets.as_deref(), Some(&[3, 3, 5][..]));
+    }
+
+    #[test]
+    fn string_literal_offsets_across_a_line_splice() {
+        let offsets = string_literal_offsets("\"a\\\nb\"");
+        assert_eq!(offsets.as_deref(), Some(&[1, 4, 5][..]));
+    }
+
+    #[test]
+    fn string_literal_offsets_of_an_empty_literal() {
+        let offsets = string_literal_offsets(r#""""#);
+        assert_eq!(offsets.as_deref(), Some(&[1][..]));
+    }
+
+    #[test]
+    fn no_string_literal_offsets_for_an_invalid_literal() {
+        assert_eq!(string_literal_offsets(r#""\x100""#), None);
+    }
+
+    #[test]
+    fn invalid_string_literals() {
+        let cases = [
+            ("\"abc\n\"", 0, 4),
+            (r#"L"abc""#, 0, 1),
+            (r#""\x100""#, 1, 6),
+        ];
+        for (src, start, end) in cases {
+            let err = Lexer::new(src)
+                .collect::<Result<Vec<_>, _>>()
+                .expect_err(src);
+            assert_eq!(err.span, Span::new(start, end), "{src}");
+        }
+    }
+
+    #[test]
+    fn comments() -> Result<(), LexerError> {
+        let lexer = Lexer::new("int // line comment\n/* block\n * comment */ x; // at eof");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Keyword(Keyword::Int),
+                TokenKind::Identifier(x),
+                TokenKind::Semicolon,
+            ] if x == "x"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn comment_separates_tokens() -> Result<(), LexerError> {
+        let lexer = Lexer::new("in/**/t");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [TokenKind::Identifier(a), TokenKind::Identifier(b)] if a == "in" && b == "t"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn line_splices() -> Result<(), LexerError> {
+        let lexer = Lexer::new("in\\\nt 4\\\r\n2 \"a\\\nb\" // spliced \\\n comment\n'\\\\\n0'");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Keyword(Keyword::Int),
+                TokenKind::Constant(Const::Int(42)),
+                TokenKind::StringLiteral(s),
+                TokenKind::Constant(Const::Char(0, CharPrefix::None)),
+            ] if s == b"ab"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn spliced_token_span() {
+        let mut lexer = Lexer::new("ma\\\nin");
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token {
+                kind: TokenKind::Identifier(_),
+                span: Span { start: 0, end: 6 },
+            }))
+        ));
+    }
+
+    #[test]
+    fn unterminated_comment() {
+        let mut lexer = Lexer::new("x /* no end *");
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        assert!(matches!(
+            lexer.next(),
+            Some(Err(LexerError {
+                kind: LexerErrorKind::UnterminatedComment,
+                span: Span { start: 2, end: 4 },
+            }))
+        ));
+    }
+
+    #[test]
+    fn lone_slash() -> Result<(), LexerError> {
+        let lexer = Lexer::new("/");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(tokens.as_slice(), [TokenKind::Slash]));
+        Ok(())
+    }
+
+    #[test]
+    fn punctuators() -> Result<(), LexerError> {
+        let src = "-> ++ -- & * + - ~ ! / % << >> < > <= >= == != ^ | && || ? : \
+                   *= /= %= += -= <<= >>= &= ^= |= # ## . ...";
+        let lexer = Lexer::new(src);
+        let spelled = lexer
+            .map(|res| res.map(|t| t.kind.to_string()))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert_eq!(spelled.join(" "), src);
+        Ok(())
+    }
+
+    #[test]
+    fn punctuators_take_longest_match() -> Result<(), LexerError> {
+        let lexer = Lexer::new("a+++b<<=c-\\\n>d");
+        let tokens = lexer
+            .map(|res| res.map(|t| t.kind))
+            .collect::<Result<Vec<_>, _>>()?;
+        assert!(matches!(
+            tokens.as_slice(),
+            [
+                TokenKind::Identifier(_),
+                TokenKind::Increment,
+                TokenKind::Plus,
+                TokenKind::Identifier(_),
+                TokenKind::ShiftLeftAssign,
+                TokenKind::Identifier(_),
+                TokenKind::Arrow,
+                TokenKind::Identifier(_),
+            ]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn line_start_and_space_before() {
+        let mut lexer = Lexer::new("# a  b /* c\n */ d\n\te");
+        let mut flags = Vec::new();
+        while let Some(Ok(_)) = lexer.next() {
+            flags.push((lexer.line_start(), lexer.space_before()));
+        }
+        assert_eq!(
+            flags,
+            [
+                (true, false),
+                (false, true),
+                (false, true),
+                (false, true),
+                (true, true)
+            ]
+        );
+    }
+
+    #[test]
+    fn header_name() {
+        let mut lexer = Lexer::new("include <a\\b//c.h> \"d.h\"\n<e.h>");
+        assert!(matches!(lexer.next(), Some(Ok(_))));
+        assert!(matches!(
+            lexer.header_name(),
+            Some(Ok(Token {
+                kind: TokenKind::HeaderName(name),
+                span: Span { start: 8, end: 18 },
+            })) if name == "<a\\b//c.h>"
+        ));
+        assert!(matches!(
+            lexer.header_name(),
+            Some(Ok(Token { kind: TokenKind::HeaderName(name), .. })) if name == "\"d.h\""
+        ));
+        // The next line is not part of the directive.
+        assert!(lexer.header_name().is_none());
+        assert!(matches!(
+            lexer.next(),
+            Some(Ok(Token {
+                kind: TokenKind::Less,
+                ..
+            }))
+        ));
+        assert!(lexer.line_start());
+    }
+
     #[test]
     fn simple_main() -> Result<(), LexerError> {
         let file = "int main(void) {
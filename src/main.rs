@@ -1,20 +1,26 @@
-use clap::Parser;
+use clap::{CommandFactory, FromArgMatches, Parser};
+use std::ffi::OsString;
 use std::io::Error;
-use std::io::ErrorKind;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::Command;
-mod lexer;
-mod token;
-use lexer::Lexer;
+mod diagnostics;
 pub mod error;
+mod lexer;
 mod parser;
 pub mod span;
-use crate::error::CompilerError;
-use crate::error::LexerError;
+mod token;
+mod types;
+use crate::diagnostics::{Diagnostics, WarningOptions};
+use crate::error::{CompilerError, UnknownWarningOption};
+use crate::preprocessor::{IncludePaths, PpToken, Preprocessor};
+use crate::source_map::SourceMap;
 use error::AppError;
 mod asm;
 mod ast;
+mod preprocessor;
 mod pretty;
+mod semantic;
+mod source_map;
 
 /// Rust C Compiler
 #[derive(Parser, Debug)]
@@ -28,6 +34,10 @@ struct Args {
     #[arg(long)]
     parse: bool,
 
+    /// Run the lexer, parser and semantic analysis, but stop before assembly generation
+    #[arg(long)]
+    validate: bool,
+
     /// Perform lexing, parsing, and assembly generation, but stop before code emission
     #[arg(long)]
     codegen: bool,
@@ -37,16 +47,229 @@ struct Args {
     #[allow(non_snake_case)]
     s: bool,
 
+    /// Only preprocess, writing the result to stdout or the `-o` file.
+    #[arg(short = 'E')]
+    preprocess_only: bool,
+
+    /// Leave the linemarkers out of the preprocessed output.
+    #[arg(short = 'P')]
+    no_linemarkers: bool,
+
+    /// With `M`, write the macros defined at the end of preprocessing
+    /// instead of the preprocessed output.
+    #[arg(short = 'd', value_name = "FLAGS", value_parser = ["M"])]
+    dump: Option<String>,
+
+    /// Write the output to this file.
+    #[arg(short = 'o', value_name = "FILE")]
+    output: Option<String>,
+
+    /// List the tokens produced by macro expansions, each with the chain of
+    /// expansions it came from.
+    #[arg(long)]
+    trace_macros: bool,
+
     /// Print the AST with pretty-print.
     #[arg(long)]
     pretty_print: bool,
 
+    /// Define a macro, as `1` unless a value is given.
+    #[arg(short = 'D', value_name = "NAME[=VALUE]")]
+    defines: Vec<String>,
+
+    /// Undefine a macro.
+    #[arg(short = 'U', value_name = "NAME")]
+    undefines: Vec<String>,
+
+    /// Add a directory to search for headers, before the system ones.
+    #[arg(short = 'I', value_name = "DIR")]
+    include_dirs: Vec<PathBuf>,
+
+    /// Add a directory to search for system headers, before the default ones.
+    #[arg(long = "isystem", value_name = "DIR")]
+    system_include_dirs: Vec<PathBuf>,
+
+    /// Include a file before the translation unit.
+    #[arg(long = "include", value_name = "FILE")]
+    preincludes: Vec<String>,
+
+    /// Write a make rule for the headers the file depends on, instead of
+    /// the preprocessed output.
+    #[arg(long = "M")]
+    dependencies_only: bool,
+
+    /// Like `-M`, but leave out system headers.
+    #[arg(long = "MM")]
+    user_dependencies_only: bool,
+
+    /// Write a make rule for the headers the file depends on to a `.d`
+    /// file while compiling.
+    #[arg(long = "MD")]
+    dependencies: bool,
+
+    /// Like `-MD`, but leave out system headers.
+    #[arg(long = "MMD")]
+    user_dependencies: bool,
+
+    /// Write the make rule to this file.
+    #[arg(long = "MF", value_name = "FILE")]
+    dependency_file: Option<String>,
+
+    /// The target of the make rule, instead of the object file.
+    #[arg(long = "MT", value_name = "TARGET")]
+    dependency_targets: Vec<String>,
+
+    /// Add an empty rule for each header, so that make does not fail once
+    /// one is deleted.
+    #[arg(long = "MP")]
+    phony_dependencies: bool,
+
+    /// Enable a warning or group of warnings, such as `all` or `extra`, or
+    /// disable it with `no-<name>`. `error` makes every warning an error,
+    /// and `error=<name>` one warning.
+    #[arg(short = 'W', value_name = "WARNING")]
+    warnings: Vec<String>,
+
+    /// Suppress all warnings.
+    #[arg(short = 'w')]
+    no_warnings: bool,
+
+    /// The `-D` and `-U` options in the order given, which decides the
+    /// outcome when they name the same macro.
+    #[arg(skip)]
+    macro_options: Vec<MacroOption>,
+
     /// Path to the file to compile.
     file_path: String,
 }
 
+#[derive(Debug, Clone)]
+enum MacroOption {
+    Define(String),
+    Undefine(String),
+}
+
+/// The options GCC spells with a single dash, though they are not single
+/// letters, and whether each takes a value.
+const SINGLE_DASH_OPTIONS: &[(&str, bool)] = &[
+    ("-isystem", true),
+    ("-include", true),
+    ("-M", false),
+    ("-MM", false),
+    ("-MD", false),
+    ("-MMD", false),
+    ("-MF", true),
+    ("-MT", true),
+    ("-MP", false),
+];
+
 fn main() -> Result<(), AppError> {
-    run(Args::parse())
+    let args = parse_args(std::env::args_os()).unwrap_or_else(|err| err.exit());
+
+    run(args)
+}
+
+/// Parses the command line, with the options GCC spells with a single
+/// dash.
+fn parse_args(argv: impl IntoIterator<Item = OsString>) -> Result<Args, clap::Error> {
+    let matches = command().try_get_matches_from(argv.into_iter().map(long_option))?;
+    let mut args = Args::from_arg_matches(&matches)?;
+    args.macro_options = macro_options(&matches);
+
+    Ok(args)
+}
+
+/// The command line, whose help lists the single-dash options apart, as
+/// GCC spells them, rather than as the long options they are parsed as.
+fn command() -> clap::Command {
+    let mut command = Args::command();
+    let styles = command.get_styles();
+    let (header, literal) = (*styles.get_header(), *styles.get_literal());
+
+    // Each option, as spelled, with the length of its name.
+    let spell = |arg: &clap::Arg| {
+        let long = arg.get_long()?;
+        let (option, takes_value) = SINGLE_DASH_OPTIONS
+            .iter()
+            .find(|(option, _)| option[1..] == *long)?;
+        match arg.get_value_names() {
+            Some([value]) if *takes_value => Some((format!("{option} <{value}>"), option.len())),
+            _ => Some((option.to_string(), option.len())),
+        }
+    };
+    let listed = command
+        .get_arguments()
+        .filter_map(|arg| Some((spell(arg)?, arg.get_help()?.to_string())))
+        .collect::<Vec<_>>();
+    let width = listed
+        .iter()
+        .map(|((spelling, _), _)| spelling.len())
+        .max()
+        .unwrap_or(0);
+    let mut help = format!("{header}Single-dash options:{header:#}\n");
+    for ((spelling, name_len), text) in listed {
+        let (name, value) = spelling.split_at(name_len);
+        let pad = " ".repeat(width - spelling.len() + 2);
+        help.push_str(&format!("  {literal}{name}{literal:#}{value}{pad}{text}\n"));
+    }
+
+    command = command.mut_args(|arg| match spell(&arg) {
+        Some(_) => arg.hide(true),
+        None => arg,
+    });
+    command.after_help(help.trim_end().to_string())
+}
+
+/// Spells a single-dash option as the long option clap knows, so that
+/// `-isystem dir` and `-isystemdir` both work.
+fn long_option(arg: OsString) -> OsString {
+    let Some(text) = arg.to_str() else {
+        return arg;
+    };
+    let option = SINGLE_DASH_OPTIONS.iter().find(|(option, takes_value)| {
+        text == *option || (*takes_value && text.starts_with(option))
+    });
+    match option {
+        Some((option, _)) if text.len() == option.len() => OsString::from(format!("-{text}")),
+        Some((option, _)) => OsString::from(format!("-{option}={}", &text[option.len()..])),
+        None => arg,
+    }
+}
+
+/// Interleaves the `-D` and `-U` options back into command-line order.
+fn macro_options(matches: &clap::ArgMatches) -> Vec<MacroOption> {
+    let mut options = Vec::new();
+    for (id, option) in [
+        ("defines", MacroOption::Define as fn(String) -> MacroOption),
+        ("undefines", MacroOption::Undefine),
+    ] {
+        let values = matches.get_many::<String>(id).into_iter().flatten();
+        let indices = matches.indices_of(id).into_iter().flatten();
+        options.extend(indices.zip(values.map(|value| option(value.clone()))));
+    }
+    options.sort_by_key(|(index, _)| *index);
+
+    options.into_iter().map(|(_, option)| option).collect()
+}
+
+/// The diagnostics engine for the warning options, having reported the
+/// `-W` options that name no warning.
+fn diagnostics(args: &Args, sources: &SourceMap) -> Diagnostics {
+    let mut options = WarningOptions::default();
+    let unknown = args
+        .warnings
+        .iter()
+        .filter(|option| !options.apply(option))
+        .collect::<Vec<_>>();
+    if args.no_warnings {
+        options.suppress_all();
+    }
+
+    let mut diagnostics = Diagnostics::new(options);
+    for option in unknown {
+        diagnostics.report(sources, &UnknownWarningOption::new(format!("-W{option}")));
+    }
+    diagnostics
 }
 
 fn with_extension(path: &str, ext: &str) -> String {
@@ -66,39 +289,190 @@ fn run_cmd(cmd: &str, args: &[&str]) -> Result<(), std::io::Error> {
     }
 
     let code = out.status.code().unwrap_or(-1);
-    Err(Error::new(
-        ErrorKind::Other,
-        format!(
-            "{cmd} exited with {code}\n{}",
-            String::from_utf8_lossy(&out.stderr)
-        ),
+    Err(Error::other(format!(
+        "{cmd} exited with {code}\n{}",
+        String::from_utf8_lossy(&out.stderr)
+    )))
+}
+
+/// Where `-MD` writes the make rule: the `-MF` file, else the output file
+/// or, without one, the input file's name with the extension `.d`.
+fn dependency_file(args: &Args) -> String {
+    if let Some(file) = &args.dependency_file {
+        return file.clone();
+    }
+    match &args.output {
+        Some(output) => with_extension(output, "d"),
+        None => {
+            let input = Path::new(&args.file_path);
+            with_extension(
+                &input.file_name().unwrap_or_default().to_string_lossy(),
+                "d",
+            )
+        }
+    }
+}
+
+/// What the compiler produced: the output to write, if any, and the make
+/// rule that `-MD` writes alongside it.
+#[derive(Default)]
+struct Compiled {
+    output: Option<String>,
+    dependencies: Option<String>,
+}
+
+/// The make rule for the headers the input depends on, if any `-M` option
+/// asks for one.
+fn dependency_rule(args: &Args, preprocessor: &Preprocessor) -> Option<String> {
+    let system = args.dependencies_only || args.dependencies;
+    if !system && !args.user_dependencies_only && !args.user_dependencies {
+        return None;
+    }
+
+    // The rule is for the output file of `-MD`, or else for the object
+    // file of the input in the working directory, unless `-MT` names the
+    // targets.
+    let input = Path::new(&args.file_path);
+    let targets = match (args.dependency_targets.as_slice(), &args.output) {
+        ([], Some(output)) if args.dependencies || args.user_dependencies => {
+            vec![output.clone()]
+        }
+        ([], _) => {
+            let name = input.file_name().unwrap_or_default().to_string_lossy();
+            vec![with_extension(&name, "o")]
+        }
+        (targets, _) => targets.to_vec(),
+    };
+    let mut prerequisites = vec![input];
+    prerequisites.extend(preprocessor.dependencies(system));
+
+    Some(preprocessor::output::make_rule(
+        &targets,
+        &prerequisites,
+        args.phony_dependencies,
     ))
 }
 
-fn run_compiler(args: &Args, pre: &str) -> Result<Option<String>, CompilerError> {
-    if args.lex {
-        let lexer = Lexer::new(&pre);
-        lexer.collect::<Result<Vec<_>, LexerError>>()?;
+/// The preprocessed tokens, with the macros they ended with for `-dM` and
+/// the make rule for the headers read for an `-M` option.
+struct Preprocessed {
+    tokens: Vec<PpToken>,
+    macros: Option<String>,
+    dependencies: Option<String>,
+}
+
+/// Runs the preprocessor over the input file, rendering its warnings and
+/// recording its diagnostic pragmas.
+fn preprocess(
+    args: &Args,
+    sources: &mut SourceMap,
+    src: String,
+    diagnostics: &mut Diagnostics,
+) -> Result<Preprocessed, CompilerError> {
+    let mut include_paths = IncludePaths {
+        angled: args.include_dirs.clone(),
+        ..IncludePaths::default()
+    };
+    include_paths
+        .system
+        .splice(0..0, args.system_include_dirs.iter().cloned());
+
+    let mut preprocessor = Preprocessor::new(sources, include_paths);
+    for option in &args.macro_options {
+        match option {
+            MacroOption::Define(definition) => preprocessor.predefine(definition),
+            MacroOption::Undefine(name) => preprocessor.preundefine(name),
+        }
+    }
+    for path in &args.preincludes {
+        preprocessor.preinclude(path);
+    }
+    let tokens = preprocessor.run(Path::new(&args.file_path), src);
+    let macros = args
+        .dump
+        .is_some()
+        .then(|| preprocessor.macro_definitions());
+    let dependencies = dependency_rule(args, &preprocessor);
+    let pragmas = preprocessor.diagnostic_pragmas().to_vec();
+    let warnings = preprocessor.into_warnings();
+    diagnostics.add_pragmas(sources, &pragmas);
+    for warning in &warnings {
+        diagnostics.report(sources, warning);
+    }
+    if args.trace_macros
+        && let Ok(tokens) = &tokens
+    {
+        eprint!("{}", preprocessor::output::trace(sources, tokens));
+    }
 
-        return Ok(None);
+    Ok(Preprocessed {
+        tokens: tokens?,
+        macros,
+        dependencies,
+    })
+}
+
+fn run_compiler(
+    args: &Args,
+    sources: &mut SourceMap,
+    src: String,
+    diagnostics: &mut Diagnostics,
+) -> Result<Compiled, CompilerError> {
+    let Preprocessed {
+        tokens,
+        macros,
+        dependencies,
+    } = preprocess(args, sources, src, diagnostics)?;
+    if args.dependencies_only || args.user_dependencies_only {
+        return Ok(Compiled {
+            output: dependencies,
+            dependencies: None,
+        });
+    }
+    let compiled = |output| Compiled {
+        output,
+        dependencies: dependencies.clone(),
+    };
+    if macros.is_some() {
+        return Ok(compiled(macros));
+    }
+    if args.preprocess_only {
+        let linemarkers = !args.no_linemarkers;
+        let output = preprocessor::output::write(sources, &tokens, linemarkers);
+        return Ok(compiled(Some(output)));
     }
+    if args.lex {
+        return Ok(compiled(None));
+    }
+
+    let mut parser = parser::Parser::new(tokens.into_iter().map(PpToken::into_token));
+    let ast = parser.parse()?;
 
     if args.parse {
-        let lexer = Lexer::new(&pre);
-        let mut parser = parser::Parser::new(lexer);
+        if args.pretty_print {
+            println!("{}", ast);
+        }
 
-        let ast = parser.parse()?;
+        return Ok(compiled(None));
+    }
 
+    let mut warnings = Vec::new();
+    let ast = semantic::analyze(ast, &mut warnings);
+    // Each pass finds its own warnings; they are reported in source order.
+    warnings.sort_by_cached_key(|warning| sources.translation_order(warning.span.start));
+    for warning in &warnings {
+        diagnostics.report(sources, warning);
+    }
+    let ast = ast?;
+
+    if args.validate {
         if args.pretty_print {
             println!("{}", ast);
         }
 
-        return Ok(None);
+        return Ok(compiled(None));
     }
 
-    let lexer = Lexer::new(&pre);
-    let mut parser = parser::Parser::new(lexer);
-    let ast = parser.parse()?;
     let asm = asm::Program::from(ast);
 
     if args.codegen {
@@ -106,34 +480,69 @@ fn run_compiler(args: &Args, pre: &str) -> Result<Option<String>, CompilerError>
             println!("{}", asm);
         }
 
-        return Ok(None);
+        return Ok(compiled(None));
     }
 
-    Ok(Some(asm.to_string()))
+    Ok(compiled(Some(asm.to_string())))
 }
 
 fn run(args: Args) -> Result<(), AppError> {
     let input = &args.file_path;
-    let pre: String = with_extension(input, "i");
-    run_cmd("gcc", &["-E", "-P", input, "-o", &pre])?;
-
-    let pre_str: String = std::fs::read_to_string(&pre)?;
-    std::fs::remove_file(pre)?;
-    let assembly = with_extension(input, "s");
-    match run_compiler(&args, &pre_str) {
-        Ok(Some(res)) => std::fs::write(&assembly, &res)?,
+    let src = std::fs::read_to_string(input)?;
+    let mut sources = SourceMap::default();
+    let assembly = match &args.output {
+        Some(output) if args.s => output.clone(),
+        _ => with_extension(input, "s"),
+    };
+    let preprocess_only = args.preprocess_only
+        || args.dump.is_some()
+        || args.dependencies_only
+        || args.user_dependencies_only;
+    let mut diagnostics = diagnostics(&args, &sources);
+    let compiled = match run_compiler(&args, &mut sources, src, &mut diagnostics) {
+        // Warnings made errors fail the compilation once all are reported.
+        Ok(_) if diagnostics.failed() => return Err(AppError::Compiler),
+        Ok(compiled) => compiled,
         Err(CompilerError::Lexer(err)) => {
-            error::render_diagnostic(&pre_str, &err);
+            error::render_diagnostic(&sources, &err);
+            return Err(AppError::Compiler);
+        }
+        Err(CompilerError::Preprocessor(err)) => {
+            error::render_diagnostic(&sources, &err);
             return Err(AppError::Compiler);
         }
         Err(CompilerError::Parser(err)) => {
-            error::render_diagnostic(&pre_str, &err);
+            error::render_diagnostic(&sources, &err);
+            return Err(AppError::Compiler);
+        }
+        Err(CompilerError::Semantic(err)) => {
+            error::render_diagnostic(&sources, &err);
             return Err(AppError::Compiler);
         }
-        _ => (),
+    };
+
+    if let Some(rule) = compiled.dependencies {
+        std::fs::write(dependency_file(&args), rule)?;
+    }
+    match compiled.output {
+        // Preprocessed output goes to stdout unless `-o` names a file, or
+        // for the rule of `-M`, `-MF` does.
+        Some(res) if preprocess_only => {
+            let output = match args.dependencies_only || args.user_dependencies_only {
+                true => args.dependency_file.as_ref().or(args.output.as_ref()),
+                false => args.output.as_ref(),
+            };
+            match output {
+                Some(output) => std::fs::write(output, &res)?,
+                None => print!("{res}"),
+            }
+            return Ok(());
+        }
+        Some(res) => std::fs::write(&assembly, &res)?,
+        None => {}
     }
 
-    if args.lex || args.parse || args.codegen {
+    if args.lex || args.parse || args.validate || args.codegen {
         return Ok(());
     }
 
@@ -141,10 +550,172 @@ fn run(args: Args) -> Result<(), AppError> {
         return Ok(());
     }
 
-    let output = with_extension(input, "");
+    let output = args
+        .output
+        .clone()
+        .unwrap_or_else(|| with_extension(input, ""));
     run_cmd("gcc", &[&assembly, "-o", &output])?;
 
     std::fs::remove_file(&assembly)?;
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Compiles `src` as `test.c` with the options `options`, to assembly.
+    fn compile(options: &[&str], src: &str) -> Result<String, CompilerError> {
+        let argv = ["rcc"].iter().chain(options).chain(&["test.c"]);
+        let args = parse_args(argv.map(OsString::from)).expect("the options parse");
+        let mut sources = SourceMap::default();
+        let mut diagnostics = diagnostics(&args, &sources);
+        let compiled = run_compiler(&args, &mut sources, src.to_string(), &mut diagnostics)?;
+        Ok(compiled.output.unwrap_or_default())
+    }
+
+    #[test]
+    fn single_dash_options_are_rewritten() {
+        let cases = [
+            ("-isystem", "--isystem"),
+            ("-isystem/usr/include", "--isystem=/usr/include"),
+            ("-includeconfig.h", "--include=config.h"),
+            ("-M", "--M"),
+            ("-MMD", "--MMD"),
+            ("-MFdeps.d", "--MF=deps.d"),
+            ("-MT", "--MT"),
+            ("-MX", "-MX"),
+            ("-MDx", "-MDx"),
+            ("-I/usr/include", "-I/usr/include"),
+            ("main.c", "main.c"),
+        ];
+        for (arg, rewritten) in cases {
+            assert_eq!(long_option(OsString::from(arg)), rewritten, "{arg}");
+        }
+
+        let argv = [
+            "rcc",
+            "-isystem",
+            "sys",
+            "-includeconfig.h",
+            "-MD",
+            "-MF",
+            "deps.d",
+            "-MTa",
+            "-MT",
+            "b",
+            "-DX",
+            "-UX",
+            "main.c",
+        ];
+        let args = parse_args(argv.map(OsString::from)).unwrap();
+        assert_eq!(args.system_include_dirs, [PathBuf::from("sys")]);
+        assert_eq!(args.preincludes, ["config.h"]);
+        assert!(args.dependencies && !args.dependencies_only);
+        assert_eq!(args.dependency_file.as_deref(), Some("deps.d"));
+        assert_eq!(args.dependency_targets, ["a", "b"]);
+        assert!(matches!(
+            args.macro_options.as_slice(),
+            [MacroOption::Define(x), MacroOption::Undefine(y)] if x == "X" && y == "X"
+        ));
+    }
+
+    #[test]
+    fn help_spells_single_dash_options() {
+        let help = command().render_help().to_string();
+        for option in [
+            "-isystem <DIR>",
+            "-include <FILE>",
+            "-MF <FILE>",
+            "-MT <TARGET>",
+        ] {
+            assert!(help.contains(&format!("  {option}  ")), "{option}");
+        }
+        assert!(help.contains("  -MMD  "));
+        assert!(!help.contains("--isystem") && !help.contains("--M"));
+    }
+
+    #[test]
+    fn overflow_is_warned_about_without_conversion() {
+        use crate::diagnostics::{Severity, Warning};
+
+        let argv = [
+            "rcc",
+            "-Werror",
+            "-Wno-conversion",
+            "-Wsign-compare",
+            "-Wsign-conversion",
+            "-Wshorten-64-to-32",
+            "test.c",
+        ];
+        let args = parse_args(argv.map(OsString::from)).expect("the options parse");
+        let sources = SourceMap::default();
+        let diagnostics = diagnostics(&args, &sources);
+        // An unknown option would have been an error.
+        assert!(!diagnostics.failed());
+        for warning in [Warning::Overflow, Warning::DivisionByZero] {
+            assert_eq!(
+                diagnostics.severity(&sources, warning, 0),
+                Severity::Error,
+                "{warning:?}"
+            );
+        }
+        assert_eq!(
+            diagnostics.severity(&sources, Warning::Conversion, 0),
+            Severity::Ignored
+        );
+    }
+
+    #[test]
+    fn unknown_warning_options_follow_the_options() {
+        let failed = |options: &[&str]| {
+            let argv = ["rcc"].iter().chain(options).chain(&["test.c"]);
+            let args = parse_args(argv.map(OsString::from)).expect("the options parse");
+            diagnostics(&args, &SourceMap::default()).failed()
+        };
+        assert!(!failed(&["-Wbogus"]));
+        assert!(failed(&["-Werror", "-Wbogus"]));
+        assert!(!failed(&[
+            "-Werror",
+            "-Wno-error=unknown-warning-option",
+            "-Wbogus"
+        ]));
+    }
+
+    #[test]
+    fn va_list_is_forwarded() -> Result<(), CompilerError> {
+        let src = "#include <stdarg.h>\n\
+                   int vsum(int count, va_list ap) {\n\
+                   int sum = 0;\n\
+                   return count ? va_arg(ap, int) + vsum(count - 1, ap) : sum;\n\
+                   }\n\
+                   int sum(int count, ...) {\n\
+                   va_list ap;\n\
+                   va_start(ap, count);\n\
+                   int sum = vsum(count, ap);\n\
+                   va_end(ap);\n\
+                   return sum;\n\
+                   }";
+        let asm = compile(&[], src)?;
+        assert!(asm.contains("vsum@PLT"));
+        Ok(())
+    }
+
+    #[test]
+    fn bundled_headers_compile() -> Result<(), CompilerError> {
+        for header in ["limits.h", "stdarg.h", "stddef.h", "stdint.h"] {
+            compile(
+                &[],
+                &format!("#include <{header}>\nint main(void) {{ return 0; }}"),
+            )?;
+        }
+
+        let src = "#include <limits.h>\n#include <stddef.h>\n#include <stdint.h>\n\
+                   int main(void) {\n\
+                   int32_t max = INT_MAX; int8_t min = INT8_MIN; ptrdiff_t d = &max - &max;\n\
+                   int *p = NULL; return CHAR_BIT; }";
+        compile(&[], src)?;
+        Ok(())
+    }
+}
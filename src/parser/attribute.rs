@@ -0,0 +1,103 @@
+use crate::ast::{Archetype, Format, Param, constant};
+use crate::error::ParseError;
+use crate::parser::Parser;
+use crate::span::Span;
+use crate::token::TokenKind;
+use crate::types::TypeKind;
+
+/// The name of an attribute or format archetype without the underscores
+/// that may surround it, as in `__format__`.
+fn attribute_name(name: &str) -> &str {
+    name.strip_prefix("__")
+        .and_then(|name| name.strip_suffix("__"))
+        .unwrap_or(name)
+}
+
+impl<'a> Parser<'a> {
+    /// Parses the `__attribute__((...))` specifiers, if any, after the
+    /// declarator of a function with the parameters `params`. `format` is
+    /// the only attribute understood.
+    pub(super) fn parse_attributes(
+        &mut self,
+        params: &[Param],
+        variadic: bool,
+    ) -> Result<Option<Format>, ParseError> {
+        let mut format = None;
+        let attribute = TokenKind::Identifier(String::from("__attribute__"));
+        while self.next_if(&attribute)?.is_some() {
+            self.expect(TokenKind::OpenParenthesis)?;
+            self.expect(TokenKind::OpenParenthesis)?;
+            while !matches!(
+                self.peek()?.map(|t| &t.kind),
+                Some(TokenKind::CloseParenthesis)
+            ) {
+                let (name, span) = self.parse_attribute_name()?;
+                match attribute_name(&name) {
+                    "format" => format = Some(self.parse_format(params, variadic)?),
+                    _ => return Err(ParseError::UnknownAttribute(name, span)),
+                }
+                if self.next_if(&TokenKind::Comma)?.is_none() {
+                    break;
+                }
+            }
+            self.expect(TokenKind::CloseParenthesis)?;
+            self.expect(TokenKind::CloseParenthesis)?;
+        }
+
+        Ok(format)
+    }
+
+    fn parse_attribute_name(&mut self) -> Result<(String, Span), ParseError> {
+        let token = self.next()?;
+        match token.kind {
+            TokenKind::Identifier(name) => Ok((name, token.span)),
+            _ => Err(ParseError::MissingIdentifier(token.span)),
+        }
+    }
+
+    /// Parses the arguments of `format(archetype, string, first)`, checking
+    /// that `string` is the position of a `char *` parameter and `first`
+    /// that of the `...`, or 0.
+    fn parse_format(&mut self, params: &[Param], variadic: bool) -> Result<Format, ParseError> {
+        self.expect(TokenKind::OpenParenthesis)?;
+        let (name, span) = self.parse_attribute_name()?;
+        let archetype = match attribute_name(&name) {
+            "printf" | "gnu_printf" => Archetype::Printf,
+            "scanf" | "gnu_scanf" => Archetype::Scanf,
+            _ => return Err(ParseError::UnsupportedFormatArchetype(name, span)),
+        };
+        self.expect(TokenKind::Comma)?;
+        let (string, string_span) = self.parse_format_position()?;
+        self.expect(TokenKind::Comma)?;
+        let (first, first_span) = self.parse_format_position()?;
+        self.expect(TokenKind::CloseParenthesis)?;
+
+        let Some(param) = string.checked_sub(1).and_then(|i| params.get(i)) else {
+            return Err(ParseError::FormatArgumentOutOfBounds(2, string_span));
+        };
+        if !matches!(&param.ty.kind, TypeKind::Pointer(pointee) if pointee.kind == TypeKind::Char) {
+            return Err(ParseError::FormatStringNotString(string_span));
+        }
+        if first != 0 && !variadic {
+            return Err(ParseError::FormatRequiresVariadic(first_span));
+        }
+        if first != 0 && first != params.len() + 1 {
+            return Err(ParseError::FormatArgumentOutOfBounds(3, first_span));
+        }
+
+        Ok(Format {
+            archetype,
+            string,
+            first,
+        })
+    }
+
+    /// Parses a parameter position, which is an integer constant
+    /// expression; a negative one is out of bounds of any parameter list.
+    fn parse_format_position(&mut self) -> Result<(usize, Span), ParseError> {
+        let exp = self.parse_conditional()?;
+        let position = constant::evaluate(&exp)?;
+
+        Ok((usize::try_from(position).unwrap_or(usize::MAX), exp.span))
+    }
+}
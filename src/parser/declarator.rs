@@ -0,0 +1,309 @@
+use crate::ast::{Param, constant};
+use crate::error::ParseError;
+use crate::parser::Parser;
+use crate::span::Span;
+use crate::token::{Keyword, Token, TokenKind};
+use crate::types::{Qualifiers, Type, TypeKind};
+
+/// A declarator as written, before it is applied to the base type from the
+/// declaration specifiers. `int (*f)(int)` parses as
+/// `Function(Pointer(Name(f)), [int])`: the outermost node binds first.
+pub enum Declarator {
+    Name(Option<String>, Span),
+    Pointer(Box<Declarator>, Qualifiers),
+    Array(Box<Declarator>, Option<i64>, Span),
+    Function(Box<Declarator>, ParameterList, Span),
+}
+
+pub struct ParameterList {
+    pub params: Vec<Param>,
+    pub variadic: bool,
+}
+
+/// The result of applying a declarator to its base type.
+pub struct Declared {
+    pub name: Option<String>,
+    pub span: Span,
+    pub ty: Type,
+    /// Parameters of the function declarator directly applied to the name.
+    pub params: Option<Vec<Param>>,
+}
+
+impl Declarator {
+    pub fn apply(self, base: Type) -> Result<Declared, ParseError> {
+        match self {
+            Self::Name(name, span) => Ok(Declared {
+                name,
+                span,
+                ty: base,
+                params: None,
+            }),
+            Self::Pointer(inner, qualifiers) => inner.apply(Type {
+                kind: TypeKind::Pointer(Box::new(base)),
+                qualifiers,
+            }),
+            Self::Array(inner, size, span) => {
+                if base.is_function() || base.kind == TypeKind::Void {
+                    return Err(ParseError::InvalidArrayElement(span));
+                }
+
+                inner.apply(Type::new(TypeKind::Array {
+                    element: Box::new(base),
+                    size,
+                }))
+            }
+            Self::Function(inner, ParameterList { params, variadic }, span) => {
+                if base.is_function() || base.is_array() {
+                    return Err(ParseError::InvalidReturnType(span));
+                }
+
+                let ty = Type::new(TypeKind::Function {
+                    params: params.iter().map(|p| p.ty.clone()).collect(),
+                    variadic,
+                    ret: Box::new(base),
+                });
+                let is_direct = matches!(*inner, Self::Name(..));
+                let mut declared = inner.apply(ty)?;
+                if is_direct {
+                    declared.params = Some(params);
+                }
+
+                Ok(declared)
+            }
+        }
+    }
+}
+
+impl<'a> Parser<'a> {
+    /// Whether the next token starts declaration specifiers.
+    pub(super) fn at_specifier(&mut self) -> Result<bool, ParseError> {
+        Ok(matches!(
+            self.peek()?.map(|t| &t.kind),
+            Some(TokenKind::Keyword(
+                Keyword::Int
+                    | Keyword::Char
+                    | Keyword::Void
+                    | Keyword::BuiltinVaList
+                    | Keyword::Const
+                    | Keyword::Volatile
+                    | Keyword::Restrict
+            ))
+        ))
+    }
+
+    /// Parses type specifiers and qualifiers, which may appear in any order.
+    pub(super) fn parse_specifiers(&mut self) -> Result<Type, ParseError> {
+        let mut kind = None;
+        let mut qualifiers = Qualifiers::default();
+        let mut restrict = None;
+
+        loop {
+            if let Some(token) = self.parse_qualifier(&mut qualifiers)? {
+                if token.kind == TokenKind::Keyword(Keyword::Restrict) {
+                    restrict.get_or_insert(token.span);
+                }
+                continue;
+            }
+
+            let specifier = match self.peek()?.map(|t| &t.kind) {
+                Some(TokenKind::Keyword(Keyword::Int)) => TypeKind::Int,
+                Some(TokenKind::Keyword(Keyword::Char)) => TypeKind::Char,
+                Some(TokenKind::Keyword(Keyword::Void)) => TypeKind::Void,
+                Some(TokenKind::Keyword(Keyword::BuiltinVaList)) => TypeKind::Array {
+                    element: Box::new(Type::new(TypeKind::VaListTag)),
+                    size: Some(1),
+                },
+                _ => break,
+            };
+            let token = self.next()?;
+            if kind.is_some() {
+                return Err(ParseError::InvalidTypeSpecifier(token));
+            }
+            kind = Some(specifier);
+        }
+
+        let Some(kind) = kind else {
+            return Err(ParseError::UnexpectedToken(
+                self.next()?,
+                TokenKind::Keyword(Keyword::Int),
+            ));
+        };
+
+        // Without typedefs the specifiers never name a pointer type.
+        if let Some(span) = restrict {
+            return Err(ParseError::InvalidRestrict(span));
+        }
+
+        Ok(Type { kind, qualifiers })
+    }
+
+    /// Parses a type name: specifiers and an abstract declarator, as in
+    /// `__builtin_va_arg(ap, int *)`.
+    pub(super) fn parse_type_name(&mut self) -> Result<Type, ParseError> {
+        let base = self.parse_specifiers()?;
+        let declared = self.parse_declarator()?.apply(base)?;
+        if declared.name.is_some() {
+            return Err(ParseError::InvalidTypeName(declared.span));
+        }
+
+        Ok(declared.ty)
+    }
+
+    /// Consumes a type qualifier, if one is next. Repeated qualifiers are
+    /// allowed and behave as if they appeared once.
+    fn parse_qualifier(
+        &mut self,
+        qualifiers: &mut Qualifiers,
+    ) -> Result<Option<Token>, ParseError> {
+        let flag = match self.peek()?.map(|t| &t.kind) {
+            Some(TokenKind::Keyword(Keyword::Const)) => &mut qualifiers.is_const,
+            Some(TokenKind::Keyword(Keyword::Volatile)) => &mut qualifiers.is_volatile,
+            Some(TokenKind::Keyword(Keyword::Restrict)) => &mut qualifiers.is_restrict,
+            _ => return Ok(None),
+        };
+        *flag = true;
+
+        self.next().map(Some)
+    }
+
+    /// Parses a possibly abstract declarator:
+    ///
+    /// ```text
+    /// declarator        = "*" qualifier* declarator | direct-declarator
+    /// direct-declarator = [ identifier | "(" declarator ")" ] suffix*
+    /// suffix            = "[" [ constant ] "]" | "(" parameter-list ")"
+    /// ```
+    pub(super) fn parse_declarator(&mut self) -> Result<Declarator, ParseError> {
+        if self.next_if(&TokenKind::Asterisk)?.is_some() {
+            let mut qualifiers = Qualifiers::default();
+            while self.parse_qualifier(&mut qualifiers)?.is_some() {}
+
+            let inner = self.parse_declarator()?;
+            return Ok(Declarator::Pointer(Box::new(inner), qualifiers));
+        }
+
+        self.parse_direct_declarator()
+    }
+
+    fn parse_direct_declarator(&mut self) -> Result<Declarator, ParseError> {
+        let start = self.peek_start()?;
+        let mut decl = match self.peek()?.map(|t| &t.kind) {
+            Some(TokenKind::Identifier(_)) => {
+                let token = self.next()?;
+                let TokenKind::Identifier(name) = token.kind else {
+                    unreachable!()
+                };
+                Declarator::Name(Some(name), token.span)
+            }
+            Some(TokenKind::OpenParenthesis) => {
+                self.next()?;
+                if self.at_parameter_list()? {
+                    // `int (int)`: the parenthesis starts the parameters of an
+                    // abstract function declarator.
+                    let params = self.parse_parameter_list()?;
+                    Declarator::Function(
+                        Box::new(Declarator::Name(None, Span::single(start))),
+                        params,
+                        Span::new(start, self.prev_end),
+                    )
+                } else {
+                    let inner = self.parse_declarator()?;
+                    self.expect(TokenKind::CloseParenthesis)?;
+                    inner
+                }
+            }
+            _ => Declarator::Name(None, Span::single(start)),
+        };
+
+        loop {
+            let start = self.peek_start()?;
+            if self.next_if(&TokenKind::OpenBracket)?.is_some() {
+                let size = self.parse_array_size()?;
+                self.expect(TokenKind::CloseBracket)?;
+                decl = Declarator::Array(Box::new(decl), size, Span::new(start, self.prev_end));
+            } else if self.next_if(&TokenKind::OpenParenthesis)?.is_some() {
+                let params = self.parse_parameter_list()?;
+                decl =
+                    Declarator::Function(Box::new(decl), params, Span::new(start, self.prev_end));
+            } else {
+                return Ok(decl);
+            }
+        }
+    }
+
+    fn at_parameter_list(&mut self) -> Result<bool, ParseError> {
+        Ok(self.at_specifier()?
+            || matches!(
+                self.peek()?.map(|t| &t.kind),
+                Some(TokenKind::CloseParenthesis | TokenKind::Ellipsis)
+            ))
+    }
+
+    fn parse_array_size(&mut self) -> Result<Option<i64>, ParseError> {
+        if let Some(TokenKind::CloseBracket) = self.peek()?.map(|t| &t.kind) {
+            return Ok(None);
+        }
+        let size = self.parse_conditional()?;
+        match constant::evaluate(&size)? {
+            n if n > 0 => Ok(Some(n)),
+            _ => Err(ParseError::InvalidArraySize(size.span)),
+        }
+    }
+
+    /// Parses the parameters following an already consumed `(`, through the
+    /// closing parenthesis. Both `()` and `(void)` declare no parameters:
+    /// there are no old-style declarations, so `()` is a prototype too, as
+    /// in C23, rather than leaving the parameters unspecified as in C17
+    /// (6.7.6.3p14), and a call to such a function with arguments is an
+    /// error.
+    fn parse_parameter_list(&mut self) -> Result<ParameterList, ParseError> {
+        let mut params = Vec::new();
+        let mut variadic = false;
+        if self.next_if(&TokenKind::CloseParenthesis)?.is_some() {
+            return Ok(ParameterList { params, variadic });
+        }
+
+        loop {
+            let start = self.peek_start()?;
+            if let Some(token) = self.next_if(&TokenKind::Ellipsis)? {
+                if params.is_empty() {
+                    return Err(ParseError::VariadicWithoutNamedParameter(token.span));
+                }
+                variadic = true;
+                break;
+            }
+
+            let base = self.parse_specifiers()?;
+            let declared = self.parse_declarator()?.apply(base)?;
+            let span = Span::new(start, self.prev_end);
+
+            if declared.ty.kind == TypeKind::Void {
+                let only = params.is_empty()
+                    && declared.name.is_none()
+                    && matches!(
+                        self.peek()?.map(|t| &t.kind),
+                        Some(TokenKind::CloseParenthesis)
+                    );
+                if !only {
+                    return Err(ParseError::InvalidVoidParameter(span));
+                }
+                if !declared.ty.qualifiers.is_empty() {
+                    return Err(ParseError::QualifiedVoidParameter(span));
+                }
+            } else {
+                params.push(Param {
+                    name: declared.name,
+                    ty: declared.ty.adjust_parameter(),
+                    span,
+                });
+            }
+
+            if self.next_if(&TokenKind::Comma)?.is_none() {
+                break;
+            }
+        }
+        self.expect(TokenKind::CloseParenthesis)?;
+
+        Ok(ParameterList { params, variadic })
+    }
+}
@@ -1,16 +1,58 @@
-use crate::ast::{Exp, Function, Program, Statement};
-use crate::error::ParseError;
-use crate::lexer::Lexer;
+mod attribute;
+mod declarator;
+
+use crate::ast::{
+    BinaryOp, Block, BlockItem, Declaration, Exp, ExpKind, Function, Program, Statement,
+    StatementKind, UnaryOp, constant,
+};
+use crate::error::{LexerError, ParseError};
 use crate::span::Span;
-use crate::token::{Const, Keyword, Token, TokenKind};
+use crate::token::{CharPrefix, Const, Keyword, Token, TokenKind};
+use crate::types::{Type, TypeKind};
 
 pub struct Parser<'a> {
-    lexer: Lexer<'a>,
+    tokens: Box<dyn Iterator<Item = Result<Token, LexerError>> + 'a>,
+    peeked: Option<Token>,
+    prev_end: usize,
+    /// Whether the function body being parsed belongs to a variadic function.
+    in_variadic: bool,
+}
+
+/// The binary operator a token spells, with its precedence, higher binding
+/// tighter.
+fn binary_op(kind: &TokenKind) -> Option<(BinaryOp, u8)> {
+    Some(match kind {
+        TokenKind::Asterisk => (BinaryOp::Multiply, 10),
+        TokenKind::Slash => (BinaryOp::Divide, 10),
+        TokenKind::Percent => (BinaryOp::Remainder, 10),
+        TokenKind::Plus => (BinaryOp::Add, 9),
+        TokenKind::Negation => (BinaryOp::Subtract, 9),
+        TokenKind::ShiftLeft => (BinaryOp::ShiftLeft, 8),
+        TokenKind::ShiftRight => (BinaryOp::ShiftRight, 8),
+        TokenKind::Less => (BinaryOp::Less, 7),
+        TokenKind::Greater => (BinaryOp::Greater, 7),
+        TokenKind::LessEqual => (BinaryOp::LessEqual, 7),
+        TokenKind::GreaterEqual => (BinaryOp::GreaterEqual, 7),
+        TokenKind::Equal => (BinaryOp::Equal, 6),
+        TokenKind::NotEqual => (BinaryOp::NotEqual, 6),
+        TokenKind::Ampersand => (BinaryOp::BitAnd, 5),
+        TokenKind::Caret => (BinaryOp::BitXor, 4),
+        TokenKind::Pipe => (BinaryOp::BitOr, 3),
+        TokenKind::LogicalAnd => (BinaryOp::And, 2),
+        TokenKind::LogicalOr => (BinaryOp::Or, 1),
+        _ => return None,
+    })
 }
 
 impl<'a> Parser<'a> {
-    pub fn new(lexer: Lexer<'a>) -> Self {
-        Self { lexer }
+    /// Creates a parser reading tokens from a `Lexer` or the preprocessor.
+    pub fn new(tokens: impl Iterator<Item = Result<Token, LexerError>> + 'a) -> Self {
+        Self {
+            tokens: Box::new(tokens),
+            peeked: None,
+            prev_end: 0,
+            in_variadic: false,
+        }
     }
 
     pub fn parse(&mut self) -> Result<Program, ParseError> {
@@ -24,83 +66,484 @@ impl<'a> Parser<'a> {
         }
     }
 
-    fn expect_eof(&mut self) -> Result<(), ParseError> {
-        match self.lexer.next().transpose()? {
-            None => Ok(()),
-            Some(t) => Err(ParseError::UnexpectedTrailing(t)),
+    fn next(&mut self) -> Result<Token, ParseError> {
+        let token = match self.peeked.take() {
+            Some(t) => t,
+            None => match self.tokens.next() {
+                None => return Err(ParseError::UnexpectedEof(Span::single(self.prev_end))),
+                Some(Ok(t)) => t,
+                Some(Err(e)) => return Err(ParseError::Lexer(e)),
+            },
+        };
+        self.prev_end = token.span.end;
+
+        Ok(token)
+    }
+
+    fn peek(&mut self) -> Result<Option<&Token>, ParseError> {
+        if self.peeked.is_none() {
+            self.peeked = self.tokens.next().transpose()?;
         }
+
+        Ok(self.peeked.as_ref())
     }
 
-    fn next(&mut self) -> Result<Token, ParseError> {
-        match self.lexer.next() {
-            None => Err(ParseError::UnexpectedEof(Span::single(self.lexer.pos()))),
-            Some(Ok(t)) => Ok(t),
-            Some(Err(e)) => Err(ParseError::Lexer(e)),
+    /// Start offset of the next token, or the end of the input consumed so far.
+    fn peek_start(&mut self) -> Result<usize, ParseError> {
+        let prev_end = self.prev_end;
+        Ok(self.peek()?.map_or(prev_end, |t| t.span.start))
+    }
+
+    fn next_if(&mut self, expected: &TokenKind) -> Result<Option<Token>, ParseError> {
+        match self.peek()? {
+            Some(token) if token.kind == *expected => self.next().map(Some),
+            _ => Ok(None),
+        }
+    }
+
+    /// The span from `start` to the end of the input consumed so far.
+    fn span_from(&self, start: usize) -> Span {
+        Span::new(start, self.prev_end)
+    }
+
+    /// An expression node spanning from `start` to the last token consumed.
+    fn exp(&self, kind: ExpKind, start: usize) -> Exp {
+        Exp {
+            kind,
+            span: self.span_from(start),
+            ty: None,
         }
     }
 }
 
 impl<'a> Parser<'a> {
     fn parse_program(&mut self) -> Result<Program, ParseError> {
-        let function_definition = self.parse_function()?;
-
-        self.expect_eof()?;
+        let start = self.peek_start()?;
+        let mut functions = Vec::new();
+        while self.peek()?.is_some() {
+            if self.at_static_assert()? {
+                self.parse_static_assert()?;
+            } else {
+                functions.push(self.parse_function()?);
+            }
+        }
 
-        Ok(Program::FunctionDefinition(function_definition))
+        Ok(Program::Program(functions, self.span_from(start)))
     }
 
+    /// Parses a file-scope declaration, which must declare or define a function.
     fn parse_function(&mut self) -> Result<Function, ParseError> {
-        self.expect(TokenKind::Keyword(Keyword::Int))?;
+        let start = self.peek_start()?;
+        let base = self.parse_specifiers()?;
+        let declared = self.parse_declarator()?.apply(base)?;
 
-        let token = self.next()?;
-        let identifier = match token.kind {
-            TokenKind::Identifier(i) => i,
-            _ => {
-                return Err(ParseError::UnexpectedToken(
-                    token,
-                    TokenKind::Identifier(String::from("")),
-                ))
+        let Some(name) = declared.name else {
+            return Err(ParseError::MissingIdentifier(declared.span));
+        };
+        let (TypeKind::Function { ret, variadic, .. }, Some(params)) =
+            (declared.ty.kind, declared.params)
+        else {
+            return Err(ParseError::UnsupportedFileScopeVariable(
+                self.span_from(start),
+            ));
+        };
+
+        let format = self.parse_attributes(&params, variadic)?;
+        let body = if self.next_if(&TokenKind::Semicolon)?.is_some() {
+            None
+        } else {
+            if params.iter().any(|p| p.name.is_none()) {
+                return Err(ParseError::MissingIdentifier(declared.span));
             }
+            self.in_variadic = variadic;
+            Some(self.parse_block()?)
         };
 
+        Ok(Function::Function {
+            return_type: *ret,
+            name,
+            params,
+            variadic,
+            format,
+            body,
+            span: self.span_from(start),
+        })
+    }
+
+    fn parse_block(&mut self) -> Result<Block, ParseError> {
+        let start = self.peek_start()?;
+        self.expect(TokenKind::OpenBrace)?;
+
+        let mut items = Vec::new();
+        while self.next_if(&TokenKind::CloseBrace)?.is_none() {
+            if self.at_static_assert()? {
+                self.parse_static_assert()?;
+            } else if self.at_specifier()? {
+                let declarations = self.parse_declaration()?;
+                items.extend(declarations.into_iter().map(BlockItem::Declaration));
+            } else {
+                items.push(BlockItem::Statement(self.parse_statement()?));
+            }
+        }
+
+        Ok(Block(items, self.span_from(start)))
+    }
+
+    fn at_static_assert(&mut self) -> Result<bool, ParseError> {
+        Ok(matches!(
+            self.peek()?.map(|t| &t.kind),
+            Some(TokenKind::Keyword(Keyword::StaticAssert))
+        ))
+    }
+
+    /// Parses `_Static_assert(constant-expression, string-literal);` and
+    /// checks the assertion, which leaves nothing in the AST.
+    fn parse_static_assert(&mut self) -> Result<(), ParseError> {
+        self.expect(TokenKind::Keyword(Keyword::StaticAssert))?;
         self.expect(TokenKind::OpenParenthesis)?;
-        self.expect(TokenKind::Keyword(Keyword::Void))?;
+        let condition = self.parse_conditional()?;
+        self.expect(TokenKind::Comma)?;
+        let token = self.next()?;
+        let TokenKind::StringLiteral(bytes) = token.kind else {
+            return Err(ParseError::UnexpectedToken(
+                token,
+                TokenKind::StringLiteral(Vec::new()),
+            ));
+        };
+        let message = self.concatenate_strings(bytes)?;
         self.expect(TokenKind::CloseParenthesis)?;
+        self.expect(TokenKind::Semicolon)?;
 
-        self.expect(TokenKind::OpenBrace)?;
-        let statement = self.parse_statement()?;
-        self.expect(TokenKind::CloseBrace)?;
+        match constant::evaluate(&condition)? {
+            0 => Err(ParseError::StaticAssertionFailed(message, condition.span)),
+            _ => Ok(()),
+        }
+    }
 
-        Ok(Function::Function {
-            name: identifier,
-            body: statement,
-        })
+    /// Parses a block-scope declaration with one or more declarators.
+    fn parse_declaration(&mut self) -> Result<Vec<Declaration>, ParseError> {
+        let base = self.parse_specifiers()?;
+
+        let mut declarations = Vec::new();
+        loop {
+            let start = self.peek_start()?;
+            let declared = self.parse_declarator()?.apply(base.clone())?;
+            let Some(name) = declared.name else {
+                return Err(ParseError::MissingIdentifier(declared.span));
+            };
+
+            let declaration = match (declared.ty.kind, declared.params) {
+                (TypeKind::Function { ret, variadic, .. }, Some(params)) => {
+                    let format = self.parse_attributes(&params, variadic)?;
+                    Declaration::Function(Function::Function {
+                        return_type: *ret,
+                        name,
+                        params,
+                        variadic,
+                        format,
+                        body: None,
+                        span: self.span_from(start),
+                    })
+                }
+                (TypeKind::Void, _) => {
+                    return Err(ParseError::VoidVariable(self.span_from(start)));
+                }
+                (kind, _) => {
+                    let span = self.span_from(start);
+                    let mut ty = Type {
+                        kind,
+                        qualifiers: declared.ty.qualifiers,
+                    };
+                    let init = match self.next_if(&TokenKind::Assign)? {
+                        Some(_) => {
+                            let init = self.parse_exp()?;
+                            let init_span = init.span;
+                            if let TypeKind::Array { element, size } = &mut ty.kind {
+                                let ExpKind::String(bytes) = &init.kind else {
+                                    return Err(ParseError::InvalidArrayInitializer(init_span));
+                                };
+                                if element.kind != TypeKind::Char {
+                                    return Err(ParseError::InvalidArrayInitializer(init_span));
+                                }
+                                // A terminator that does not fit is dropped,
+                                // as in `char s[2] = "ab"`.
+                                let len = bytes.len() as i64;
+                                match size {
+                                    Some(size) if *size < len => {
+                                        return Err(ParseError::InitializerStringTooLong(
+                                            init_span,
+                                        ));
+                                    }
+                                    Some(_) => {}
+                                    None => *size = Some(len + 1),
+                                }
+                            }
+                            Some(init)
+                        }
+                        None => None,
+                    };
+                    if let TypeKind::Array { size: None, .. } = ty.kind {
+                        return Err(ParseError::ArraySizeMissing(span));
+                    }
+                    Declaration::Variable {
+                        name,
+                        ty,
+                        init,
+                        span: self.span_from(start),
+                    }
+                }
+            };
+            declarations.push(declaration);
+
+            if self.next_if(&TokenKind::Comma)?.is_none() {
+                break;
+            }
+        }
+        self.expect(TokenKind::Semicolon)?;
+
+        Ok(declarations)
     }
 
     fn parse_statement(&mut self) -> Result<Statement, ParseError> {
-        self.expect(TokenKind::Keyword(Keyword::Return))?;
-        let exp = self.parse_exp()?;
-        self.expect(TokenKind::Semicolon)?;
+        let start = self.peek_start()?;
+        let kind = match self.peek()?.map(|t| &t.kind) {
+            Some(TokenKind::Keyword(Keyword::Return)) => {
+                self.next()?;
+                let exp = self.parse_exp()?;
+                self.expect(TokenKind::Semicolon)?;
+
+                StatementKind::Return(exp)
+            }
+            Some(TokenKind::Semicolon) => {
+                self.next()?;
+
+                StatementKind::Null
+            }
+            _ => {
+                let exp = self.parse_exp()?;
+                self.expect(TokenKind::Semicolon)?;
 
-        Ok(Statement::Return(exp))
+                StatementKind::Expression(exp)
+            }
+        };
+
+        Ok(Statement {
+            kind,
+            span: self.span_from(start),
+        })
     }
 
     fn parse_exp(&mut self) -> Result<Exp, ParseError> {
+        let start = self.peek_start()?;
+        let exp = self.parse_conditional()?;
+
+        // Assignment is right associative; the checker verifies the lvalue.
+        if self.next_if(&TokenKind::Assign)?.is_some() {
+            let rhs = self.parse_exp()?;
+            let kind = ExpKind::Assignment(Box::new(exp), Box::new(rhs));
+            return Ok(self.exp(kind, start));
+        }
+
+        Ok(exp)
+    }
+
+    /// Parses a conditional expression, the operand of everything but
+    /// assignment, and the grammar of constant expressions.
+    fn parse_conditional(&mut self) -> Result<Exp, ParseError> {
+        let start = self.peek_start()?;
+        let condition = self.parse_binary(1)?;
+        if self.next_if(&TokenKind::Question)?.is_none() {
+            return Ok(condition);
+        }
+
+        let then = self.parse_exp()?;
+        self.expect(TokenKind::Colon)?;
+        let otherwise = self.parse_conditional()?;
+        let kind = ExpKind::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise));
+
+        Ok(self.exp(kind, start))
+    }
+
+    /// Parses binary operators of at least `min` precedence, which are all
+    /// left associative.
+    fn parse_binary(&mut self, min: u8) -> Result<Exp, ParseError> {
+        let start = self.peek_start()?;
+        let mut lhs = self.parse_unary()?;
+        while let Some((op, precedence)) = self.peek()?.and_then(|t| binary_op(&t.kind))
+            && precedence >= min
+        {
+            self.next()?;
+            let rhs = self.parse_binary(precedence + 1)?;
+            lhs = self.exp(ExpKind::Binary(op, Box::new(lhs), Box::new(rhs)), start);
+        }
+
+        Ok(lhs)
+    }
+
+    fn parse_unary(&mut self) -> Result<Exp, ParseError> {
+        let start = self.peek_start()?;
+        if self.next_if(&TokenKind::Ampersand)?.is_some() {
+            let kind = ExpKind::AddressOf(Box::new(self.parse_unary()?));
+            return Ok(self.exp(kind, start));
+        }
+        if self.next_if(&TokenKind::Asterisk)?.is_some() {
+            let kind = ExpKind::Dereference(Box::new(self.parse_unary()?));
+            return Ok(self.exp(kind, start));
+        }
+        let op = match self.peek()?.map(|t| &t.kind) {
+            Some(TokenKind::Negation) => UnaryOp::Negate,
+            Some(TokenKind::Plus) => UnaryOp::Plus,
+            Some(TokenKind::Complement) => UnaryOp::Complement,
+            Some(TokenKind::Exclamation) => UnaryOp::Not,
+            _ => return self.parse_postfix(),
+        };
+        self.next()?;
+        let kind = ExpKind::Unary(op, Box::new(self.parse_unary()?));
+
+        Ok(self.exp(kind, start))
+    }
+
+    fn parse_postfix(&mut self) -> Result<Exp, ParseError> {
+        let start = self.peek_start()?;
+        let mut exp = self.parse_primary()?;
+
+        loop {
+            if self.next_if(&TokenKind::OpenParenthesis)?.is_some() {
+                let args = self.parse_arguments()?;
+                exp = self.exp(ExpKind::FunctionCall(Box::new(exp), args), start);
+            } else if self.next_if(&TokenKind::OpenBracket)?.is_some() {
+                let index = self.parse_exp()?;
+                self.expect(TokenKind::CloseBracket)?;
+                exp = self.exp(ExpKind::Subscript(Box::new(exp), Box::new(index)), start);
+            } else {
+                return Ok(exp);
+            }
+        }
+    }
+
+    fn parse_arguments(&mut self) -> Result<Vec<Exp>, ParseError> {
+        let mut args = Vec::new();
+        if self.next_if(&TokenKind::CloseParenthesis)?.is_some() {
+            return Ok(args);
+        }
+
+        loop {
+            args.push(self.parse_exp()?);
+            if self.next_if(&TokenKind::Comma)?.is_none() {
+                break;
+            }
+        }
+        self.expect(TokenKind::CloseParenthesis)?;
+
+        Ok(args)
+    }
+
+    fn parse_primary(&mut self) -> Result<Exp, ParseError> {
         let token = self.next()?;
+        let start = token.span.start;
 
-        match token.kind {
-            TokenKind::Constant(Const::Int(num)) => Ok(Exp::ConstantInt(num)),
-            _ => Err(ParseError::UnexpectedToken(
-                token,
-                TokenKind::Constant(Const::Int(0)),
-            )),
+        let kind = match token.kind {
+            TokenKind::Constant(Const::Int(num)) => ExpKind::ConstantInt(num),
+            TokenKind::Constant(Const::Char(
+                value,
+                prefix @ (CharPrefix::None | CharPrefix::Wide),
+            )) => ExpKind::ConstantChar(value, prefix),
+            // Their types, `char16_t`, `char32_t` and `unsigned char`, are
+            // unsigned, which no type is yet.
+            TokenKind::Constant(Const::Char(..)) => {
+                return Err(ParseError::UnsupportedCharPrefix(token.span));
+            }
+            TokenKind::StringLiteral(bytes) => ExpKind::String(self.concatenate_strings(bytes)?),
+            TokenKind::Identifier(name) if name.starts_with("__builtin_va_") => {
+                return self.parse_va_builtin(name, token.span);
+            }
+            TokenKind::Identifier(name) => ExpKind::Var(name),
+            // The parentheses are part of the expression's span.
+            TokenKind::OpenParenthesis => {
+                let exp = self.parse_exp()?;
+                self.expect(TokenKind::CloseParenthesis)?;
+                exp.kind
+            }
+            _ => {
+                return Err(ParseError::UnexpectedToken(
+                    token,
+                    TokenKind::Constant(Const::Int(0)),
+                ));
+            }
+        };
+
+        Ok(self.exp(kind, start))
+    }
+
+    /// Concatenates any string literals that follow one already consumed,
+    /// whose contents are `bytes`, into one array.
+    fn concatenate_strings(&mut self, mut bytes: Vec<u8>) -> Result<Vec<u8>, ParseError> {
+        while let Some(TokenKind::StringLiteral(_)) = self.peek()?.map(|t| &t.kind) {
+            if let TokenKind::StringLiteral(next) = self.next()?.kind {
+                bytes.extend(next);
+            }
         }
+        Ok(bytes)
+    }
+
+    /// Parses the arguments of a `__builtin_va_*` builtin whose name has
+    /// already been consumed.
+    fn parse_va_builtin(&mut self, name: String, span: Span) -> Result<Exp, ParseError> {
+        if !matches!(
+            name.as_str(),
+            "__builtin_va_start" | "__builtin_va_arg" | "__builtin_va_end" | "__builtin_va_copy"
+        ) {
+            return Ok(self.exp(ExpKind::Var(name), span.start));
+        }
+
+        self.expect(TokenKind::OpenParenthesis)?;
+        let ap = Box::new(self.parse_exp()?);
+        let kind = match name.as_str() {
+            "__builtin_va_start" => {
+                if !self.in_variadic {
+                    return Err(ParseError::VaStartOutsideVariadic(span));
+                }
+                self.expect(TokenKind::Comma)?;
+                self.parse_exp()?;
+                ExpKind::VaStart(ap)
+            }
+            "__builtin_va_arg" => {
+                self.expect(TokenKind::Comma)?;
+                let start = self.peek_start()?;
+                let ty = self.parse_type_name()?;
+                if !matches!(ty.kind, TypeKind::Int | TypeKind::Pointer(_)) {
+                    return Err(ParseError::UnsupportedVaArgType(self.span_from(start)));
+                }
+                ExpKind::VaArg(ap, ty)
+            }
+            "__builtin_va_end" => ExpKind::VaEnd(ap),
+            _ => {
+                self.expect(TokenKind::Comma)?;
+                ExpKind::VaCopy(ap, Box::new(self.parse_exp()?))
+            }
+        };
+        self.expect(TokenKind::CloseParenthesis)?;
+
+        Ok(self.exp(kind, span.start))
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::lexer::Lexer;
+
+    /// Checks that `src` fails to parse with `message`, at the code spelled
+    /// `spelled`.
+    fn assert_error(src: &str, message: &str, spelled: &str) {
+        use crate::span::HasSpan;
+
+        let mut parser = Parser::new(Lexer::new(src));
+        let err = parser.parse_program().err().expect("the source is invalid");
+        let span = err.span();
+        assert_eq!(err.to_string(), message);
+        assert_eq!(&src[span.start..span.end], spelled);
+    }
 
     #[test]
     fn expect_semicolon() {
@@ -124,10 +567,23 @@ mod tests {
             let mut parser = Parser::new(lexer);
             assert!(matches!(
                 parser.parse_program(),
-                Ok(Program::FunctionDefinition(Function::Function {
-                    name: identifier,
-                    body: Statement::Return(Exp::ConstantInt(2))
-                })) if identifier == "main"
+                Ok(Program::Program(functions, _)) if matches!(
+                    functions.as_slice(),
+                    [Function::Function {
+                        name: identifier,
+                        body: Some(Block(items, _)),
+                        ..
+                    }] if identifier == "main" && matches!(
+                        items.as_slice(),
+                        [BlockItem::Statement(Statement {
+                        kind: StatementKind::Return(Exp {
+                            kind: ExpKind::ConstantInt(2),
+                            ..
+                        }),
+                        ..
+                    })]
+                    )
+                )
             ));
         }
 
@@ -144,11 +600,42 @@ mod tests {
                 if kind == TokenKind::CloseBrace
             ));
         }
+
+        #[test]
+        fn declarations_and_definitions() {
+            let file = "int add(int a, int b);
+            int apply(int (*op)(int, int), int x) { return op(x, x); }";
+            let lexer = Lexer::new(file);
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_program(),
+                Ok(Program::Program(functions, _)) if matches!(
+                    functions.as_slice(),
+                    [
+                        Function::Function { body: None, .. },
+                        Function::Function { body: Some(_), .. },
+                    ]
+                )
+            ));
+        }
+
+        #[test]
+        fn file_scope_variable() {
+            let lexer = Lexer::new("int x;");
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_program(),
+                Err(ParseError::UnsupportedFileScopeVariable(Span {
+                    start: 0,
+                    end: 5
+                }))
+            ));
+        }
     }
 
     mod function {
         use super::*;
-        use crate::ast::Exp;
+        use crate::ast::{Exp, Param};
         use crate::lexer::Lexer;
 
         #[test]
@@ -162,8 +649,241 @@ mod tests {
                 parser.parse_function(),
                 Ok(Function::Function {
                     name: identifier,
-                    body: Statement::Return(Exp::ConstantInt(2))
-                }) if identifier == "main"
+                    body: Some(Block(items, _)),
+                    ..
+                }) if identifier == "main" && matches!(
+                    items.as_slice(),
+                    [BlockItem::Statement(Statement {
+                        kind: StatementKind::Return(Exp {
+                            kind: ExpKind::ConstantInt(2),
+                            ..
+                        }),
+                        ..
+                    })]
+                )
+            ));
+        }
+
+        #[test]
+        fn returns_function_pointer() -> Result<(), ParseError> {
+            let lexer = Lexer::new("int (*pick(int which))(int) { return 0; }");
+            let mut parser = Parser::new(lexer);
+            let Function::Function {
+                return_type,
+                name,
+                params,
+                ..
+            } = parser.parse_function()?;
+            assert_eq!(name, "pick");
+            assert_eq!(return_type.to_string(), "int (*)(int)");
+            assert!(matches!(params.as_slice(), [Param { name: Some(n), .. }] if n == "which"));
+            Ok(())
+        }
+
+        #[test]
+        fn unnamed_parameter_in_definition() {
+            let lexer = Lexer::new("int f(int) { return 0; }");
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_function(),
+                Err(ParseError::MissingIdentifier(Span { start: 4, end: 5 }))
+            ));
+        }
+    }
+
+    mod declarator {
+        use super::*;
+        use crate::types::Qualifiers;
+
+        fn parse_type(src: &str) -> Result<Type, ParseError> {
+            let lexer = Lexer::new(src);
+            let mut parser = Parser::new(lexer);
+            let base = parser.parse_specifiers()?;
+            Ok(parser.parse_declarator()?.apply(base)?.ty)
+        }
+
+        #[test]
+        fn qualifiers_in_any_order() -> Result<(), ParseError> {
+            let expected = Type {
+                kind: TypeKind::Int,
+                qualifiers: Qualifiers {
+                    is_const: true,
+                    is_volatile: true,
+                    is_restrict: false,
+                },
+            };
+            assert_eq!(parse_type("const volatile int")?, expected);
+            assert_eq!(parse_type("int volatile const")?, expected);
+            assert_eq!(parse_type("const int const volatile")?, expected);
+            Ok(())
+        }
+
+        #[test]
+        fn qualified_pointers() -> Result<(), ParseError> {
+            let ty = parse_type("const int *volatile *restrict")?;
+            assert_eq!(ty.to_string(), "const int *volatile *restrict");
+            assert!(ty.qualifiers.is_restrict);
+            assert!(matches!(
+                &ty.kind,
+                TypeKind::Pointer(inner)
+                if inner.qualifiers.is_volatile && !inner.qualifiers.is_const
+            ));
+            Ok(())
+        }
+
+        #[test]
+        fn restrict_non_pointer() {
+            assert!(matches!(
+                parse_type("int restrict *"),
+                Err(ParseError::InvalidRestrict(Span { start: 4, end: 12 }))
+            ));
+        }
+
+        #[test]
+        fn duplicate_specifier() {
+            assert!(matches!(
+                parse_type("int const void"),
+                Err(ParseError::InvalidTypeSpecifier(Token {
+                    kind: TokenKind::Keyword(Keyword::Void),
+                    ..
+                }))
+            ));
+        }
+
+        #[test]
+        fn missing_specifier() {
+            assert!(matches!(
+                parse_type("const main"),
+                Err(ParseError::UnexpectedToken(
+                    Token {
+                        kind: TokenKind::Identifier(_),
+                        ..
+                    },
+                    TokenKind::Keyword(Keyword::Int)
+                ))
+            ));
+        }
+
+        #[test]
+        fn qualified_void_parameter() {
+            let lexer = Lexer::new("int main(const void) { return 0; }");
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_function(),
+                Err(ParseError::QualifiedVoidParameter(Span {
+                    start: 9,
+                    end: 19
+                }))
+            ));
+        }
+
+        #[test]
+        fn void_among_parameters() {
+            assert!(matches!(
+                parse_type("int (int, void)"),
+                Err(ParseError::InvalidVoidParameter(Span {
+                    start: 10,
+                    end: 14
+                }))
+            ));
+        }
+
+        #[test]
+        fn function_pointers() -> Result<(), ParseError> {
+            assert_eq!(
+                parse_type("int (*fp)(int, int *)")?.to_string(),
+                "int (*)(int, int *)"
+            );
+            assert_eq!(parse_type("int (*)(void)")?.to_string(), "int (*)(void)");
+            assert_eq!(
+                parse_type("int (*table[4])(int)")?.to_string(),
+                "int (*[4])(int)"
+            );
+            assert_eq!(
+                parse_type("void (*signal(int, void (*)(int)))(int)")?.to_string(),
+                "void (*(int, void (*)(int)))(int)"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn abstract_function_parameter() -> Result<(), ParseError> {
+            assert_eq!(
+                parse_type("int (int (int))")?.to_string(),
+                "int (int (*)(int))"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn empty_parameter_list_is_a_prototype() -> Result<(), ParseError> {
+            // Not an old-style declaration, which C17 would make it, but
+            // the same as `(void)`, as in C23.
+            assert_eq!(parse_type("int ()")?, parse_type("int (void)")?);
+            assert_eq!(parse_type("int (*)()")?.to_string(), "int (*)(void)");
+            Ok(())
+        }
+
+        #[test]
+        fn parameters_adjusted_to_pointers() -> Result<(), ParseError> {
+            let ty = parse_type("int (int a[3], int f(void), int (*rows)[2])")?;
+            assert_eq!(ty.to_string(), "int (int *, int (*)(void), int (*)[2])");
+            Ok(())
+        }
+
+        #[test]
+        fn function_returning_function() {
+            assert!(matches!(
+                parse_type("int f(void)(int)"),
+                Err(ParseError::InvalidReturnType(Span { start: 5, end: 11 }))
+            ));
+        }
+
+        #[test]
+        fn function_returning_array() {
+            assert!(matches!(
+                parse_type("int f(void)[2]"),
+                Err(ParseError::InvalidReturnType(Span { start: 5, end: 11 }))
+            ));
+        }
+
+        #[test]
+        fn array_of_functions() {
+            assert!(matches!(
+                parse_type("int a[2](void)"),
+                Err(ParseError::InvalidArrayElement(Span { start: 5, end: 8 }))
+            ));
+        }
+
+        #[test]
+        fn variadic_prototypes() -> Result<(), ParseError> {
+            assert_eq!(
+                parse_type("int (*)(int, ...)")?.to_string(),
+                "int (*)(int, ...)"
+            );
+            assert_eq!(
+                parse_type("int (int, __builtin_va_list)")?.to_string(),
+                "int (int, __va_list_tag *)"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn variadic_without_named_parameter() {
+            assert!(matches!(
+                parse_type("int f(...)"),
+                Err(ParseError::VariadicWithoutNamedParameter(Span {
+                    start: 6,
+                    end: 9
+                }))
+            ));
+        }
+
+        #[test]
+        fn zero_array_size() {
+            assert!(matches!(
+                parse_type("int a[0]"),
+                Err(ParseError::InvalidArraySize(Span { start: 6, end: 7 }))
             ));
         }
     }
@@ -171,16 +891,151 @@ mod tests {
     mod statement {
         use super::*;
         use crate::lexer::Lexer;
-        use crate::span::Span;
+        use crate::span::{HasSpan, Span};
         use crate::token::{Const, Token, TokenKind};
 
+        #[test]
+        fn parse_block() -> Result<(), ParseError> {
+            let lexer = Lexer::new("{ int x = 1, *p; ; p = &x; int f(int); return *p; }");
+            let mut parser = Parser::new(lexer);
+            let Block(items, _) = parser.parse_block()?;
+            let kinds = items
+                .iter()
+                .map(|item| match item {
+                    BlockItem::Declaration(Declaration::Variable { init: Some(_), .. }) => {
+                        "initialized"
+                    }
+                    BlockItem::Declaration(Declaration::Variable { .. }) => "variable",
+                    BlockItem::Declaration(Declaration::Function(_)) => "function",
+                    BlockItem::Statement(Statement { kind, .. }) => match kind {
+                        StatementKind::Null => "null",
+                        StatementKind::Expression(Exp {
+                            kind: ExpKind::Assignment(..),
+                            ..
+                        }) => "assignment",
+                        StatementKind::Return(Exp {
+                            kind: ExpKind::Dereference(_),
+                            ..
+                        }) => "return",
+                        _ => "other",
+                    },
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(
+                kinds,
+                [
+                    "initialized",
+                    "variable",
+                    "null",
+                    "assignment",
+                    "function",
+                    "return"
+                ]
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn spans_cover_whole_constructs() -> Result<(), ParseError> {
+            let src = "int main(void) { int x = 1, *p; return x; }";
+            let mut parser = Parser::new(Lexer::new(src));
+            let program = parser.parse_program()?;
+            let spelled = |span: &Span| &src[span.start..span.end];
+            assert_eq!(spelled(program.span()), src);
+
+            let Program::Program(functions, _) = &program;
+            let Function::Function {
+                body: Some(body), ..
+            } = &functions[0]
+            else {
+                panic!("expected a definition");
+            };
+            assert_eq!(spelled(functions[0].span()), src);
+            assert_eq!(spelled(body.span()), "{ int x = 1, *p; return x; }");
+            let items = body.0.iter().map(|item| spelled(item.span()));
+            assert_eq!(items.collect::<Vec<_>>(), ["x = 1", "*p", "return x;"]);
+            Ok(())
+        }
+
+        #[test]
+        fn void_variable() {
+            let lexer = Lexer::new("{ void v; }");
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_block(),
+                Err(ParseError::VoidVariable(Span { start: 7, end: 8 }))
+            ));
+        }
+
+        #[test]
+        fn char_array_from_string() -> Result<(), ParseError> {
+            let lexer = Lexer::new(r#"{ char s[] = "ab" "c"; char t[2] = "ab"; }"#);
+            let mut parser = Parser::new(lexer);
+            let Block(items, _) = parser.parse_block()?;
+            let sizes = items
+                .iter()
+                .map(|item| match item {
+                    BlockItem::Declaration(Declaration::Variable {
+                        ty:
+                            Type {
+                                kind: TypeKind::Array { size, .. },
+                                ..
+                            },
+                        init:
+                            Some(Exp {
+                                kind: ExpKind::String(_),
+                                ..
+                            }),
+                        ..
+                    }) => *size,
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, [Some(4), Some(2)]);
+            Ok(())
+        }
+
+        #[test]
+        fn invalid_array_declarations() {
+            let cases = [
+                (
+                    "{ int a[]; }",
+                    ParseError::ArraySizeMissing(Span::new(6, 9)),
+                ),
+                (
+                    r#"{ int a[] = "x"; }"#,
+                    ParseError::InvalidArrayInitializer(Span::new(12, 15)),
+                ),
+                (
+                    "{ char a[2] = 0; }",
+                    ParseError::InvalidArrayInitializer(Span::new(14, 15)),
+                ),
+                (
+                    r#"{ char a[2] = "abc"; }"#,
+                    ParseError::InitializerStringTooLong(Span::new(14, 19)),
+                ),
+            ];
+            for (src, expected) in cases {
+                let mut parser = Parser::new(Lexer::new(src));
+                let err = parser.parse_block().err().expect(src);
+                assert_eq!(err.to_string(), expected.to_string(), "{src}");
+                assert_eq!(err.span(), expected.span(), "{src}");
+            }
+        }
+
         #[test]
         fn parse_return() {
             let lexer = Lexer::new("return 2;");
             let mut parser = Parser::new(lexer);
             assert!(matches!(
                 parser.parse_statement(),
-                Ok(Statement::Return(Exp::ConstantInt(2)))
+                Ok(Statement {
+                    kind: StatementKind::Return(Exp {
+                        kind: ExpKind::ConstantInt(2),
+                        ..
+                    }),
+                    ..
+                })
             ));
         }
 
@@ -214,12 +1069,549 @@ mod tests {
 
     mod exp {
         use super::*;
+        use crate::span::HasSpan;
 
         #[test]
         fn parse_const_int() {
             let lexer = Lexer::new("5");
             let mut parser = Parser::new(lexer);
-            assert!(matches!(parser.parse_exp(), Ok(Exp::ConstantInt(5))));
+            assert!(matches!(
+                parser.parse_exp(),
+                Ok(Exp {
+                    kind: ExpKind::ConstantInt(5),
+                    ..
+                })
+            ));
+        }
+
+        #[test]
+        fn char_prefixes() -> Result<(), ParseError> {
+            let mut parser = Parser::new(Lexer::new(r"L'\xffffffff'"));
+            assert!(matches!(
+                parser.parse_exp()?.kind,
+                ExpKind::ConstantChar(-1, CharPrefix::Wide)
+            ));
+            for src in [r"U'\xffffffff'", "u'a'", "u8'a'"] {
+                let mut parser = Parser::new(Lexer::new(src));
+                assert!(
+                    matches!(
+                        parser.parse_exp(),
+                        Err(ParseError::UnsupportedCharPrefix(span)) if span == Span::new(0, src.len())
+                    ),
+                    "{src}"
+                );
+            }
+            Ok(())
+        }
+
+        #[test]
+        fn parse_calls() -> Result<(), ParseError> {
+            let lexer = Lexer::new("(*table[1])(f(), 2)");
+            let mut parser = Parser::new(lexer);
+            assert_eq!(
+                parser.parse_exp()?.to_string(),
+                "FunctionCall(
+    Dereference(
+        Subscript(
+            Var(table)
+            ConstantInt(1)
+        )
+    )
+    FunctionCall(
+        Var(f)
+    )
+    ConstantInt(2)
+)
+"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn va_builtins() -> Result<(), ParseError> {
+            let file = "int f(int n, ...) {
+                __builtin_va_list ap, aq;
+                __builtin_va_start(ap, n);
+                __builtin_va_copy(aq, ap);
+                __builtin_va_arg(aq, int *);
+                __builtin_va_end(ap);
+            }";
+            let lexer = Lexer::new(file);
+            let mut parser = Parser::new(lexer);
+            let Function::Function {
+                variadic,
+                body: Some(Block(items, _)),
+                ..
+            } = parser.parse_function()?
+            else {
+                panic!("expected a definition");
+            };
+            assert!(variadic);
+            let kinds = items
+                .iter()
+                .map(|item| match item {
+                    BlockItem::Statement(Statement {
+                        kind: StatementKind::Expression(exp),
+                        ..
+                    }) => match &exp.kind {
+                        ExpKind::VaStart(_) => String::from("start"),
+                        ExpKind::VaCopy(..) => String::from("copy"),
+                        ExpKind::VaArg(_, ty) => ty.to_string(),
+                        ExpKind::VaEnd(_) => String::from("end"),
+                        _ => String::new(),
+                    },
+                    _ => String::new(),
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(kinds, ["", "", "start", "copy", "int *", "end"]);
+            Ok(())
+        }
+
+        #[test]
+        fn va_start_outside_variadic() {
+            let file = "int f(int n) { __builtin_va_list ap; __builtin_va_start(ap, n); }";
+            let lexer = Lexer::new(file);
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_function(),
+                Err(ParseError::VaStartOutsideVariadic(Span {
+                    start: 37,
+                    end: 55
+                }))
+            ));
+        }
+
+        #[test]
+        fn va_arg_named_type() {
+            let lexer = Lexer::new("__builtin_va_arg(ap, int x)");
+            let mut parser = Parser::new(lexer);
+            assert!(matches!(
+                parser.parse_exp(),
+                Err(ParseError::InvalidTypeName(Span { start: 25, end: 26 }))
+            ));
+        }
+
+        #[test]
+        fn assignment_is_right_associative() -> Result<(), ParseError> {
+            let lexer = Lexer::new("a = b = &c");
+            let mut parser = Parser::new(lexer);
+            assert_eq!(
+                parser.parse_exp()?.to_string(),
+                "Assignment(
+    Var(a)
+    Assignment(
+        Var(b)
+        AddressOf(
+            Var(c)
+        )
+    )
+)
+"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn spans_cover_whole_expressions() -> Result<(), ParseError> {
+            let src = "*(p)[i] = f(a, b)";
+            let lexer = Lexer::new(src);
+            let mut parser = Parser::new(lexer);
+            let exp = parser.parse_exp()?;
+            let ExpKind::Assignment(lhs, rhs) = &exp.kind else {
+                panic!("expected an assignment");
+            };
+            let ExpKind::Dereference(subscript) = &lhs.kind else {
+                panic!("expected a dereference");
+            };
+            let ExpKind::Subscript(base, _) = &subscript.kind else {
+                panic!("expected a subscript");
+            };
+            let spelled = |exp: &Exp| &src[exp.span().start..exp.span().end];
+            assert_eq!(spelled(&exp), src);
+            assert_eq!(spelled(lhs), "*(p)[i]");
+            assert_eq!(spelled(subscript), "(p)[i]");
+            assert_eq!(spelled(base), "(p)");
+            assert_eq!(spelled(rhs), "f(a, b)");
+            Ok(())
+        }
+
+        #[test]
+        fn operator_precedence() -> Result<(), ParseError> {
+            let lexer = Lexer::new("a || !b && c == -d * 2 + 1 ? x : y ? 1 : 0");
+            let mut parser = Parser::new(lexer);
+            assert_eq!(
+                parser.parse_exp()?.to_string(),
+                "Conditional(
+    Or(
+        Var(a)
+        And(
+            Not(
+                Var(b)
+            )
+            Equal(
+                Var(c)
+                Add(
+                    Multiply(
+                        Negate(
+                            Var(d)
+                        )
+                        ConstantInt(2)
+                    )
+                    ConstantInt(1)
+                )
+            )
+        )
+    )
+    Var(x)
+    Conditional(
+        Var(y)
+        ConstantInt(1)
+        ConstantInt(0)
+    )
+)
+"
+            );
+            Ok(())
+        }
+
+        #[test]
+        fn binary_operators_are_left_associative() -> Result<(), ParseError> {
+            let lexer = Lexer::new("a - b - c << 1");
+            let mut parser = Parser::new(lexer);
+            assert_eq!(
+                parser.parse_exp()?.to_string(),
+                "ShiftLeft(
+    Subtract(
+        Subtract(
+            Var(a)
+            Var(b)
+        )
+        Var(c)
+    )
+    ConstantInt(1)
+)
+"
+            );
+            Ok(())
+        }
+    }
+
+    mod constant {
+        use super::*;
+
+        #[test]
+        fn constant_array_sizes() -> Result<(), ParseError> {
+            let src = "{ char a[2 * 3 + 1], b['a' - 96 << 2], c[0 && 1 / 0 ? 1 : -~2]; }";
+            let mut parser = Parser::new(Lexer::new(src));
+            let Block(items, _) = parser.parse_block()?;
+            let sizes = items
+                .iter()
+                .map(|item| match item {
+                    BlockItem::Declaration(Declaration::Variable {
+                        ty:
+                            Type {
+                                kind: TypeKind::Array { size, .. },
+                                ..
+                            },
+                        ..
+                    }) => *size,
+                    _ => None,
+                })
+                .collect::<Vec<_>>();
+            assert_eq!(sizes, [Some(7), Some(4), Some(3)]);
+            Ok(())
+        }
+
+        #[test]
+        fn static_assertions() -> Result<(), ParseError> {
+            let src = "_Static_assert(1, \"file\");
+                int main(void) { _Static_assert(-1 >> 1 == -1, \"block\"); return 0; }";
+            Parser::new(Lexer::new(src)).parse_program()?;
+            Ok(())
+        }
+
+        #[test]
+        fn failed_static_assertion() {
+            assert_error(
+                "_Static_assert(1 + 1 == 3, \"one\" \" plus one\");",
+                "static assertion failed: one plus one",
+                "1 + 1 == 3",
+            );
+        }
+
+        #[test]
+        fn call_in_array_size() {
+            assert_error(
+                "int a[2 + f()];",
+                "expression is not an integer constant expression",
+                "f()",
+            );
+        }
+
+        #[test]
+        fn unevaluated_operand_must_be_constant() {
+            assert_error(
+                "_Static_assert(1 ? 2 : x, \"\");",
+                "expression is not an integer constant expression",
+                "x",
+            );
+        }
+
+        #[test]
+        fn non_positive_array_size() {
+            assert_error(
+                "int a[1 - 1];",
+                "array size must be a positive integer",
+                "1 - 1",
+            );
+        }
+
+        #[test]
+        fn addition_overflow() {
+            assert_error(
+                "int a[2147483647 + 1];",
+                "overflow in expression; result is -2147483648 with type 'int'",
+                "2147483647 + 1",
+            );
+        }
+
+        #[test]
+        fn negation_overflow() {
+            assert_error(
+                "int a[-(-2147483647 - 1)];",
+                "overflow in expression; result is -2147483648 with type 'int'",
+                "-(-2147483647 - 1)",
+            );
+        }
+
+        #[test]
+        fn shift_overflow() {
+            assert_error(
+                "int a[1 << 31];",
+                "overflow in expression; result is -2147483648 with type 'int'",
+                "1 << 31",
+            );
+        }
+
+        #[test]
+        fn too_large_constant_in_static_assertion() {
+            assert_error(
+                "_Static_assert(4294967296 == 0, \"trunc\");",
+                "integer constant 4294967296 is too large for type 'int'",
+                "4294967296",
+            );
+        }
+
+        #[test]
+        fn too_large_array_size() {
+            assert_error(
+                "int a[4294967297];",
+                "integer constant 4294967297 is too large for type 'int'",
+                "4294967297",
+            );
+        }
+
+        #[test]
+        fn division_by_zero() {
+            assert_error(
+                "int a[1 + 4 / (1 - 1)];",
+                "division by zero is undefined",
+                "4 / (1 - 1)",
+            );
+        }
+
+        #[test]
+        fn remainder_by_zero_where_evaluated() {
+            assert_error(
+                "int a[1 || 2 % 0 ? 5 % 0 : 1];",
+                "remainder by zero is undefined",
+                "5 % 0",
+            );
+        }
+
+        #[test]
+        fn negative_shift_count() {
+            assert_error("int a[1 >> -1];", "shift count is negative", "1 >> -1");
+        }
+
+        #[test]
+        fn shift_count_too_large() {
+            assert_error("int a[1 << 32];", "shift count >= width of type", "1 << 32");
+        }
+    }
+
+    mod visit {
+        use super::*;
+        use crate::ast::fold::{self, Fold};
+        use crate::ast::visit::{self, Visit};
+        use crate::ast::visit_mut::{self, VisitMut};
+        use crate::span::HasSpan;
+
+        const SRC: &str = "int f(int a, int *p) { int b = a; *p = f(b, &a); return 1; }";
+
+        fn parse(src: &str) -> Result<Program, ParseError> {
+            Parser::new(Lexer::new(src)).parse_program()
+        }
+
+        /// Lists the variables used, in source order.
+        #[derive(Default)]
+        struct Uses(Vec<String>);
+
+        impl Visit for Uses {
+            fn visit_exp(&mut self, exp: &Exp) {
+                if let ExpKind::Var(name) = &exp.kind {
+                    self.0.push(name.clone());
+                }
+                visit::walk_exp(self, exp);
+            }
+        }
+
+        struct Rename;
+
+        impl VisitMut for Rename {
+            fn visit_exp_mut(&mut self, exp: &mut Exp) {
+                if let ExpKind::Var(name) = &mut exp.kind {
+                    name.insert(0, '_');
+                }
+                visit_mut::walk_exp_mut(self, exp);
+            }
+        }
+
+        /// Replaces `&x` with `x`, keeping the span of the whole.
+        struct StripAddressOf;
+
+        impl Fold for StripAddressOf {
+            fn fold_exp(&mut self, exp: Exp) -> Exp {
+                match exp.kind {
+                    ExpKind::AddressOf(inner) => Exp {
+                        kind: self.fold_exp(*inner).kind,
+                        span: exp.span,
+                        ty: None,
+                    },
+                    kind => fold::walk_exp(self, Exp { kind, ..exp }),
+                }
+            }
+        }
+
+        #[test]
+        fn visit_in_source_order() -> Result<(), ParseError> {
+            let mut uses = Uses::default();
+            uses.visit_program(&parse(SRC)?);
+            assert_eq!(uses.0, ["a", "p", "f", "b", "a"]);
+            Ok(())
+        }
+
+        #[test]
+        fn visit_mut_and_fold() -> Result<(), ParseError> {
+            let mut program = parse(SRC)?;
+            Rename.visit_program_mut(&mut program);
+            let program = StripAddressOf.fold_program(program);
+
+            let mut uses = Uses::default();
+            uses.visit_program(&program);
+            assert_eq!(uses.0, ["_a", "_p", "_f", "_b", "_a"]);
+            assert!(!program.to_string().contains("AddressOf"));
+            assert_eq!(program.span(), &Span::new(0, SRC.len()));
+            Ok(())
+        }
+    }
+
+    mod attribute {
+        use super::*;
+        use crate::ast::{Archetype, Format};
+
+        #[test]
+        fn format_attributes() -> Result<(), ParseError> {
+            let src = "int f(const char *s, ...) __attribute__((format(printf, 1, 2)));
+                int g(int n, char *s, __builtin_va_list ap)
+                    __attribute__(()) __attribute__((__format__(__scanf__, 1 + 1, 0)))
+                { int h(char *s, ...) __attribute__((format(gnu_printf, 1, 2))); return n; }";
+            let Program::Program(functions, _) = Parser::new(Lexer::new(src)).parse()?;
+            let format = |function: &Function| match function {
+                Function::Function { format, .. } => *format,
+            };
+            let printf = |first| {
+                Some(Format {
+                    archetype: Archetype::Printf,
+                    string: 1,
+                    first,
+                })
+            };
+            assert_eq!(format(&functions[0]), printf(2));
+            assert_eq!(
+                format(&functions[1]),
+                Some(Format {
+                    archetype: Archetype::Scanf,
+                    string: 2,
+                    first: 0,
+                })
+            );
+            let Function::Function {
+                body: Some(Block(items, _)),
+                ..
+            } = &functions[1]
+            else {
+                panic!("g has a body");
+            };
+            let BlockItem::Declaration(Declaration::Function(h)) = &items[0] else {
+                panic!("h is declared in g");
+            };
+            assert_eq!(format(h), printf(2));
+            Ok(())
+        }
+
+        #[test]
+        fn unknown_attribute() {
+            assert_error(
+                "int f(char *s, ...) __attribute__((noreturn));",
+                "unknown attribute 'noreturn'",
+                "noreturn",
+            );
+        }
+
+        #[test]
+        fn unsupported_archetype() {
+            assert_error(
+                "int f(char *s, ...) __attribute__((format(strftime, 1, 0)));",
+                "'strftime' is an unsupported format archetype",
+                "strftime",
+            );
+        }
+
+        #[test]
+        fn format_string_index_out_of_bounds() {
+            assert_error(
+                "int f(char *s, ...) __attribute__((format(printf, 0, 2)));",
+                "'format' attribute parameter 2 is out of bounds",
+                "0",
+            );
+        }
+
+        #[test]
+        fn first_argument_index_out_of_bounds() {
+            assert_error(
+                "int f(char *s, ...) __attribute__((format(printf, 1, 3)));",
+                "'format' attribute parameter 3 is out of bounds",
+                "3",
+            );
+        }
+
+        #[test]
+        fn format_string_not_a_string() {
+            assert_error(
+                "int f(int n, ...) __attribute__((format(printf, 1, 2)));",
+                "format argument not a string type",
+                "1",
+            );
+        }
+
+        #[test]
+        fn format_without_variadic_arguments() {
+            assert_error(
+                "int f(char *s) __attribute__((format(printf, 1, 2)));",
+                "format attribute requires variadic function",
+                "2",
+            );
         }
     }
 }
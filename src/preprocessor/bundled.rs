@@ -0,0 +1,26 @@
+use std::path::Path;
+
+/// The directory the bundled headers appear to be in. It is searched after
+/// the `-I` and `-isystem` directories and before the system ones, as
+/// GCC's own headers are.
+pub const BUNDLED_DIR: &str = "<rcc>/include";
+
+/// The freestanding standard headers, built into the compiler so that it
+/// needs no headers of its own on disk. Those that need keywords or types
+/// the compiler does not have yet, `<stdbool.h>`, `<stdalign.h>`,
+/// `<stdnoreturn.h>` and `<float.h>`, are left out.
+const HEADERS: &[(&str, &str)] = &[
+    ("limits.h", include_str!("../../include/limits.h")),
+    ("stdarg.h", include_str!("../../include/stdarg.h")),
+    ("stddef.h", include_str!("../../include/stddef.h")),
+    ("stdint.h", include_str!("../../include/stdint.h")),
+];
+
+/// The contents of the bundled header at `path`, if it names one.
+pub fn source(path: &Path) -> Option<&'static str> {
+    let name = path.strip_prefix(BUNDLED_DIR).ok()?;
+    HEADERS
+        .iter()
+        .find(|(header, _)| name == Path::new(header))
+        .map(|(_, src)| *src)
+}
@@ -0,0 +1,365 @@
+use crate::error::{PreprocessError, PreprocessWarning, PreprocessWarningKind};
+use crate::preprocessor::expr::evaluate;
+use crate::preprocessor::predefined::DYNAMIC;
+use crate::preprocessor::{Lexed, PpToken, Preprocessor, identifier};
+use crate::span::Span;
+use crate::token::{Const, Token, TokenKind};
+
+/// The attributes `__has_attribute` reports, which are those the parser
+/// understands.
+const ATTRIBUTES: &[&str] = &["format"];
+
+/// An `#if`, `#ifdef` or `#ifndef` whose `#endif` has not been read yet.
+pub(super) struct Conditional {
+    /// The directive that opened it, and where.
+    pub(super) directive: String,
+    pub(super) span: Span,
+    /// Whether one of its groups was taken, so that the rest are skipped.
+    taken: bool,
+    seen_else: bool,
+}
+
+impl<'a> Preprocessor<'a> {
+    /// Handles the conditional directive `name`, given the rest of its line.
+    pub(super) fn conditional(
+        &mut self,
+        name: &str,
+        line: Vec<Lexed>,
+        directive: Span,
+    ) -> Result<(), PreprocessError> {
+        let taken = match name {
+            "if" | "ifdef" | "ifndef" => {
+                let taken = self.condition(name, line, directive)?;
+                self.conditionals().push(Conditional {
+                    directive: name.to_string(),
+                    span: directive,
+                    taken,
+                    seen_else: false,
+                });
+                taken
+            }
+            "elif" | "elifdef" | "elifndef" | "else" => {
+                let Some(open) = self.conditionals().last() else {
+                    return Err(PreprocessError::UnmatchedConditional(
+                        name.to_string(),
+                        directive,
+                    ));
+                };
+                if open.seen_else {
+                    return Err(PreprocessError::ConditionalAfterElse(
+                        name.to_string(),
+                        directive,
+                    ));
+                }
+
+                // Once a group was taken, later conditions are not even
+                // evaluated.
+                let taken = match (open.taken, name) {
+                    (true, _) => false,
+                    (false, "else") => {
+                        self.extra_tokens(name, &line);
+                        true
+                    }
+                    (false, _) => self.condition(name, line, directive)?,
+                };
+                let open = self
+                    .conditionals()
+                    .last_mut()
+                    .expect("the conditional was just found");
+                open.taken |= taken;
+                open.seen_else = name == "else";
+                taken
+            }
+            _ => {
+                if self.conditionals().pop().is_none() {
+                    return Err(PreprocessError::UnmatchedConditional(
+                        name.to_string(),
+                        directive,
+                    ));
+                }
+                self.extra_tokens(name, &line);
+                true
+            }
+        };
+
+        if !taken {
+            self.skip_group();
+        }
+        Ok(())
+    }
+
+    fn conditionals(&mut self) -> &mut Vec<Conditional> {
+        &mut self
+            .frames
+            .last_mut()
+            .expect("directives are read from a file")
+            .conditionals
+    }
+
+    fn extra_tokens(&mut self, name: &str, line: &[Lexed]) {
+        if let Some(extra) = line.first() {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::ExtraTokens(name.to_string()),
+                span: extra.span(),
+            });
+        }
+    }
+
+    /// Decides whether the group after a conditional directive is taken.
+    fn condition(
+        &mut self,
+        name: &str,
+        line: Vec<Lexed>,
+        directive: Span,
+    ) -> Result<bool, PreprocessError> {
+        let tokens = Self::line_tokens(line)?;
+        let negated = match name {
+            "ifdef" | "elifdef" => false,
+            "ifndef" | "elifndef" => true,
+            _ => {
+                if tokens.is_empty() {
+                    return Err(PreprocessError::MissingExpression(
+                        name.to_string(),
+                        directive,
+                    ));
+                }
+                let tokens = self.expand_condition(tokens)?;
+                return Ok(evaluate(&tokens, directive)?.is_true());
+            }
+        };
+
+        let mut tokens = tokens.into_iter();
+        let Some(name_token) = tokens.next() else {
+            return Err(PreprocessError::InvalidMacroName(directive));
+        };
+        let Some(macro_name) = identifier(&name_token.token) else {
+            return Err(PreprocessError::InvalidMacroName(name_token.token.span));
+        };
+        if let Some(extra) = tokens.next() {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::ExtraTokens(name.to_string()),
+                span: extra.token.span,
+            });
+        }
+
+        Ok(self.is_defined(&macro_name) != negated)
+    }
+
+    /// Whether `defined` holds for a name. The operators only valid in
+    /// conditions count as macros, so that headers can test for them.
+    fn is_defined(&self, name: &str) -> bool {
+        self.macros.contains_key(name)
+            || DYNAMIC.contains(&name)
+            || matches!(
+                name,
+                "__has_include" | "__has_include_next" | "__has_attribute"
+            )
+    }
+
+    /// Macro-expands the expression of `#if` or `#elif`, replacing the
+    /// `defined`, `__has_include`, `__has_include_next` and
+    /// `__has_attribute` operators with their values. Their operands are not
+    /// expanded, even where a macro expansion produced the operator.
+    fn expand_condition(&mut self, tokens: Vec<PpToken>) -> Result<Vec<PpToken>, PreprocessError> {
+        self.with_tokens(tokens, |pp| {
+            let mut expanded = Vec::new();
+            while let Some(token) = pp.next_expanded(false)? {
+                let value = match identifier(&token.token).as_deref() {
+                    Some("defined") => pp.defined_operand(&token)?,
+                    Some("__has_include") => pp.has_include_operand(&token, false)?,
+                    Some("__has_include_next") => pp.has_include_operand(&token, true)?,
+                    Some("__has_attribute") => pp.has_attribute_operand(&token)?,
+                    _ => {
+                        expanded.push(token);
+                        continue;
+                    }
+                };
+                expanded.push(value);
+            }
+
+            Ok(expanded)
+        })
+    }
+
+    /// The next token of the innermost input, without macro expansion.
+    fn next_raw(&mut self) -> Result<Option<PpToken>, PreprocessError> {
+        match self.frames.last_mut().and_then(|frame| frame.tokens.pop()) {
+            Some(Lexed::Token(token)) => Ok(Some(token)),
+            Some(Lexed::Error(err, _)) => Err(err.into()),
+            None => Ok(None),
+        }
+    }
+
+    /// Reads a token of the kind `kind` as part of the operand of
+    /// `operator`.
+    fn expect_raw(
+        &mut self,
+        operator: &PpToken,
+        kind: TokenKind,
+    ) -> Result<PpToken, PreprocessError> {
+        match self.next_raw()? {
+            Some(token) if token.token.kind == kind => Ok(token),
+            next => Err(PreprocessError::OperatorOperand(
+                operator.spelling.clone(),
+                next.map_or(operator.token.span, |next| next.token.span),
+            )),
+        }
+    }
+
+    /// Reads the identifier operand of `operator`.
+    fn identifier_operand(&mut self, operator: &PpToken) -> Result<PpToken, PreprocessError> {
+        match self.next_raw()? {
+            Some(token) if identifier(&token.token).is_some() => Ok(token),
+            next => Err(PreprocessError::OperatorOperand(
+                operator.spelling.clone(),
+                next.map_or(operator.token.span, |next| next.token.span),
+            )),
+        }
+    }
+
+    /// Reads the operand of `defined`, either `NAME` or `(NAME)`.
+    fn defined_operand(&mut self, operator: &PpToken) -> Result<PpToken, PreprocessError> {
+        let parenthesized = self.frames.last().and_then(|frame| frame.tokens.last()).is_some_and(
+            |next| matches!(next, Lexed::Token(next) if next.token.kind == TokenKind::OpenParenthesis),
+        );
+        if parenthesized {
+            self.next_raw()?;
+        }
+        let name = self.identifier_operand(operator)?;
+        let mut end = name.token.span;
+        if parenthesized {
+            end = self
+                .expect_raw(operator, TokenKind::CloseParenthesis)?
+                .token
+                .span;
+        }
+
+        let name = identifier(&name.token).expect("the operand is an identifier");
+        Ok(value_token(operator, self.is_defined(&name), end))
+    }
+
+    /// Reads the `("name")` or `(<name>)` operand of `__has_include`, or of
+    /// `__has_include_next` if `next`.
+    fn has_include_operand(
+        &mut self,
+        operator: &PpToken,
+        next: bool,
+    ) -> Result<PpToken, PreprocessError> {
+        self.expect_raw(operator, TokenKind::OpenParenthesis)?;
+        let (name, system) = match self.next_raw()? {
+            Some(PpToken {
+                token:
+                    Token {
+                        kind: TokenKind::StringLiteral(bytes),
+                        ..
+                    },
+                ..
+            }) => (String::from_utf8_lossy(&bytes).into_owned(), false),
+            // Outside `#include`, `<name>` is lexed as ordinary tokens.
+            Some(less) if less.token.kind == TokenKind::Less => {
+                let mut name = String::new();
+                loop {
+                    match self.next_raw()? {
+                        Some(greater) if greater.token.kind == TokenKind::Greater => break,
+                        Some(token) => {
+                            if token.space_before && !name.is_empty() {
+                                name.push(' ');
+                            }
+                            name.push_str(&token.spelling);
+                        }
+                        None => {
+                            return Err(PreprocessError::OperatorOperand(
+                                operator.spelling.clone(),
+                                less.token.span,
+                            ));
+                        }
+                    }
+                }
+                (name, true)
+            }
+            next => {
+                return Err(PreprocessError::OperatorOperand(
+                    operator.spelling.clone(),
+                    next.map_or(operator.token.span, |next| next.token.span),
+                ));
+            }
+        };
+        let close = self.expect_raw(operator, TokenKind::CloseParenthesis)?;
+
+        let found = self.find_include(&name, system, next).is_some();
+        Ok(value_token(operator, found, close.token.span))
+    }
+
+    /// Reads the `(name)` operand of `__has_attribute`.
+    fn has_attribute_operand(&mut self, operator: &PpToken) -> Result<PpToken, PreprocessError> {
+        self.expect_raw(operator, TokenKind::OpenParenthesis)?;
+        let name = self.identifier_operand(operator)?;
+        let close = self.expect_raw(operator, TokenKind::CloseParenthesis)?;
+
+        // `__format__` names the same attribute as `format`.
+        let name = identifier(&name.token).expect("the operand is an identifier");
+        let name = name
+            .strip_prefix("__")
+            .and_then(|name| name.strip_suffix("__"))
+            .unwrap_or(&name);
+        Ok(value_token(
+            operator,
+            ATTRIBUTES.contains(&name),
+            close.token.span,
+        ))
+    }
+
+    /// Skips the lines of a group whose condition failed, up to the `#elif`,
+    /// `#else` or `#endif` that ends it, which is left to be read next.
+    /// Nested conditionals are skipped whole, and nothing else in the group
+    /// need be valid.
+    fn skip_group(&mut self) {
+        let Some(frame) = self.frames.last_mut() else {
+            return;
+        };
+        let mut depth = 0;
+        while let Some(lexed) = frame.tokens.pop() {
+            let Lexed::Token(hash) = &lexed else {
+                continue;
+            };
+            if !hash.line_start || hash.token.kind != TokenKind::Hash {
+                continue;
+            }
+            let name = match frame.tokens.last() {
+                Some(Lexed::Token(name)) if !name.line_start => identifier(&name.token),
+                _ => None,
+            };
+
+            match name.as_deref() {
+                Some("if" | "ifdef" | "ifndef") => depth += 1,
+                Some("elif" | "elifdef" | "elifndef" | "else" | "endif") if depth == 0 => {
+                    frame.tokens.push(lexed);
+                    return;
+                }
+                Some("endif") => depth -= 1,
+                _ => {}
+            }
+        }
+    }
+}
+
+/// The constant `1` or `0` that an operator gives, spanning from the
+/// operator to `end`.
+fn value_token(operator: &PpToken, value: bool, end: Span) -> PpToken {
+    let value = i64::from(value);
+    let mut token = PpToken::new(
+        Token {
+            kind: TokenKind::Constant(Const::Int(value)),
+            span: Span::new(
+                operator.token.span.start,
+                end.end.max(operator.token.span.end),
+            ),
+        },
+        value.to_string(),
+        false,
+        operator.space_before,
+    );
+    token.hide_set = operator.hide_set.clone();
+
+    token
+}
@@ -0,0 +1,277 @@
+use crate::error::{LexerError, LexerErrorKind, PreprocessError};
+use crate::lexer::integer_value;
+use crate::preprocessor::{PpToken, identifier};
+use crate::span::Span;
+use crate::token::{CharPrefix, Const, TokenKind};
+
+/// A value in a preprocessor expression, where every signed integer type
+/// acts as `intmax_t` and every unsigned one as `uintmax_t` (C17 6.10.1p4).
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Value {
+    Signed(i64),
+    Unsigned(u64),
+}
+
+impl Value {
+    pub fn is_true(self) -> bool {
+        self != Value::Signed(0) && self != Value::Unsigned(0)
+    }
+
+    fn from_bool(b: bool) -> Self {
+        Value::Signed(i64::from(b))
+    }
+
+    /// The usual arithmetic conversions: if either operand is unsigned,
+    /// both are.
+    fn convert(self, other: Value) -> (Value, Value) {
+        match (self, other) {
+            (Value::Signed(a), Value::Unsigned(b)) => {
+                (Value::Unsigned(a as u64), Value::Unsigned(b))
+            }
+            (Value::Unsigned(a), Value::Signed(b)) => {
+                (Value::Unsigned(a), Value::Unsigned(b as u64))
+            }
+            _ => (self, other),
+        }
+    }
+}
+
+/// Evaluates the fully macro-expanded expression of an `#if` or `#elif`,
+/// whose directive is at `directive`.
+pub fn evaluate(tokens: &[PpToken], directive: Span) -> Result<Value, PreprocessError> {
+    let mut evaluator = Evaluator {
+        tokens,
+        pos: 0,
+        end: Span::single(
+            tokens
+                .last()
+                .map_or(directive.end, |last| last.token.span.end),
+        ),
+    };
+    let value = evaluator.conditional(true)?;
+    match evaluator.peek() {
+        Some(extra) => Err(PreprocessError::MissingBinaryOperator(
+            extra.spelling.clone(),
+            extra.token.span,
+        )),
+        None => Ok(value),
+    }
+}
+
+struct Evaluator<'t> {
+    tokens: &'t [PpToken],
+    pos: usize,
+    /// Where the expression ends, for errors about what is missing there.
+    end: Span,
+}
+
+/// The precedence of a binary operator, higher binding tighter.
+fn precedence(kind: &TokenKind) -> Option<u8> {
+    Some(match kind {
+        TokenKind::Asterisk | TokenKind::Slash | TokenKind::Percent => 10,
+        TokenKind::Plus | TokenKind::Negation => 9,
+        TokenKind::ShiftLeft | TokenKind::ShiftRight => 8,
+        TokenKind::Less | TokenKind::Greater | TokenKind::LessEqual | TokenKind::GreaterEqual => 7,
+        TokenKind::Equal | TokenKind::NotEqual => 6,
+        TokenKind::Ampersand => 5,
+        TokenKind::Caret => 4,
+        TokenKind::Pipe => 3,
+        TokenKind::LogicalAnd => 2,
+        TokenKind::LogicalOr => 1,
+        _ => return None,
+    })
+}
+
+impl<'t> Evaluator<'t> {
+    fn peek(&self) -> Option<&'t PpToken> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<&'t PpToken> {
+        let token = self.tokens.get(self.pos);
+        self.pos += 1;
+        token
+    }
+
+    /// Evaluates `a ? b : c` or anything tighter. Errors such as division
+    /// by zero only count where `live`, outside the operands that `&&`,
+    /// `||` and `?:` skip.
+    fn conditional(&mut self, live: bool) -> Result<Value, PreprocessError> {
+        let condition = self.binary(1, live)?;
+        let Some(question) = self
+            .peek()
+            .filter(|token| token.token.kind == TokenKind::Question)
+        else {
+            return Ok(condition);
+        };
+        self.pos += 1;
+
+        let taken = condition.is_true();
+        let then = self.conditional(live && taken)?;
+        match self.next() {
+            Some(colon) if colon.token.kind == TokenKind::Colon => {}
+            _ => return Err(PreprocessError::MissingColon(question.token.span)),
+        }
+        let otherwise = self.conditional(live && !taken)?;
+
+        let (then, otherwise) = then.convert(otherwise);
+        Ok(if taken { then } else { otherwise })
+    }
+
+    /// Evaluates binary operators of at least `min` precedence.
+    fn binary(&mut self, min: u8, live: bool) -> Result<Value, PreprocessError> {
+        let mut lhs = self.unary(live)?;
+        while let Some(op) = self.peek()
+            && let Some(precedence) = precedence(&op.token.kind)
+            && precedence >= min
+        {
+            self.pos += 1;
+            let rhs_live = match op.token.kind {
+                TokenKind::LogicalAnd => live && lhs.is_true(),
+                TokenKind::LogicalOr => live && !lhs.is_true(),
+                _ => live,
+            };
+            let rhs = self.binary(precedence + 1, rhs_live)?;
+            lhs = apply(&op.token.kind, lhs, rhs, live, op.token.span)?;
+        }
+
+        Ok(lhs)
+    }
+
+    fn unary(&mut self, live: bool) -> Result<Value, PreprocessError> {
+        let Some(token) = self.next() else {
+            return Err(PreprocessError::ExpectedValue(self.end));
+        };
+        let span = token.token.span;
+
+        match &token.token.kind {
+            TokenKind::Plus => self.unary(live),
+            TokenKind::Negation => Ok(match self.unary(live)? {
+                Value::Signed(n) => Value::Signed(n.wrapping_neg()),
+                Value::Unsigned(n) => Value::Unsigned(n.wrapping_neg()),
+            }),
+            TokenKind::Complement => Ok(match self.unary(live)? {
+                Value::Signed(n) => Value::Signed(!n),
+                Value::Unsigned(n) => Value::Unsigned(!n),
+            }),
+            TokenKind::Exclamation => Ok(Value::from_bool(!self.unary(live)?.is_true())),
+            TokenKind::OpenParenthesis => {
+                let value = self.conditional(live)?;
+                match self.next() {
+                    Some(close) if close.token.kind == TokenKind::CloseParenthesis => Ok(value),
+                    _ => Err(PreprocessError::MissingCloseParen(span)),
+                }
+            }
+            // Numbers are read from their spelling, as the lexer rejects
+            // those beyond `i64`.
+            TokenKind::Constant(Const::Int(_))
+            | TokenKind::Invalid(
+                LexerErrorKind::InvalidIntegerLiteral | LexerErrorKind::InvalidConstSuffix,
+            ) => {
+                let (value, unsigned) = integer_value(&token.spelling)
+                    .map_err(|kind| PreprocessError::Lexer(LexerError { kind, span }))?;
+                // A constant too large for `intmax_t` can only be unsigned.
+                Ok(match i64::try_from(value) {
+                    Ok(value) if !unsigned => Value::Signed(value),
+                    _ => Value::Unsigned(value),
+                })
+            }
+            TokenKind::Constant(Const::Char(value, CharPrefix::Utf32)) => {
+                Ok(Value::Unsigned(*value as u64))
+            }
+            TokenKind::Constant(Const::Char(value, _)) => Ok(Value::Signed(*value)),
+            TokenKind::Invalid(kind) => Err(PreprocessError::Lexer(LexerError {
+                kind: kind.clone(),
+                span,
+            })),
+            // Identifiers left after expansion are not macros, and are 0.
+            _ => match identifier(&token.token).as_deref() {
+                Some("true") => Ok(Value::Signed(1)),
+                Some(_) => Ok(Value::Signed(0)),
+                None => Err(PreprocessError::ExpectedValue(span)),
+            },
+        }
+    }
+}
+
+fn apply(
+    op: &TokenKind,
+    lhs: Value,
+    rhs: Value,
+    live: bool,
+    span: Span,
+) -> Result<Value, PreprocessError> {
+    match op {
+        TokenKind::LogicalAnd => return Ok(Value::from_bool(lhs.is_true() && rhs.is_true())),
+        TokenKind::LogicalOr => return Ok(Value::from_bool(lhs.is_true() || rhs.is_true())),
+        // The type of a shift is that of its left operand.
+        TokenKind::ShiftLeft | TokenKind::ShiftRight => {
+            let amount = match rhs {
+                Value::Signed(n) => n,
+                Value::Unsigned(n) => i64::try_from(n).unwrap_or(i64::MAX),
+            };
+            let left = (*op == TokenKind::ShiftLeft) == (amount >= 0);
+            return Ok(shift(lhs, amount.unsigned_abs(), left));
+        }
+        _ => {}
+    }
+
+    if matches!(op, TokenKind::Slash | TokenKind::Percent) && !rhs.is_true() {
+        return match live {
+            true => Err(PreprocessError::DivisionByZero(span)),
+            false => Ok(lhs.convert(rhs).0),
+        };
+    }
+
+    Ok(match lhs.convert(rhs) {
+        (Value::Signed(a), Value::Signed(b)) => match op {
+            TokenKind::Asterisk => Value::Signed(a.wrapping_mul(b)),
+            TokenKind::Slash => Value::Signed(a.wrapping_div(b)),
+            TokenKind::Percent => Value::Signed(a.wrapping_rem(b)),
+            TokenKind::Plus => Value::Signed(a.wrapping_add(b)),
+            TokenKind::Negation => Value::Signed(a.wrapping_sub(b)),
+            TokenKind::Ampersand => Value::Signed(a & b),
+            TokenKind::Caret => Value::Signed(a ^ b),
+            TokenKind::Pipe => Value::Signed(a | b),
+            _ => Value::from_bool(compare(op, a, b)),
+        },
+        (Value::Unsigned(a), Value::Unsigned(b)) => match op {
+            TokenKind::Asterisk => Value::Unsigned(a.wrapping_mul(b)),
+            TokenKind::Slash => Value::Unsigned(a / b),
+            TokenKind::Percent => Value::Unsigned(a % b),
+            TokenKind::Plus => Value::Unsigned(a.wrapping_add(b)),
+            TokenKind::Negation => Value::Unsigned(a.wrapping_sub(b)),
+            TokenKind::Ampersand => Value::Unsigned(a & b),
+            TokenKind::Caret => Value::Unsigned(a ^ b),
+            TokenKind::Pipe => Value::Unsigned(a | b),
+            _ => Value::from_bool(compare(op, a, b)),
+        },
+        _ => unreachable!("converted operands have the same signedness"),
+    })
+}
+
+fn compare<T: PartialOrd>(op: &TokenKind, a: T, b: T) -> bool {
+    match op {
+        TokenKind::Less => a < b,
+        TokenKind::Greater => a > b,
+        TokenKind::LessEqual => a <= b,
+        TokenKind::GreaterEqual => a >= b,
+        TokenKind::Equal => a == b,
+        _ => a != b,
+    }
+}
+
+/// Shifts by `amount` bits, as GCC does: a negative amount shifts the other
+/// way, and bits shifted out are lost. A right shift of a negative value
+/// fills with ones.
+fn shift(value: Value, amount: u64, left: bool) -> Value {
+    let amount = u32::try_from(amount).unwrap_or(u32::MAX);
+    match (value, left) {
+        (Value::Signed(n), true) => Value::Signed(n.checked_shl(amount).unwrap_or(0)),
+        (Value::Signed(n), false) => {
+            Value::Signed(n.checked_shr(amount).unwrap_or(if n < 0 { -1 } else { 0 }))
+        }
+        (Value::Unsigned(n), true) => Value::Unsigned(n.checked_shl(amount).unwrap_or(0)),
+        (Value::Unsigned(n), false) => Value::Unsigned(n.checked_shr(amount).unwrap_or(0)),
+    }
+}
@@ -0,0 +1,628 @@
+use crate::error::{PreprocessError, PreprocessWarning, PreprocessWarningKind};
+use crate::lexer::Lexer;
+use crate::preprocessor::predefined::DYNAMIC;
+use crate::preprocessor::{HideSet, Lexed, PpToken, Preprocessor, identifier, pp_token};
+use crate::span::Span;
+use crate::token::{Const, Token, TokenKind};
+use std::rc::Rc;
+
+pub struct Macro {
+    /// Where the name appears in the definition.
+    pub span: Span,
+    /// The parameters of a function-like macro. Those of a variadic one end
+    /// with `__VA_ARGS__`.
+    pub params: Option<Vec<String>>,
+    pub variadic: bool,
+    pub body: Vec<PpToken>,
+}
+
+impl Macro {
+    /// Whether two definitions are the same, which makes redefining a macro
+    /// harmless: their parameters must match, and their bodies token for
+    /// token, including where whitespace separates them.
+    fn same_as(&self, other: &Macro) -> bool {
+        self.params == other.params
+            && self.variadic == other.variadic
+            && self.body.len() == other.body.len()
+            && self
+                .body
+                .iter()
+                .zip(&other.body)
+                .all(|(a, b)| a.spelling == b.spelling && a.space_before == b.space_before)
+    }
+
+    /// The index of the parameter a token names.
+    fn param(&self, token: &PpToken) -> Option<usize> {
+        let name = identifier(&token.token)?;
+        self.params
+            .as_ref()?
+            .iter()
+            .position(|param| *param == name)
+    }
+}
+
+/// An argument of a macro invocation, macro-expanded only if a parameter
+/// needs it so.
+struct Argument {
+    tokens: Vec<PpToken>,
+    expanded: Option<Vec<PpToken>>,
+}
+
+/// An element of a replacement list being substituted. A placemarker
+/// stands for an empty argument until the `##` operators around it have
+/// been applied (C17 6.10.3.3).
+enum Piece {
+    Token(PpToken),
+    Placemarker,
+    Paste(Span),
+}
+
+fn is_va_opt(token: &PpToken) -> bool {
+    identifier(&token.token).as_deref() == Some("__VA_OPT__")
+}
+
+/// The index of the `)` closing the `__VA_OPT__` at `start` in `body`.
+fn va_opt_end(body: &[PpToken], start: usize) -> Result<usize, PreprocessError> {
+    let unterminated = PreprocessError::UnterminatedVaOpt(body[start].token.span);
+    if body
+        .get(start + 1)
+        .is_none_or(|open| open.token.kind != TokenKind::OpenParenthesis)
+    {
+        return Err(unterminated);
+    }
+
+    let mut depth = 0;
+    for (i, token) in body.iter().enumerate().skip(start + 1) {
+        match token.token.kind {
+            TokenKind::OpenParenthesis => depth += 1,
+            TokenKind::CloseParenthesis if depth == 1 => return Ok(i),
+            TokenKind::CloseParenthesis => depth -= 1,
+            _ => {}
+        }
+    }
+
+    Err(unterminated)
+}
+
+/// Parses the parameter list of a function-like macro definition, whose `(`
+/// is at `open`.
+fn parameters(
+    line: &mut impl Iterator<Item = PpToken>,
+    open: Span,
+) -> Result<(Vec<String>, bool), PreprocessError> {
+    let mut params: Vec<String> = Vec::new();
+    let mut last = open;
+    loop {
+        let Some(token) = line.next() else {
+            return Err(PreprocessError::ExpectedParameterSeparator(last));
+        };
+        let span = token.token.span;
+        match &token.token.kind {
+            TokenKind::CloseParenthesis if params.is_empty() => return Ok((params, false)),
+            TokenKind::Ellipsis => {
+                params.push(String::from("__VA_ARGS__"));
+                return match line.next() {
+                    Some(close) if close.token.kind == TokenKind::CloseParenthesis => {
+                        Ok((params, true))
+                    }
+                    Some(other) => Err(PreprocessError::ExpectedParameterSeparator(
+                        other.token.span,
+                    )),
+                    None => Err(PreprocessError::ExpectedParameterSeparator(span)),
+                };
+            }
+            _ => match identifier(&token.token) {
+                Some(name) if name == "__VA_ARGS__" || name == "__VA_OPT__" => {
+                    return Err(PreprocessError::VariadicOnly(name, span));
+                }
+                Some(name) if params.contains(&name) => {
+                    return Err(PreprocessError::DuplicateParameter(name, span));
+                }
+                Some(name) => params.push(name),
+                None => return Err(PreprocessError::ExpectedParameter(span)),
+            },
+        }
+        last = span;
+
+        match line.next() {
+            Some(comma) if comma.token.kind == TokenKind::Comma => last = comma.token.span,
+            Some(close) if close.token.kind == TokenKind::CloseParenthesis => {
+                return Ok((params, false));
+            }
+            Some(other) => {
+                return Err(PreprocessError::ExpectedParameterSeparator(
+                    other.token.span,
+                ));
+            }
+            None => return Err(PreprocessError::ExpectedParameterSeparator(last)),
+        }
+    }
+}
+
+/// Checks the operators in a replacement list, or in the contents of a
+/// `__VA_OPT__` within one.
+fn check_body(
+    body: &[PpToken],
+    definition: &Macro,
+    in_va_opt: bool,
+) -> Result<(), PreprocessError> {
+    for edge in [body.first(), body.last()].into_iter().flatten() {
+        if edge.token.kind == TokenKind::HashHash {
+            return Err(PreprocessError::PasteAtEdge(edge.token.span));
+        }
+    }
+
+    let mut i = 0;
+    while i < body.len() {
+        let token = &body[i];
+        let name = identifier(&token.token);
+        match name.as_deref() {
+            Some(name @ ("__VA_ARGS__" | "__VA_OPT__")) if !definition.variadic => {
+                return Err(PreprocessError::VariadicOnly(
+                    name.to_string(),
+                    token.token.span,
+                ));
+            }
+            Some("__VA_OPT__") if in_va_opt => {
+                return Err(PreprocessError::NestedVaOpt(token.token.span));
+            }
+            Some("__VA_OPT__") => {
+                let end = va_opt_end(body, i)?;
+                check_body(&body[i + 2..end], definition, true)?;
+                i = end;
+            }
+            _ if token.token.kind == TokenKind::Hash && definition.params.is_some() => {
+                let operand = body.get(i + 1);
+                if !operand.is_some_and(|operand| {
+                    definition.param(operand).is_some()
+                        || (definition.variadic && is_va_opt(operand))
+                }) {
+                    return Err(PreprocessError::StringifyNonParameter(token.token.span));
+                }
+            }
+            _ => {}
+        }
+        i += 1;
+    }
+
+    Ok(())
+}
+
+/// Lexes `text` as exactly one preprocessing token.
+fn relex(text: &str) -> Option<TokenKind> {
+    let mut lexer = Lexer::new(text);
+    let token = pp_token(lexer.next()?).ok()?;
+    (token.span == Span::new(0, text.len()) && lexer.next().is_none()).then_some(token.kind)
+}
+
+/// Applies `#` to the tokens of an argument, the `#` and operand being at
+/// `span`.
+fn stringify(tokens: &[PpToken], span: Span) -> Result<PpToken, PreprocessError> {
+    let mut spelling = String::from("\"");
+    for (i, token) in tokens.iter().enumerate() {
+        if i > 0 && (token.space_before || token.line_start) {
+            spelling.push(' ');
+        }
+        match token.token.kind {
+            TokenKind::StringLiteral(_) | TokenKind::Constant(Const::Char(..)) => {
+                for ch in token.spelling.chars() {
+                    if ch == '"' || ch == '\\' {
+                        spelling.push('\\');
+                    }
+                    spelling.push(ch);
+                }
+            }
+            _ => spelling.push_str(&token.spelling),
+        }
+    }
+    spelling.push('"');
+
+    match relex(&spelling) {
+        Some(kind @ TokenKind::StringLiteral(_)) => {
+            Ok(PpToken::new(Token { kind, span }, spelling, false, false))
+        }
+        _ => Err(PreprocessError::InvalidStringify(span)),
+    }
+}
+
+/// Applies the `##` at `span` to two tokens.
+fn glue(left: PpToken, right: PpToken, span: Span) -> Result<PpToken, PreprocessError> {
+    let spelling = format!("{}{}", left.spelling, right.spelling);
+    let Some(kind) = relex(&spelling) else {
+        return Err(PreprocessError::InvalidPaste(
+            left.spelling,
+            right.spelling,
+            span,
+        ));
+    };
+
+    Ok(PpToken {
+        token: Token { kind, span },
+        spelling,
+        line_start: false,
+        space_before: left.space_before,
+        location: left.location,
+        hide_set: left
+            .hide_set
+            .intersection(&right.hide_set)
+            .cloned()
+            .collect(),
+    })
+}
+
+/// Applies the `##` operators among substituted pieces, keeping any
+/// placemarkers that are left.
+fn paste(pieces: Vec<Piece>) -> Result<Vec<Piece>, PreprocessError> {
+    let mut out: Vec<Piece> = Vec::new();
+    let mut pieces = pieces.into_iter();
+    while let Some(piece) = pieces.next() {
+        let Piece::Paste(span) = piece else {
+            out.push(piece);
+            continue;
+        };
+        let left = out.pop().expect("'##' is not first in a replacement list");
+        let right = pieces
+            .next()
+            .expect("'##' is not last in a replacement list");
+        out.push(match (left, right) {
+            (Piece::Token(left), Piece::Token(right)) => Piece::Token(glue(left, right, span)?),
+            (Piece::Token(left), Piece::Paste(_)) => {
+                return Err(PreprocessError::InvalidPaste(
+                    left.spelling,
+                    String::from("##"),
+                    span,
+                ));
+            }
+            (Piece::Placemarker, right) => right,
+            (left, _) => left,
+        });
+    }
+
+    Ok(out)
+}
+
+impl<'a> Preprocessor<'a> {
+    /// Handles `#define`, given the tokens after the directive name.
+    pub(super) fn define(
+        &mut self,
+        line: Vec<PpToken>,
+        directive: Span,
+    ) -> Result<(), PreprocessError> {
+        let mut line = line.into_iter().peekable();
+        let Some(name_token) = line.next() else {
+            return Err(PreprocessError::InvalidMacroName(directive));
+        };
+        let Some(name) = identifier(&name_token.token) else {
+            return Err(PreprocessError::InvalidMacroName(name_token.token.span));
+        };
+
+        // A `(` right after the name, with no space, makes the macro
+        // function-like.
+        let (params, variadic) = match line
+            .next_if(|open| open.token.kind == TokenKind::OpenParenthesis && !open.space_before)
+        {
+            Some(open) => {
+                let (params, variadic) = parameters(&mut line, open.token.span)?;
+                (Some(params), variadic)
+            }
+            None => (None, false),
+        };
+        let mut body = line.collect::<Vec<_>>();
+        if let Some(first) = body.first_mut() {
+            first.space_before = false;
+        }
+
+        let definition = Macro {
+            span: name_token.token.span,
+            params,
+            variadic,
+            body,
+        };
+        check_body(&definition.body, &definition, false)?;
+        if let Some(previous) = self.macros.get(&name)
+            && !previous.same_as(&definition)
+        {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::MacroRedefined(name.clone()),
+                span: definition.span,
+            });
+        }
+        self.macros.insert(name, Rc::new(definition));
+
+        Ok(())
+    }
+
+    /// Handles `#undef`, given the tokens after the directive name.
+    pub(super) fn undef(
+        &mut self,
+        line: Vec<PpToken>,
+        directive: Span,
+    ) -> Result<(), PreprocessError> {
+        let mut line = line.into_iter();
+        let Some(name_token) = line.next() else {
+            return Err(PreprocessError::InvalidMacroName(directive));
+        };
+        let Some(name) = identifier(&name_token.token) else {
+            return Err(PreprocessError::InvalidMacroName(name_token.token.span));
+        };
+        if let Some(extra) = line.next() {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::ExtraTokens(String::from("undef")),
+                span: extra.token.span,
+            });
+        }
+
+        self.macros.remove(&name);
+        Ok(())
+    }
+
+    /// Replaces a macro invocation with its expansion, which is pushed back
+    /// onto the input to be rescanned. Returns the token itself if it does
+    /// not name a macro, names one it was produced by, or names a
+    /// function-like macro without being followed by `(` (C17 6.10.3.4).
+    pub(super) fn expand(&mut self, token: PpToken) -> Result<Option<PpToken>, PreprocessError> {
+        let Some(name) = identifier(&token.token) else {
+            return Ok(Some(token));
+        };
+        if token.hide_set.contains(&name) {
+            return Ok(Some(token));
+        }
+        if DYNAMIC.contains(&name.as_str()) {
+            return Ok(Some(self.expand_dynamic(&name, &token)));
+        }
+        let Some(definition) = self.macros.get(&name).cloned() else {
+            return Ok(Some(token));
+        };
+
+        // The expansion may not reinvoke the macro, nor any that produced
+        // both its name and, for a function-like macro, the closing `)`.
+        let (mut args, mut hide_set, end) = match definition.params {
+            None => (Vec::new(), token.hide_set.clone(), token.token.span.end),
+            Some(_) if !self.next_is_open_parenthesis() => return Ok(Some(token)),
+            Some(_) => {
+                let (args, close) = self.arguments(&name, &definition, token.token.span)?;
+                let hide_set: HideSet = token
+                    .hide_set
+                    .intersection(&close.hide_set)
+                    .cloned()
+                    .collect();
+                (args, hide_set, close.token.span.end)
+            }
+        };
+        hide_set.insert(name.clone());
+
+        let pieces = self.substitute(&definition, &definition.body, &mut args)?;
+        let mut replacement = paste(pieces)?
+            .into_iter()
+            .filter_map(|piece| match piece {
+                Piece::Token(token) => Some(token),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        // Each token of the expansion gets a span of its own, from which
+        // diagnostics find the invocation.
+        let site = Span::new(token.token.span.start, end);
+        let expansion = self.sources.add_expansion(name, site);
+        for (i, replaced) in replacement.iter_mut().enumerate() {
+            replaced.token.span = self.sources.expanded_span(replaced.token.span, expansion);
+            replaced.hide_set.extend(hide_set.iter().cloned());
+            replaced.line_start = false;
+            if i == 0 {
+                replaced.space_before = token.space_before;
+            }
+        }
+        self.push_tokens(replacement);
+
+        Ok(None)
+    }
+
+    fn next_is_open_parenthesis(&self) -> bool {
+        matches!(
+            self.frames.last().and_then(|frame| frame.tokens.last()),
+            Some(Lexed::Token(next)) if next.token.kind == TokenKind::OpenParenthesis
+        )
+    }
+
+    /// Reads the arguments of an invocation of the macro `name`, at `span`,
+    /// up to and including the closing `)`, which is returned with them.
+    fn arguments(
+        &mut self,
+        name: &str,
+        definition: &Macro,
+        span: Span,
+    ) -> Result<(Vec<Argument>, PpToken), PreprocessError> {
+        let params = definition.params.as_deref().unwrap_or_default();
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("a macro is invoked while reading input");
+        frame.tokens.pop();
+
+        let mut args = vec![Vec::new()];
+        let mut depth = 0;
+        let close = loop {
+            let token = match frame.tokens.pop() {
+                Some(Lexed::Token(token)) => token,
+                Some(Lexed::Error(err, _)) => return Err(err.into()),
+                None => {
+                    return Err(PreprocessError::UnterminatedMacroCall(
+                        name.to_string(),
+                        span,
+                    ));
+                }
+            };
+            match token.token.kind {
+                TokenKind::OpenParenthesis => depth += 1,
+                TokenKind::CloseParenthesis if depth == 0 => break token,
+                TokenKind::CloseParenthesis => depth -= 1,
+                // The variable arguments keep their commas.
+                TokenKind::Comma
+                    if depth == 0 && !(definition.variadic && args.len() == params.len()) =>
+                {
+                    args.push(Vec::new());
+                    continue;
+                }
+                _ => {}
+            }
+            args.last_mut()
+                .expect("there is always a current argument")
+                .push(token);
+        };
+
+        // `f()` passes no arguments to a macro without parameters, and the
+        // variable arguments may be left out entirely.
+        if params.is_empty() && matches!(args.as_slice(), [arg] if arg.is_empty()) {
+            args.clear();
+        }
+        if definition.variadic && args.len() + 1 == params.len() {
+            args.push(Vec::new());
+        }
+        if args.len() != params.len() {
+            return Err(PreprocessError::MacroArgumentCount(
+                name.to_string(),
+                params.len() - usize::from(definition.variadic),
+                args.len(),
+                Span::new(span.start, close.token.span.end),
+            ));
+        }
+
+        let args = args
+            .into_iter()
+            .map(|tokens| Argument {
+                tokens,
+                expanded: None,
+            })
+            .collect();
+        Ok((args, close))
+    }
+
+    /// The fully macro-expanded tokens of an argument.
+    fn expanded(&mut self, arg: &mut Argument) -> Result<Vec<PpToken>, PreprocessError> {
+        if arg.expanded.is_none() {
+            arg.expanded = Some(self.expand_tokens(arg.tokens.clone())?);
+        }
+
+        Ok(arg.expanded.clone().unwrap_or_default())
+    }
+
+    /// Substitutes the arguments into `body`, part of the replacement list
+    /// of `definition`. A parameter is replaced by its expanded argument,
+    /// except next to `#` or `##`, which take the argument as written.
+    fn substitute(
+        &mut self,
+        definition: &Macro,
+        body: &[PpToken],
+        args: &mut [Argument],
+    ) -> Result<Vec<Piece>, PreprocessError> {
+        let mut pieces = Vec::new();
+        let mut i = 0;
+        while i < body.len() {
+            let token = &body[i];
+            if token.token.kind == TokenKind::HashHash {
+                pieces.push(Piece::Paste(token.token.span));
+                i += 1;
+                continue;
+            }
+
+            if token.token.kind == TokenKind::Hash && definition.params.is_some() {
+                let operand = &body[i + 1];
+                let (tokens, end) = if is_va_opt(operand) {
+                    let end = va_opt_end(body, i + 1)?;
+                    let pieces = self.va_opt(definition, &body[i + 3..end], args)?;
+                    let tokens = pieces
+                        .into_iter()
+                        .filter_map(|piece| match piece {
+                            Piece::Token(token) => Some(token),
+                            _ => None,
+                        })
+                        .collect::<Vec<_>>();
+                    (tokens, end)
+                } else {
+                    let index = definition
+                        .param(operand)
+                        .expect("'#' is followed by a parameter");
+                    (args[index].tokens.clone(), i + 1)
+                };
+                let span = Span::new(token.token.span.start, body[end].token.span.end);
+                let mut string = stringify(&tokens, span)?;
+                string.space_before = token.space_before;
+                pieces.push(Piece::Token(string));
+                i = end + 1;
+                continue;
+            }
+
+            if definition.variadic && is_va_opt(token) {
+                let end = va_opt_end(body, i)?;
+                let mut va_opt = self.va_opt(definition, &body[i + 2..end], args)?;
+                if let Some(Piece::Token(first)) = va_opt.first_mut() {
+                    first.space_before = token.space_before;
+                }
+                pieces.append(&mut va_opt);
+                i = end + 1;
+                continue;
+            }
+
+            let pasted = (i > 0 && body[i - 1].token.kind == TokenKind::HashHash)
+                || body
+                    .get(i + 1)
+                    .is_some_and(|next| next.token.kind == TokenKind::HashHash);
+            match definition.param(token) {
+                Some(index) => {
+                    let tokens = match pasted {
+                        true => args[index].tokens.clone(),
+                        false => self.expanded(&mut args[index])?,
+                    };
+                    if tokens.is_empty() {
+                        pieces.push(Piece::Placemarker);
+                    }
+                    for (j, mut arg_token) in tokens.into_iter().enumerate() {
+                        arg_token.space_before |= arg_token.line_start;
+                        arg_token.line_start = false;
+                        if j == 0 {
+                            arg_token.space_before = token.space_before;
+                        }
+                        pieces.push(Piece::Token(arg_token));
+                    }
+                }
+                None => pieces.push(Piece::Token(token.clone())),
+            }
+            i += 1;
+        }
+
+        Ok(pieces)
+    }
+
+    /// Replaces `__VA_OPT__` with its `contents`: nothing if the variable
+    /// arguments expand to nothing, and otherwise the contents, substituted
+    /// and with their own `##` operators applied.
+    fn va_opt(
+        &mut self,
+        definition: &Macro,
+        contents: &[PpToken],
+        args: &mut [Argument],
+    ) -> Result<Vec<Piece>, PreprocessError> {
+        let va_args = args
+            .last_mut()
+            .expect("a variadic macro has the __VA_ARGS__ parameter");
+        if self.expanded(va_args)?.is_empty() {
+            return Ok(vec![Piece::Placemarker]);
+        }
+
+        let pieces = paste(self.substitute(definition, contents, args)?)?;
+        Ok(match pieces.is_empty() {
+            true => vec![Piece::Placemarker],
+            false => pieces,
+        })
+    }
+
+    /// Pushes tokens back onto the innermost input, to be read next.
+    pub(super) fn push_tokens(&mut self, tokens: Vec<PpToken>) {
+        let frame = self
+            .frames
+            .last_mut()
+            .expect("tokens are pushed back while reading input");
+        frame
+            .tokens
+            .extend(tokens.into_iter().rev().map(Lexed::Token));
+    }
+}
@@ -0,0 +1,1654 @@
+mod bundled;
+mod conditional;
+mod expr;
+mod macros;
+pub mod output;
+mod predefined;
+
+use crate::diagnostics::{Pragma, Severity, Warning};
+use crate::error::{
+    LexerError, LexerErrorKind, PreprocessError, PreprocessWarning, PreprocessWarningKind,
+};
+use crate::lexer::Lexer;
+use crate::preprocessor::conditional::Conditional;
+use crate::preprocessor::macros::Macro;
+use crate::source_map::SourceMap;
+use crate::span::Span;
+use crate::token::{Token, TokenKind};
+use std::collections::{BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+const MAX_INCLUDE_DEPTH: usize = 200;
+
+/// The names of the macros whose expansion produced a token. A token may
+/// not be expanded again by any of them, even after rescanning has moved
+/// on (C17 6.10.3.4).
+type HideSet = BTreeSet<String>;
+
+/// A token with the layout and expansion state the preprocessor needs.
+#[derive(Debug, Clone)]
+pub struct PpToken {
+    pub token: Token,
+    /// The token as written, without line splices. `#` and `##` work on
+    /// spellings.
+    pub spelling: String,
+    /// Whether the token is the first on its line, where `#` starts a
+    /// directive.
+    pub line_start: bool,
+    /// Whether whitespace or a comment came before the token.
+    pub space_before: bool,
+    /// The global offset the output token counts as read from: that of
+    /// the macro invocation for one produced by an expansion.
+    pub location: usize,
+    hide_set: HideSet,
+}
+
+impl PpToken {
+    fn new(token: Token, spelling: String, line_start: bool, space_before: bool) -> Self {
+        Self {
+            location: token.span.start,
+            token,
+            spelling,
+            line_start,
+            space_before,
+            hide_set: HideSet::new(),
+        }
+    }
+
+    /// The token to parse, or the error an invalid one stands for.
+    pub fn into_token(self) -> Result<Token, LexerError> {
+        match self.token.kind {
+            TokenKind::Invalid(kind) => Err(LexerError {
+                kind,
+                span: self.token.span,
+            }),
+            _ => Ok(self.token),
+        }
+    }
+}
+
+/// An element of the input. Lexer errors stay in the stream until read, so
+/// that those in skipped lines can be ignored.
+enum Lexed {
+    Token(PpToken),
+    Error(LexerError, bool),
+}
+
+impl Lexed {
+    fn line_start(&self) -> bool {
+        match self {
+            Self::Token(token) => token.line_start,
+            Self::Error(_, line_start) => *line_start,
+        }
+    }
+
+    fn span(&self) -> Span {
+        match self {
+            Self::Token(token) => token.token.span,
+            Self::Error(err, _) => err.span,
+        }
+    }
+}
+
+/// An input being read: a source file, or the tokens of a directive line
+/// being macro-expanded. Tokens are stored in reverse so that expansions
+/// can be pushed back on top.
+struct Frame {
+    /// The file the tokens come from, used to resolve `#include "..."`.
+    path: PathBuf,
+    tokens: Vec<Lexed>,
+    /// The conditional directives open in the file, innermost last.
+    conditionals: Vec<Conditional>,
+    /// Whether the tokens are the file itself, rather than a line or macro
+    /// argument taken from it.
+    is_file: bool,
+    /// Whether the file is a system header: found in a system directory,
+    /// or included by one.
+    system: bool,
+    /// The index in the search chain of the directory the file was found
+    /// in, or `None` if it was not searched for.
+    dir: Option<usize>,
+}
+
+/// A header found by searching for it.
+struct Header {
+    path: PathBuf,
+    system: bool,
+    dir: Option<usize>,
+}
+
+/// Directories searched by `#include`. Quoted names are first looked up
+/// next to the including file, then in `quote`; both forms then search
+/// `angled`, the `-I` directories, and `system`. Together they make up the
+/// search chain that `#include_next` resumes.
+pub struct IncludePaths {
+    pub quote: Vec<PathBuf>,
+    pub angled: Vec<PathBuf>,
+    pub system: Vec<PathBuf>,
+}
+
+impl Default for IncludePaths {
+    fn default() -> Self {
+        Self {
+            quote: Vec::new(),
+            angled: Vec::new(),
+            system: [
+                bundled::BUNDLED_DIR,
+                "/usr/local/include",
+                "/usr/include/x86_64-linux-gnu",
+                "/usr/include",
+            ]
+            .into_iter()
+            .map(PathBuf::from)
+            .collect(),
+        }
+    }
+}
+
+pub struct Preprocessor<'a> {
+    sources: &'a mut SourceMap,
+    include_paths: IncludePaths,
+    macros: HashMap<String, Rc<Macro>>,
+    /// Canonical paths of the files that contained `#pragma once`.
+    once: HashSet<PathBuf>,
+    frames: Vec<Frame>,
+    warnings: Vec<PreprocessWarning>,
+    /// The `#pragma GCC diagnostic` directives read, at the offsets of
+    /// their `#`.
+    diagnostic_pragmas: Vec<(usize, Pragma)>,
+    /// The directives that the `-D`, `-U` and `-include` options stand for,
+    /// read as the file `<command-line>`.
+    command_line: String,
+    /// The global offset of the last token read from the source, for
+    /// `__FILE__` and `__LINE__`.
+    position: usize,
+    /// The next value of `__COUNTER__`.
+    counter: usize,
+    /// The headers read, in order, and whether each is a system header.
+    headers: Vec<(PathBuf, bool)>,
+}
+
+/// The name a token spells if it is an identifier. Keywords count: the
+/// preprocessor runs before they are special, so `#define int long` works.
+fn identifier(token: &Token) -> Option<String> {
+    match &token.kind {
+        TokenKind::Identifier(name) => Some(name.clone()),
+        TokenKind::Keyword(keyword) => Some(keyword.to_string()),
+        _ => None,
+    }
+}
+
+/// Keeps a lexer error that still covers a whole preprocessing token, a
+/// stray character or a malformed number, as an invalid token.
+fn pp_token(result: Result<Token, LexerError>) -> Result<Token, LexerError> {
+    match result {
+        Err(LexerError {
+            kind:
+                kind @ (LexerErrorKind::UnexpectedCharacter(_)
+                | LexerErrorKind::InvalidConstSuffix
+                | LexerErrorKind::InvalidIntegerLiteral),
+            span,
+        }) => Ok(Token {
+            kind: TokenKind::Invalid(kind),
+            span,
+        }),
+        result => result,
+    }
+}
+
+/// Lexes a whole file whose first byte has the global offset `base`.
+fn lex_file(src: &str, base: usize) -> (Vec<Lexed>, Vec<PreprocessWarning>) {
+    let shift = |span: Span| Span::new(span.start + base, span.end + base);
+    let mut lexer = Lexer::new(src);
+    let mut items: Vec<Lexed> = Vec::new();
+
+    loop {
+        // The operand of `#include` is a header name, in which `<` and `"`
+        // do not start ordinary tokens.
+        let at_include = match items.as_slice() {
+            [
+                ..,
+                Lexed::Token(hash),
+                Lexed::Token(PpToken {
+                    token:
+                        Token {
+                            kind: TokenKind::Identifier(name),
+                            ..
+                        },
+                    line_start: false,
+                    ..
+                }),
+            ] => {
+                hash.line_start
+                    && hash.token.kind == TokenKind::Hash
+                    && (name == "include" || name == "include_next")
+            }
+            _ => false,
+        };
+        let next = match at_include {
+            true => lexer.header_name().or_else(|| lexer.next()),
+            false => lexer.next(),
+        };
+        let Some(result) = next else {
+            break;
+        };
+
+        items.push(match pp_token(result) {
+            Ok(mut token) => {
+                let spelling = src[token.span.start..token.span.end]
+                    .replace("\\\r\n", "")
+                    .replace("\\\n", "");
+                token.span = shift(token.span);
+                Lexed::Token(PpToken::new(
+                    token,
+                    spelling,
+                    lexer.line_start(),
+                    lexer.space_before(),
+                ))
+            }
+            Err(mut err) => {
+                err.span = shift(err.span);
+                Lexed::Error(err, lexer.line_start())
+            }
+        });
+    }
+
+    let warnings = lexer
+        .warnings()
+        .iter()
+        .map(|warning| PreprocessWarning {
+            kind: PreprocessWarningKind::Lexer(warning.kind.clone()),
+            span: shift(warning.span),
+        })
+        .collect();
+
+    (items, warnings)
+}
+
+impl<'a> Preprocessor<'a> {
+    pub fn new(sources: &'a mut SourceMap, include_paths: IncludePaths) -> Self {
+        Self {
+            sources,
+            include_paths,
+            macros: HashMap::new(),
+            once: HashSet::new(),
+            frames: Vec::new(),
+            warnings: Vec::new(),
+            diagnostic_pragmas: Vec::new(),
+            command_line: String::new(),
+            position: 0,
+            counter: 0,
+            headers: Vec::new(),
+        }
+    }
+
+    /// Defines a macro as `-D` does: `NAME` defines it as `1`, and
+    /// `NAME=VALUE` as `VALUE`.
+    pub fn predefine(&mut self, definition: &str) {
+        let (name, value) = definition.split_once('=').unwrap_or((definition, "1"));
+        self.command_line
+            .push_str(&format!("#define {name} {value}\n"));
+    }
+
+    /// Undefines a macro as `-U` does.
+    pub fn preundefine(&mut self, name: &str) {
+        self.command_line.push_str(&format!("#undef {name}\n"));
+    }
+
+    /// Includes a file before the translation unit, as `-include` does. It
+    /// is looked up relative to the working directory first.
+    pub fn preinclude(&mut self, path: &str) {
+        let path = path.replace('\\', "\\\\").replace('"', "\\\"");
+        self.command_line
+            .push_str(&format!("#include \"{path}\"\n"));
+    }
+
+    /// The `#pragma GCC diagnostic` directives read, in translation order.
+    pub fn diagnostic_pragmas(&self) -> &[(usize, Pragma)] {
+        &self.diagnostic_pragmas
+    }
+
+    pub fn into_warnings(self) -> Vec<PreprocessWarning> {
+        self.warnings
+    }
+
+    /// The headers read while preprocessing, each once and in the order
+    /// first read. System headers are left out unless `system` is set.
+    pub fn dependencies(&self, system: bool) -> Vec<&Path> {
+        let mut seen = HashSet::new();
+        self.headers
+            .iter()
+            .filter(|(_, is_system)| system || !is_system)
+            .map(|(path, _)| path.as_path())
+            .filter(|path| seen.insert(*path))
+            .collect()
+    }
+
+    /// Preprocesses the translation unit in `src`, read from `path`, into
+    /// the tokens to parse.
+    pub fn run(&mut self, path: &Path, src: String) -> Result<Vec<PpToken>, PreprocessError> {
+        // The predefined macros come first, then the command line, each
+        // read to its end before the next.
+        let main = Header {
+            path: path.to_path_buf(),
+            system: false,
+            dir: None,
+        };
+        self.push_file(main, src, None);
+        let command_line = std::mem::take(&mut self.command_line);
+        for (name, src) in [
+            ("<command-line>", command_line),
+            ("<built-in>", predefined::builtins()),
+        ] {
+            let header = Header {
+                path: PathBuf::from(name),
+                system: false,
+                dir: None,
+            };
+            self.push_file(header, src, None);
+        }
+
+        let mut tokens = Vec::new();
+        while !self.frames.is_empty() {
+            match self.next_expanded(true)? {
+                Some(mut token) => {
+                    token.location = self.position;
+                    tokens.push(token);
+                }
+                None => {
+                    let frame = self.frames.pop().expect("input remains");
+                    if let Some(open) = frame.conditionals.last() {
+                        return Err(PreprocessError::UnterminatedConditional(
+                            open.directive.clone(),
+                            open.span,
+                        ));
+                    }
+                }
+            }
+        }
+
+        Ok(tokens)
+    }
+
+    /// Starts reading a file, included by the directive at `included_from`
+    /// if any.
+    fn push_file(&mut self, header: Header, src: String, included_from: Option<usize>) {
+        let Header { path, system, dir } = header;
+        let base = self
+            .sources
+            .add(path.display().to_string(), src, included_from);
+        let file = self.sources.file(base).expect("the file was just added");
+        let (mut tokens, warnings) = lex_file(&file.src, base);
+        tokens.reverse();
+
+        self.warnings.extend(warnings);
+        self.frames.push(Frame {
+            path,
+            tokens,
+            conditionals: Vec::new(),
+            is_file: true,
+            system,
+            dir,
+        });
+    }
+
+    /// The next fully expanded token of the innermost input, or `None` at
+    /// its end. Directives are carried out when `directives` is set.
+    fn next_expanded(&mut self, directives: bool) -> Result<Option<PpToken>, PreprocessError> {
+        loop {
+            let Some(frame) = self.frames.last_mut() else {
+                return Ok(None);
+            };
+            let token = match frame.tokens.pop() {
+                Some(Lexed::Token(token)) => token,
+                Some(Lexed::Error(err, _)) => return Err(err.into()),
+                None => return Ok(None),
+            };
+            if frame.is_file && token.hide_set.is_empty() {
+                self.position = token.token.span.start;
+            }
+
+            if directives
+                && token.line_start
+                && token.token.kind == TokenKind::Hash
+                && token.hide_set.is_empty()
+            {
+                self.directive(token.token.span)?;
+                continue;
+            }
+            if let Some(token) = self.expand(token)? {
+                return Ok(Some(token));
+            }
+        }
+    }
+
+    /// Takes the rest of the current line from the innermost input.
+    fn rest_of_line(&mut self) -> Vec<Lexed> {
+        let mut line = Vec::new();
+        if let Some(frame) = self.frames.last_mut() {
+            while frame.tokens.last().is_some_and(|next| !next.line_start()) {
+                line.extend(frame.tokens.pop());
+            }
+        }
+
+        line
+    }
+
+    /// Checks that a directive line lexed cleanly.
+    fn line_tokens(line: Vec<Lexed>) -> Result<Vec<PpToken>, PreprocessError> {
+        line.into_iter()
+            .map(|lexed| match lexed {
+                Lexed::Token(token) => Ok(token),
+                Lexed::Error(err, _) => Err(err.into()),
+            })
+            .collect()
+    }
+
+    /// Fully macro-expands tokens on their own, as for a directive line or a
+    /// macro argument.
+    fn expand_tokens(&mut self, tokens: Vec<PpToken>) -> Result<Vec<PpToken>, PreprocessError> {
+        self.with_tokens(tokens, |pp| {
+            let mut expanded = Vec::new();
+            while let Some(token) = pp.next_expanded(false)? {
+                expanded.push(token);
+            }
+
+            Ok(expanded)
+        })
+    }
+
+    /// Runs `read` with `tokens` as the innermost input, which ends with
+    /// them.
+    fn with_tokens<T>(
+        &mut self,
+        tokens: Vec<PpToken>,
+        read: impl FnOnce(&mut Self) -> Result<T, PreprocessError>,
+    ) -> Result<T, PreprocessError> {
+        let current = self.frames.last().expect("directives are read from a file");
+        let (path, system, dir) = (current.path.clone(), current.system, current.dir);
+        self.frames.push(Frame {
+            path,
+            tokens: Vec::new(),
+            conditionals: Vec::new(),
+            is_file: false,
+            system,
+            dir,
+        });
+        self.push_tokens(tokens);
+
+        let result = read(self);
+        self.frames.pop();
+
+        result
+    }
+
+    fn current_path(&self) -> &Path {
+        &self
+            .frames
+            .last()
+            .expect("directives are read from a file")
+            .path
+    }
+
+    /// Carries out the directive introduced by the `#` at `hash`.
+    fn directive(&mut self, hash: Span) -> Result<(), PreprocessError> {
+        let mut line = self.rest_of_line().into_iter();
+        let Some(name) = line.next() else {
+            // A lone `#` is the null directive.
+            return Ok(());
+        };
+        let line = line.collect::<Vec<_>>();
+        let directive = Span::new(hash.start, name.span().end);
+        let name = match &name {
+            Lexed::Token(token) => identifier(&token.token),
+            Lexed::Error(..) => None,
+        };
+
+        match name.as_deref() {
+            Some(
+                name @ ("if" | "ifdef" | "ifndef" | "elif" | "elifdef" | "elifndef" | "else"
+                | "endif"),
+            ) => self.conditional(name, line, directive),
+            Some("define") => self.define(Self::line_tokens(line)?, directive),
+            Some("undef") => self.undef(Self::line_tokens(line)?, directive),
+            Some(name @ ("include" | "include_next")) => {
+                self.include(name, Self::line_tokens(line)?, directive)
+            }
+            Some("error") => Err(PreprocessError::ErrorDirective(
+                self.line_text(&line),
+                directive,
+            )),
+            Some("warning") => {
+                self.warnings.push(PreprocessWarning {
+                    kind: PreprocessWarningKind::WarningDirective(self.line_text(&line)),
+                    span: directive,
+                });
+                Ok(())
+            }
+            Some("pragma") => {
+                self.pragma(&Self::line_tokens(line)?, directive);
+                Ok(())
+            }
+            _ => Err(PreprocessError::InvalidDirective(directive)),
+        }
+    }
+
+    /// The source text of a directive line, as `#error` reports it. The
+    /// line need not consist of valid tokens.
+    fn line_text(&self, line: &[Lexed]) -> String {
+        match (line.first(), line.last()) {
+            (Some(first), Some(last)) => self
+                .sources
+                .snippet(Span::new(first.span().start, last.span().end))
+                .to_string(),
+            _ => String::new(),
+        }
+    }
+
+    fn pragma(&mut self, line: &[PpToken], directive: Span) {
+        let name = |i: usize| line.get(i).and_then(|t| identifier(&t.token));
+        // Other pragmas are ignored, as the standard allows for unknown ones.
+        match (name(0).as_deref(), name(1).as_deref()) {
+            (Some("once"), _) if line.len() == 1 => {
+                if let Ok(path) = self.current_path().canonicalize() {
+                    self.once.insert(path);
+                }
+            }
+            (Some("GCC" | "clang"), Some("diagnostic")) => {
+                self.diagnostic_pragma(&line[2..], directive);
+            }
+            _ => {}
+        }
+    }
+
+    /// Handles `#pragma GCC diagnostic`, given the tokens after it, which
+    /// must be `push`, `pop`, or `ignored`, `warning` or `error` followed
+    /// by a warning option as a string.
+    fn diagnostic_pragma(&mut self, line: &[PpToken], directive: Span) {
+        let kind = line.first().and_then(|t| identifier(&t.token));
+        let pragma = match (kind.as_deref(), &line[line.len().min(1)..]) {
+            (Some("push"), []) => Pragma::Push,
+            (Some("pop"), []) => Pragma::Pop,
+            (Some(kind @ ("ignored" | "warning" | "error")), [token]) => {
+                let TokenKind::StringLiteral(bytes) = &token.token.kind else {
+                    return;
+                };
+                let option = String::from_utf8_lossy(bytes);
+                let Some(warnings) = option.strip_prefix("-W").and_then(Warning::named) else {
+                    self.warnings.push(PreprocessWarning {
+                        kind: PreprocessWarningKind::UnknownWarningGroup(option.into_owned()),
+                        span: token.token.span,
+                    });
+                    return;
+                };
+                let severity = match kind {
+                    "ignored" => Severity::Ignored,
+                    "warning" => Severity::Warning,
+                    _ => Severity::Error,
+                };
+                Pragma::Set(warnings, severity)
+            }
+            _ => return,
+        };
+        self.diagnostic_pragmas.push((directive.start, pragma));
+    }
+
+    /// Handles `#include` or `#include_next`, given the tokens after the
+    /// directive name.
+    fn include(
+        &mut self,
+        directive_name: &str,
+        line: Vec<PpToken>,
+        directive: Span,
+    ) -> Result<(), PreprocessError> {
+        // Any other form is macro-expanded, and must then name a header.
+        let mut line = match line.first().map(|t| &t.token.kind) {
+            Some(TokenKind::HeaderName(_)) => line,
+            _ => self.expand_tokens(line)?,
+        }
+        .into_iter();
+        let operand = line.next();
+        let (name, system, span) = match operand.as_ref().map(|t| &t.token) {
+            Some(Token {
+                kind: TokenKind::HeaderName(spelling),
+                span,
+            }) => {
+                let name = spelling[1..spelling.len() - 1].to_string();
+                (name, spelling.starts_with('<'), *span)
+            }
+            Some(Token {
+                kind: TokenKind::StringLiteral(bytes),
+                span,
+            }) => (String::from_utf8_lossy(bytes).into_owned(), false, *span),
+            Some(token) => return Err(PreprocessError::InvalidInclude(token.span)),
+            None => return Err(PreprocessError::InvalidInclude(directive)),
+        };
+        if let Some(extra) = line.next() {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::ExtraTokens(directive_name.to_string()),
+                span: extra.token.span,
+            });
+        }
+
+        if self.frames.len() > MAX_INCLUDE_DEPTH {
+            return Err(PreprocessError::IncludeTooDeep(directive));
+        }
+        let next = directive_name == "include_next";
+        if next && self.in_primary_source() {
+            self.warnings.push(PreprocessWarning {
+                kind: PreprocessWarningKind::IncludeNextInPrimarySource,
+                span: directive,
+            });
+        }
+        let Some(header) = self.find_include(&name, system, next) else {
+            return Err(PreprocessError::IncludeNotFound(name, span));
+        };
+        if header
+            .path
+            .canonicalize()
+            .is_ok_and(|canonical| self.once.contains(&canonical))
+        {
+            return Ok(());
+        }
+        let src = match bundled::source(&header.path) {
+            Some(src) => src.to_string(),
+            // Bundled headers are not files, which make could depend on.
+            None => {
+                let Ok(src) = std::fs::read_to_string(&header.path) else {
+                    return Err(PreprocessError::UnreadableInclude(name, span));
+                };
+                self.headers.push((header.path.clone(), header.system));
+                src
+            }
+        };
+
+        self.push_file(header, src, Some(directive.start));
+        Ok(())
+    }
+
+    /// Whether the directive being read is in the main file or on the
+    /// command line, rather than in a header.
+    fn in_primary_source(&self) -> bool {
+        self.sources
+            .file(self.position)
+            .is_some_and(|file| file.included_from.is_none())
+    }
+
+    /// Searches for the header `name`, written as `<name>` if `angled`.
+    /// For `#include_next`, the search resumes after the directory the
+    /// current file was found in.
+    fn find_include(&self, name: &str, angled: bool, next: bool) -> Option<Header> {
+        let current = self.frames.last().expect("directives are read from a file");
+        let exists = |path: &Path| bundled::source(path).is_some() || path.is_file();
+
+        let (start, beside) = match (next, current.dir) {
+            (true, Some(dir)) => (dir + 1, None),
+            _ => (0, current.path.parent().filter(|_| !angled)),
+        };
+        if let Some(path) = beside.map(|dir| dir.join(name))
+            && exists(&path)
+        {
+            return Some(Header {
+                path,
+                system: current.system,
+                dir: None,
+            });
+        }
+
+        let paths = &self.include_paths;
+        let system_start = paths.quote.len() + paths.angled.len();
+        paths
+            .quote
+            .iter()
+            .chain(&paths.angled)
+            .chain(&paths.system)
+            .enumerate()
+            .skip(start)
+            .filter(|(i, _)| !angled || *i >= paths.quote.len())
+            .map(|(i, dir)| (i, dir.join(name)))
+            .find(|(_, path)| exists(path))
+            .map(|(i, path)| Header {
+                path,
+                system: i >= system_start || current.system,
+                dir: Some(i),
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::HasSpan;
+
+    /// A directory of files for a test, removed once dropped.
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        /// Creates `rcc-<name>-<pid>` in the temporary directory, holding
+        /// each of `files`, a path within it with its contents.
+        fn new(name: &str, files: &[(&str, &str)]) -> Self {
+            let dir = std::env::temp_dir().join(format!("rcc-{name}-{}", std::process::id()));
+            std::fs::create_dir_all(&dir).unwrap();
+            for (file, src) in files {
+                let path = dir.join(file);
+                std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+                std::fs::write(path, src).unwrap();
+            }
+            TempDir(dir)
+        }
+    }
+
+    impl std::ops::Deref for TempDir {
+        type Target = Path;
+
+        fn deref(&self) -> &Path {
+            &self.0
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Preprocesses `src` as the file `path` and spells the resulting tokens,
+    /// separated where the source separated them.
+    fn preprocess_file(path: &Path, src: &str) -> Result<String, PreprocessError> {
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        let tokens = preprocessor.run(path, src.to_string())?;
+
+        let mut out = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            if i > 0 && (token.space_before || token.line_start) {
+                out.push(' ');
+            }
+            out.push_str(&token.spelling);
+        }
+        Ok(out)
+    }
+
+    fn preprocess_tokens(src: &str) -> Vec<PpToken> {
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        preprocessor
+            .run(Path::new("test.c"), src.to_string())
+            .unwrap()
+    }
+
+    fn preprocess(src: &str) -> Result<String, PreprocessError> {
+        preprocess_file(Path::new("test.c"), src)
+    }
+
+    #[test]
+    fn object_like_macros() -> Result<(), PreprocessError> {
+        let src = "#define N 4\n#define ARRAY a[N]\nint ARRAY;\n#undef N\nN";
+        assert_eq!(preprocess(src)?, "int a[4]; N");
+        Ok(())
+    }
+
+    #[test]
+    fn recursive_macro_is_not_reexpanded() -> Result<(), PreprocessError> {
+        let src = "#define x (4, y)\n#define y (2, x)\nx y";
+        assert_eq!(preprocess(src)?, "(4, (2, x)) (2, (4, y))");
+        Ok(())
+    }
+
+    /// C17 6.10.3.5p5.
+    #[test]
+    fn standard_example_3() -> Result<(), PreprocessError> {
+        let src = "#define x 3
+#define f(a) f(x * (a))
+#undef x
+#define x 2
+#define g f
+#define z z[0]
+#define h g(~
+#define m(a) a(w)
+#define w 0,1
+#define t(a) a
+#define p() int
+#define q(x) x
+#define r(x,y) x ## y
+#define str(x) # x
+f(y+1) + f(f(z)) % t(t(g)(0) + t)(1);
+g(x+(3,4)-w) | h 5) & m
+(f)^m(m);
+p() i[q()] = { q(1), r(2,3), r(4,), r(,5), r(,) };
+char c[2][6] = { str( hello), str() };";
+        let expected = [
+            "f(2 * (y+1)) + f(2 * (f(2 * (z[0])))) % f(2 * (0)) + t(1);",
+            "f(2 * (2+(3,4)-0,1)) | f(2 * (~ 5)) & f(2 * (0,1))^m(0,1);",
+            "int i[] = { 1, 23, 4, 5, };",
+            "char c[2][6] = { \"hello\", \"\" };",
+        ];
+        assert_eq!(preprocess(src)?, expected.join(" "));
+        Ok(())
+    }
+
+    /// C17 6.10.3.5p6, with the header the `#include` names.
+    #[test]
+    fn standard_example_4() -> Result<(), PreprocessError> {
+        let dir = TempDir::new("example", &[("vers2.h", "vers2_h\n")]);
+
+        let src = r#"#define str(s) # s
+#define xstr(s) str(s)
+#define debug(s, t) printf("x" # s "= %d, x" # t "= %s", \
+ x ## s, x ## t)
+#define INCFILE(n) vers ## n
+#define glue(a, b) a ## b
+#define xglue(a, b) glue(a, b)
+#define HIGHLOW "hello"
+#define LOW LOW ", world"
+debug(1, 2);
+fputs(str(strncmp("abc\0d", "abc", '\4') // this goes away
+ == 0) str(: @\n), s);
+#include xstr(INCFILE(2).h)
+glue(HIGH, LOW);
+xglue(HIGH, LOW)"#;
+        let expected = [
+            r#"printf("x" "1" "= %d, x" "2" "= %s", x1, x2);"#,
+            r#"fputs("strncmp(\"abc\\0d\", \"abc\", '\\4') == 0" ": @\n", s);"#,
+            "vers2_h",
+            r#""hello";"#,
+            r#""hello" ", world""#,
+        ];
+        let out = preprocess_file(&dir.join("main.c"), src);
+        assert_eq!(out?, expected.join(" "));
+        Ok(())
+    }
+
+    /// C17 6.10.3.5p7.
+    #[test]
+    fn standard_example_5() -> Result<(), PreprocessError> {
+        let src = "#define t(x,y,z) x ## y ## z
+int j[] = { t(1,2,3), t(,4,5), t(6,,7), t(8,9,),
+ t(10,,), t(,11,), t(,,12), t(,,) };";
+        assert_eq!(
+            preprocess(src)?,
+            "int j[] = { 123, 45, 67, 89, 10, 11, 12, };"
+        );
+        Ok(())
+    }
+
+    /// C17 6.10.3.5p8: the first definitions of each macro are the same,
+    /// and each later one is different.
+    #[test]
+    fn standard_example_6() -> Result<(), PreprocessError> {
+        let src = "#define OBJ_LIKE (1-1)
+#define OBJ_LIKE /* white space */ (1-1) /* other */
+#define FUNC_LIKE(a) ( a )
+#define FUNC_LIKE( a )( /* note the white space */ \\
+ a /* other stuff on this line
+ */ )
+#define OBJ_LIKE (0) // different token sequence
+#define OBJ_LIKE (1 - 1) // different white space
+#define FUNC_LIKE(b) ( a ) // different parameter usage
+#define FUNC_LIKE(b) ( b ) // different parameter spelling
+";
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        preprocessor.run(Path::new("test.c"), src.to_string())?;
+        let lines = preprocessor
+            .into_warnings()
+            .iter()
+            .map(|warning| sources.snippet(warning.span).to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(lines, ["OBJ_LIKE", "OBJ_LIKE", "FUNC_LIKE", "FUNC_LIKE"]);
+        Ok(())
+    }
+
+    /// C17 6.10.3.5p9.
+    #[test]
+    fn standard_example_7() -> Result<(), PreprocessError> {
+        let src = r#"#define debug(...) fprintf(stderr, __VA_ARGS__)
+#define showlist(...) puts(#__VA_ARGS__)
+#define report(test, ...) ((test)?puts(#test):\
+ printf(__VA_ARGS__))
+debug("Flag");
+debug("X = %d\n", x);
+showlist(The first, second, and third items.);
+report(x>y, "x is %d but y is %d", x, y);"#;
+        let expected = [
+            r#"fprintf(stderr, "Flag");"#,
+            r#"fprintf(stderr, "X = %d\n", x);"#,
+            r#"puts("The first, second, and third items.");"#,
+            r#"((x>y)?puts("x>y"): printf("x is %d but y is %d", x, y));"#,
+        ];
+        assert_eq!(preprocess(src)?, expected.join(" "));
+        Ok(())
+    }
+
+    /// C17 6.10.3.3p4.
+    #[test]
+    fn pasted_hash_hash_is_not_an_operator() -> Result<(), PreprocessError> {
+        let src = "#define hash_hash # ## #
+#define mkstr(a) # a
+#define in_between(a) mkstr(a)
+#define join(c, d) in_between(c hash_hash d)
+char p[] = join(x, y);";
+        assert_eq!(preprocess(src)?, r#"char p[] = "x ## y";"#);
+        Ok(())
+    }
+
+    /// C17 6.10.3.4p4: the `g` left at the end of an expansion may take
+    /// its arguments from the text after it.
+    #[test]
+    fn rescanning_reads_past_the_expansion() -> Result<(), PreprocessError> {
+        let src = "#define f(a) a*g\n#define g(a) f(a)\nf(2)(9)";
+        assert_eq!(preprocess(src)?, "2*9*g");
+        Ok(())
+    }
+
+    /// The `__VA_OPT__` examples of C23 6.10.5.1.
+    #[test]
+    fn va_opt() -> Result<(), PreprocessError> {
+        let src = "#define F(...) f(0 __VA_OPT__(,) __VA_ARGS__)
+#define G(X, ...) f(0, X __VA_OPT__(,) __VA_ARGS__)
+#define SDEF(sname, ...) S sname __VA_OPT__(= { __VA_ARGS__ })
+#define EMP
+#define H2(X, Y, ...) __VA_OPT__(X ## Y,) __VA_ARGS__
+#define H3(X, ...) #__VA_OPT__(X##X X##X)
+#define H4(X, ...) __VA_OPT__(a X ## X) ## b
+#define H5A(...) __VA_OPT__()/**/__VA_OPT__()
+#define H5B(X) a ## X ## b
+#define H5C(X) H5B(X)
+";
+        let cases = [
+            ("F(a,b,c)", "f(0 , a,b,c)"),
+            ("F()", "f(0)"),
+            ("F(EMP)", "f(0)"),
+            ("G(a,b,c)", "f(0, a , b,c)"),
+            ("G(a,)", "f(0, a)"),
+            ("G(a)", "f(0, a)"),
+            ("SDEF(foo);", "S foo;"),
+            ("SDEF(bar, 1, 2);", "S bar = { 1, 2 };"),
+            ("H2(a, b, c, d)", "ab, c, d"),
+            ("H3(, 0)", "\"\""),
+            ("H4(, 1)", "a b"),
+            ("H5C(H5A())", "ab"),
+        ];
+        for (invocation, expected) in cases {
+            assert_eq!(
+                preprocess(&format!("{src}{invocation}"))?,
+                expected,
+                "{invocation}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn macro_arguments() -> Result<(), PreprocessError> {
+        let src = "#define CALL(f, ...) f(__VA_ARGS__)
+#define ID(x) x
+#define TWICE(x) x x
+CALL(g, (1, 2), [3, 4]) ID() TWICE(ID
+(a)) ID ;";
+        assert_eq!(preprocess(src)?, "g((1, 2), [3, 4]) a a ID ;");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_macros() {
+        let cases = [
+            (
+                "#define f(x x",
+                PreprocessError::ExpectedParameterSeparator(Span::new(12, 13)),
+            ),
+            (
+                "#define f(x,",
+                PreprocessError::ExpectedParameterSeparator(Span::new(11, 12)),
+            ),
+            (
+                "#define f(1)",
+                PreprocessError::ExpectedParameter(Span::new(10, 11)),
+            ),
+            (
+                "#define f(x, x)",
+                PreprocessError::DuplicateParameter(String::from("x"), Span::new(13, 14)),
+            ),
+            (
+                "#define f(x) #y",
+                PreprocessError::StringifyNonParameter(Span::new(13, 14)),
+            ),
+            (
+                "#define f ## x",
+                PreprocessError::PasteAtEdge(Span::new(10, 12)),
+            ),
+            (
+                "#define f(x) __VA_ARGS__",
+                PreprocessError::VariadicOnly(String::from("__VA_ARGS__"), Span::new(13, 24)),
+            ),
+            (
+                "#define f(...) __VA_OPT__(",
+                PreprocessError::UnterminatedVaOpt(Span::new(15, 25)),
+            ),
+            (
+                "#define f(...) __VA_OPT__(__VA_OPT__())",
+                PreprocessError::NestedVaOpt(Span::new(26, 36)),
+            ),
+            (
+                "#define f(x) x\nf(1",
+                PreprocessError::UnterminatedMacroCall(String::from("f"), Span::new(15, 16)),
+            ),
+            (
+                "#define f(x, y) x\nf(1)",
+                PreprocessError::MacroArgumentCount(String::from("f"), 2, 1, Span::new(18, 22)),
+            ),
+            (
+                "#define f(x) x\nf(1, 2)",
+                PreprocessError::MacroArgumentCount(String::from("f"), 1, 2, Span::new(15, 22)),
+            ),
+            (
+                "#define cat(x, y) x ## y\ncat(+, /)",
+                PreprocessError::InvalidPaste(
+                    String::from("+"),
+                    String::from("/"),
+                    Span::new(20, 22),
+                ),
+            ),
+            (
+                "#define str(x) #x\nstr(\\)",
+                PreprocessError::InvalidStringify(Span::new(15, 17)),
+            ),
+        ];
+        for (src, expected) in cases {
+            let err = preprocess(src).unwrap_err();
+            assert_eq!(err.to_string(), expected.to_string(), "{src}");
+            assert_eq!(err.span(), expected.span(), "{src}");
+        }
+    }
+
+    #[test]
+    fn stray_characters_are_preprocessing_tokens() {
+        assert_eq!(preprocess("#define S(x) #x\nS(@ $)").unwrap(), "\"@ $\"");
+        assert!(matches!(
+            preprocess_tokens("@")[0].clone().into_token(),
+            Err(LexerError {
+                kind: LexerErrorKind::UnexpectedCharacter('@'),
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn conditional_groups() -> Result<(), PreprocessError> {
+        let src = "#define A 2
+#if A == 1
+one
+#elif A == 2
+two
+# if 0
+#  bogus directive ' unterminated
+# elif 1
+inner
+# else
+#  error not reached
+# endif
+#else
+#error not reached either
+#endif
+#ifdef A
+a
+#endif
+#ifndef A
+#elifdef B
+#elifndef B
+not_b
+#else
+#endif";
+        assert_eq!(preprocess(src)?, "two inner a not_b");
+        Ok(())
+    }
+
+    #[test]
+    fn if_expressions() -> Result<(), PreprocessError> {
+        let cases = [
+            "1 + 2 * 3 == 7 && (1 + 2) * 3 == 9",
+            "-1 < 0 && !(-1 < 0u)",
+            "0x7fffffffffffffff + 1 < 0",
+            "18446744073709551615 == -1 && 0xffffffffffffffff > 0",
+            "~0 == -1 && ~0u == 18446744073709551615u",
+            "-1 >> 63 == -1 && 1 << 63 < 0 && 1 << 64 == 0 && 4 >> -1 == 8",
+            "0 && 1 / 0 || 1 || 1 % 0",
+            "(0 ? 1 / 0 : 2) == 2 && (1 ? -1 : 0u) > 0",
+            "'a' == 97 && '\\377' < 0 && U'\\xffffffff' > 0",
+            "017 == 15 && 0b101 == 5 && 10UL == 10ll",
+            "undefined_name == 0 && true && !false",
+            "-9 / 2 == -4 && -9 % 2 == -1 && 5 - 7 == -2 && (6 ^ 3 | 8) == 13 && (6 & 3) == 2",
+            "1 < 2 && 2 > 1 && 2 <= 2 && 2 >= 3 == 0 && 1 != 2",
+        ];
+        for expression in cases {
+            assert_eq!(
+                preprocess(&format!("#if {expression}\nyes\n#endif"))?,
+                "yes",
+                "{expression}"
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn conditional_operators() -> Result<(), PreprocessError> {
+        let dir = TempDir::new("has-include", &[("present.h", "")]);
+
+        let src = "#define D
+#define HAS_D defined(D) && defined D
+#if defined D && defined(D) && !defined E && HAS_D
+defined
+#endif
+#if __has_include(\"present.h\") && !__has_include(<rcc-absent.h>)
+has_include
+#endif
+#if defined __has_attribute && !__has_attribute(unknown_attribute) && __has_attribute(__format__)
+has_attribute
+#endif
+#ifdef __has_include
+ifdef
+#endif";
+        let out = preprocess_file(&dir.join("main.c"), src);
+        assert_eq!(out?, "defined has_include has_attribute ifdef");
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_conditionals() {
+        let cases = [
+            (
+                "#if 1\n#ifdef X\n#endif",
+                PreprocessError::UnterminatedConditional(String::from("if"), Span::new(0, 3)),
+            ),
+            (
+                "#ifndef X\n#else\n",
+                PreprocessError::UnterminatedConditional(String::from("ifndef"), Span::new(0, 7)),
+            ),
+            (
+                "#endif",
+                PreprocessError::UnmatchedConditional(String::from("endif"), Span::new(0, 6)),
+            ),
+            (
+                "#elif 1",
+                PreprocessError::UnmatchedConditional(String::from("elif"), Span::new(0, 5)),
+            ),
+            (
+                "#if 0\n#else\n#else\n#endif",
+                PreprocessError::ConditionalAfterElse(String::from("else"), Span::new(12, 17)),
+            ),
+            (
+                "#if 1\n#else\n#elif 1\n#endif",
+                PreprocessError::ConditionalAfterElse(String::from("elif"), Span::new(12, 17)),
+            ),
+            (
+                "#if\n#endif",
+                PreprocessError::MissingExpression(String::from("if"), Span::new(0, 3)),
+            ),
+            (
+                "#if 1 +\n#endif",
+                PreprocessError::ExpectedValue(Span::new(7, 8)),
+            ),
+            (
+                "#if 1 2\n#endif",
+                PreprocessError::MissingBinaryOperator(String::from("2"), Span::new(6, 7)),
+            ),
+            (
+                "#if (1\n#endif",
+                PreprocessError::MissingCloseParen(Span::new(4, 5)),
+            ),
+            (
+                "#if 1 ? 2\n#endif",
+                PreprocessError::MissingColon(Span::new(6, 7)),
+            ),
+            (
+                "#if 1 / 0\n#endif",
+                PreprocessError::DivisionByZero(Span::new(6, 7)),
+            ),
+            (
+                "#if defined(1)\n#endif",
+                PreprocessError::OperatorOperand(String::from("defined"), Span::new(12, 13)),
+            ),
+            (
+                "#if __has_include(x)\n#endif",
+                PreprocessError::OperatorOperand(String::from("__has_include"), Span::new(18, 19)),
+            ),
+            (
+                "#ifdef\n#endif",
+                PreprocessError::InvalidMacroName(Span::new(0, 6)),
+            ),
+        ];
+        for (src, expected) in cases {
+            let err = preprocess(src).unwrap_err();
+            assert_eq!(err.to_string(), expected.to_string(), "{src}");
+            assert_eq!(err.span(), expected.span(), "{src}");
+        }
+    }
+
+    #[test]
+    fn keywords_can_be_macro_names() -> Result<(), PreprocessError> {
+        assert_eq!(preprocess("#define int void\nint x;")?, "void x;");
+        Ok(())
+    }
+
+    #[test]
+    fn hash_inside_line_is_not_directive() -> Result<(), PreprocessError> {
+        let src = "#define EMPTY\nEMPTY # define X 1\nX";
+        assert_eq!(preprocess(src)?, "# define X 1 X");
+        Ok(())
+    }
+
+    #[test]
+    fn null_directive_and_pragma() -> Result<(), PreprocessError> {
+        assert_eq!(preprocess("#\n# pragma weak foo\nx")?, "x");
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostic_pragmas() -> Result<(), PreprocessError> {
+        use crate::diagnostics::{Diagnostics, WarningOptions};
+
+        let dir = TempDir::new(
+            "pragma",
+            &[(
+                "ignore.h",
+                "#pragma GCC diagnostic ignored \"-Wunused\"\nh\n",
+            )],
+        );
+        let src = "#pragma GCC diagnostic push\n\
+                   #pragma GCC diagnostic error \"-Wall\"\n\
+                   a\n\
+                   #pragma GCC diagnostic pop\n\
+                   b\n\
+                   #include \"ignore.h\"\n\
+                   #define C c\n\
+                   C\n\
+                   #pragma clang diagnostic warning \"-Wbogus\"\n\
+                   #pragma GCC diagnostic pop\n";
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        let tokens = preprocessor.run(&dir.join("main.c"), src.to_string());
+        let pragmas = preprocessor.diagnostic_pragmas().to_vec();
+        let warnings = preprocessor.into_warnings();
+        assert!(matches!(
+            warnings.as_slice(),
+            [PreprocessWarning {
+                kind: PreprocessWarningKind::UnknownWarningGroup(option),
+                ..
+            }] if option == "-Wbogus"
+        ));
+
+        let mut options = WarningOptions::default();
+        assert!(options.apply("unused-variable"));
+        let mut diagnostics = Diagnostics::new(options);
+        diagnostics.add_pragmas(&sources, &pragmas);
+        let tokens = tokens?;
+        let severities = tokens
+            .iter()
+            .map(|token| {
+                let severity =
+                    diagnostics.severity(&sources, Warning::UnusedVariable, token.token.span.start);
+                (token.spelling.as_str(), severity)
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            severities,
+            [
+                ("a", Severity::Error),
+                ("b", Severity::Warning),
+                ("h", Severity::Ignored),
+                ("c", Severity::Ignored),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn error_directive() {
+        let err = preprocess("int x;\n#error don't stop \"me\"").unwrap_err();
+        assert!(matches!(
+            &err,
+            PreprocessError::ErrorDirective(message, Span { start: 7, end: 13 })
+                if message == "don't stop \"me\""
+        ));
+    }
+
+    #[test]
+    fn warning_directive() -> Result<(), PreprocessError> {
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        preprocessor.run(Path::new("test.c"), String::from("#warning careful\n"))?;
+        assert!(matches!(
+            preprocessor.into_warnings().as_slice(),
+            [PreprocessWarning {
+                kind: PreprocessWarningKind::WarningDirective(message),
+                ..
+            }] if message == "careful"
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn invalid_directives() {
+        let cases = [
+            ("#foo", PreprocessError::InvalidDirective(Span::new(0, 4))),
+            (
+                "#define 3",
+                PreprocessError::InvalidMacroName(Span::new(8, 9)),
+            ),
+            ("#undef", PreprocessError::InvalidMacroName(Span::new(0, 6))),
+            ("#include", PreprocessError::InvalidInclude(Span::new(0, 8))),
+            (
+                "#include <rcc-missing.h>",
+                PreprocessError::IncludeNotFound(String::from("rcc-missing.h"), Span::new(9, 24)),
+            ),
+        ];
+        for (src, expected) in cases {
+            let err = preprocess(src).unwrap_err();
+            assert_eq!(err.to_string(), expected.to_string(), "{src}");
+            assert_eq!(err.span(), expected.span(), "{src}");
+        }
+    }
+
+    #[test]
+    fn includes() -> Result<(), PreprocessError> {
+        let dir = TempDir::new(
+            "include",
+            &[
+                ("sub/once.h", "#pragma once\nonce\n"),
+                ("a.h", "#include \"sub/once.h\"\n#define A a\n"),
+                ("b.h", "#define B b\n"),
+            ],
+        );
+
+        let src = "#include \"a.h\"\n#define HEADER \"b.h\"\n#include HEADER\n\
+                   #include \"sub/once.h\"\nA B";
+        let out = preprocess_file(&dir.join("main.c"), src);
+        assert_eq!(out?, "once a b");
+        Ok(())
+    }
+
+    #[test]
+    fn predefined_macros() {
+        let src = "__STDC__ __STDC_VERSION__ __x86_64__ __linux__ __CHAR_BIT__\n\
+                   #define f(x) __LINE__ x __FILE__\n\
+                   f(\n\
+                   a\n\
+                   )\n\
+                   __LINE__ __COUNTER__ __COUNTER__\n\
+                   #if defined __FILE__ && __LINE__ == 7\n\
+                   ok\n\
+                   #endif";
+        assert_eq!(
+            preprocess(src).unwrap(),
+            "1 201710L 1 1 8 3 a \"test.c\" 6 0 1 ok"
+        );
+
+        let tokens = preprocess_tokens("__DATE__ __TIME__");
+        assert_eq!(tokens[0].spelling.len(), "\"Oct 18 2026\"".len());
+        assert_eq!(tokens[1].spelling.len(), "\"14:03:59\"".len());
+    }
+
+    #[test]
+    fn predefined_type_macros() {
+        let src = "__PTRDIFF_TYPE__ __SIZEOF_PTRDIFF_T__ __WCHAR_TYPE__ __WCHAR_MAX__ \
+                   __INT_MAX__ __SCHAR_MAX__\n\
+                   #if defined __SIZE_TYPE__ || defined __LONG_MAX__ || defined __SIZEOF_LONG__\n\
+                   long\n\
+                   #endif";
+        assert_eq!(
+            preprocess(src).unwrap(),
+            "int 4 int 0x7fffffff 0x7fffffff 0x7f"
+        );
+    }
+
+    #[test]
+    fn dates_and_times() {
+        let cases = [
+            (0, "Jan  1 1970", "00:00:00"),
+            (951_782_400, "Feb 29 2000", "00:00:00"),
+            (1_792_332_239, "Oct 18 2026", "14:03:59"),
+        ];
+        for (secs, date, time) in cases {
+            let expected = (date.to_string(), time.to_string());
+            let since_epoch = std::time::Duration::from_secs(secs);
+            assert_eq!(predefined::date_time(since_epoch), expected, "{secs}");
+        }
+    }
+
+    #[test]
+    fn command_line_options() -> Result<(), PreprocessError> {
+        let dir = TempDir::new(
+            "command-line",
+            &[("inc/angled.h", "angled\n"), ("pre.h", "#define PRE pre\n")],
+        );
+
+        let mut sources = SourceMap::default();
+        let include_paths = IncludePaths {
+            angled: vec![dir.join("inc")],
+            ..IncludePaths::default()
+        };
+        let mut preprocessor = Preprocessor::new(&mut sources, include_paths);
+        preprocessor.predefine("A");
+        preprocessor.predefine("B=2");
+        preprocessor.predefine("F(x)=x+x");
+        preprocessor.predefine("C");
+        preprocessor.preundefine("C");
+        preprocessor.preinclude(&dir.join("pre.h").display().to_string());
+        let src = "#include <angled.h>\nA B F(3) C PRE";
+        let tokens = preprocessor.run(&dir.join("main.c"), src.to_string());
+
+        let spellings = tokens?.into_iter().map(|token| token.spelling);
+        assert_eq!(
+            spellings.collect::<Vec<_>>(),
+            ["angled", "1", "2", "3", "+", "3", "C", "pre"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn preprocessed_output() -> Result<(), PreprocessError> {
+        let dir = TempDir::new("output", &[("a.h", "int a;\n")]);
+
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        let src = "#define EMPTY\n#define f(x, ...) x+x\n\n#include \"a.h\"\n\
+                   \tint b = -EMPTY-1 + f(+1)\n+ f(1, x) EMPTY. 5;\n\n\n\n\n\n\n\n\n\n}";
+        let main = dir.join("main.c");
+        let tokens = preprocessor.run(&main, src.to_string());
+        let tokens = tokens?;
+
+        let macros = preprocessor.macro_definitions();
+        assert!(macros.starts_with("#define EMPTY\n#define _LP64 1\n"));
+        assert!(macros.ends_with("\n#define f(x,...) x+x\n"));
+        assert!(macros.contains("\n#define __STDC__ 1\n"));
+
+        let main = main.display();
+        let header = dir.join("a.h");
+        let header = header.display();
+        assert_eq!(
+            output::write(&sources, &tokens, true),
+            format!(
+                "# 1 \"{main}\"\n# 1 \"{header}\" 1\nint a;\n# 5 \"{main}\" 2\n \
+                 int b = - -1 + +1+ +1\n+ 1+1 . 5;\n# 16 \"{main}\"\n}}\n"
+            )
+        );
+        assert_eq!(
+            output::write(&sources, &tokens, false),
+            "int a;\n int b = - -1 + +1+ +1\n+ 1+1 . 5;\n}\n"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn dependencies() -> Result<(), PreprocessError> {
+        let dir = TempDir::new(
+            "dependencies",
+            &[
+                ("sys/sys.h", "#include \"nested.h\"\n"),
+                ("sys/nested.h", ""),
+                ("a.h", "#pragma once\n"),
+            ],
+        );
+
+        let mut sources = SourceMap::default();
+        let include_paths = IncludePaths {
+            system: vec![dir.join("sys")],
+            ..IncludePaths::default()
+        };
+        let mut preprocessor = Preprocessor::new(&mut sources, include_paths);
+        let src = "#include \"a.h\"\n#include <sys.h>\n#include \"a.h\"\n#include <sys.h>";
+        let tokens = preprocessor.run(&dir.join("main.c"), src.to_string());
+        tokens?;
+
+        let (a, sys, nested) = (
+            dir.join("a.h"),
+            dir.join("sys/sys.h"),
+            dir.join("sys/nested.h"),
+        );
+        assert_eq!(preprocessor.dependencies(false), [&a]);
+        assert_eq!(preprocessor.dependencies(true), [&a, &sys, &nested]);
+        Ok(())
+    }
+
+    #[test]
+    fn make_rules() {
+        let targets = [String::from("main.o")];
+        let paths = [
+            Path::new("main.c"),
+            Path::new("some dir/#$.h"),
+            Path::new("/usr/include/x86_64-linux-gnu/bits/types/struct_timespec.h"),
+            Path::new("b.h"),
+        ];
+        assert_eq!(
+            output::make_rule(&targets, &paths, true),
+            "main.o: main.c some\\ dir/\\#$$.h \\\n \
+             /usr/include/x86_64-linux-gnu/bits/types/struct_timespec.h b.h\n\
+             some\\ dir/\\#$$.h:\n\
+             /usr/include/x86_64-linux-gnu/bits/types/struct_timespec.h:\n\
+             b.h:\n"
+        );
+        assert_eq!(
+            output::make_rule(&[String::from("a"), String::from("b")], &paths[..1], false),
+            "a b: main.c\n"
+        );
+    }
+
+    #[test]
+    fn bundled_headers() -> Result<(), PreprocessError> {
+        let src = "#include <stddef.h>\n#include <limits.h>\n\
+                   #include <stdarg.h>\n#include <stdint.h>\n\
+                   ptrdiff_t d; NULL; INT_MAX; va_list ap; va_arg(ap, int); int32_t; INT8_MIN";
+        assert_eq!(
+            preprocess(src)?,
+            "int d; 0; 0x7fffffff; __builtin_va_list ap; __builtin_va_arg(ap, int); int; (-128)"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn include_next() -> Result<(), PreprocessError> {
+        let dir = TempDir::new(
+            "include-next",
+            &[
+                ("first/x.h", "first\n#include_next <x.h>\n"),
+                (
+                    "second/x.h",
+                    "second\n#if __has_include_next(<x.h>)\n#include_next \"x.h\"\n#endif\n",
+                ),
+                (
+                    "third/x.h",
+                    "third\n#if !__has_include_next(<x.h>)\nlast\n#endif\n",
+                ),
+                ("first/stddef.h", "shadowed\n"),
+            ],
+        );
+
+        let mut sources = SourceMap::default();
+        let include_paths = IncludePaths {
+            angled: vec![dir.join("first"), dir.join("second"), dir.join("third")],
+            ..IncludePaths::default()
+        };
+        let mut preprocessor = Preprocessor::new(&mut sources, include_paths);
+        let src = "#include <x.h>\n#include <stddef.h>\n#include_next <stddef.h>";
+        let tokens = preprocessor.run(&dir.join("main.c"), src.to_string());
+
+        let spellings = tokens?.into_iter().map(|token| token.spelling);
+        assert_eq!(
+            spellings.collect::<Vec<_>>(),
+            ["first", "second", "third", "last", "shadowed", "shadowed"]
+        );
+        let warnings = preprocessor.into_warnings();
+        assert!(matches!(
+            warnings.as_slice(),
+            [PreprocessWarning {
+                kind: PreprocessWarningKind::IncludeNextInPrimarySource,
+                ..
+            }]
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics_show_macro_expansions() {
+        let src = "#define BAD(x) x +\n#define F(x) BAD(x)\nint a = F(1);\n";
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        let tokens = preprocessor
+            .run(Path::new("test.c"), src.to_string())
+            .unwrap();
+
+        let plus = tokens.iter().find(|token| token.spelling == "+").unwrap();
+        let err = PreprocessError::ExpectedValue(plus.token.span);
+        assert_eq!(
+            crate::error::format_diagnostic(&sources, &err),
+            "test.c:1:18\n\
+             #define BAD(x) x +\n\
+             \x20                ^ expected value in expression\n\
+             test.c:2:14\n\
+             #define F(x) BAD(x)\n\
+             \x20            ^^^^^^ note: in expansion of macro 'BAD'\n\
+             test.c:3:9\n\
+             int a = F(1);\n\
+             \x20       ^^^^ note: in expansion of macro 'F'\n"
+        );
+
+        // A span from an argument to a token of the replacement list is
+        // shown as the invocation.
+        let one = tokens.iter().find(|token| token.spelling == "1").unwrap();
+        let span = Span::new(one.token.span.start, plus.token.span.end);
+        assert_eq!(sources.snippet(span), "BAD(x)");
+        assert_eq!(
+            output::trace(&sources, &tokens[3..4]),
+            "test.c:3:11: '1' in expansion of BAD at test.c:2:14, F at test.c:3:9\n"
+        );
+    }
+
+    #[test]
+    fn diagnostics_show_the_include_stack() {
+        let dir = TempDir::new(
+            "include-stack",
+            &[
+                ("a.h", "#include \"b.h\"\n"),
+                ("b.h", "int x;\n\t#error bad\n"),
+            ],
+        );
+
+        let mut sources = SourceMap::default();
+        let mut preprocessor = Preprocessor::new(&mut sources, IncludePaths::default());
+        let main = dir.join("main.c");
+        let err = preprocessor
+            .run(&main, String::from("\n#include \"a.h\"\n"))
+            .unwrap_err();
+        let out = crate::error::format_diagnostic(&sources, &err);
+
+        let name = |file: &str| dir.join(file).display().to_string();
+        let expected = format!(
+            "In file included from {}:1,\n                 from {}:2:\n{}:2:2\n",
+            name("a.h"),
+            name("main.c"),
+            name("b.h"),
+        );
+        assert!(out.starts_with(&expected), "{out}");
+    }
+}
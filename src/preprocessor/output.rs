@@ -0,0 +1,209 @@
+use crate::lexer::Lexer;
+use crate::preprocessor::{PpToken, Preprocessor};
+use crate::source_map::SourceMap;
+use crate::span::Span;
+use std::path::Path;
+
+/// The most blank lines written out to keep the line count right, beyond
+/// which a linemarker is shorter.
+const MAX_BLANK_LINES: usize = 8;
+
+/// The width that dependency rules are wrapped to.
+const MAX_RULE_COLUMNS: usize = 72;
+
+/// Whether writing `next` right after `prev` would lex differently, as
+/// `+` then `+` would give `++`, so that a space must separate them.
+fn would_paste(prev: &PpToken, next: &PpToken) -> bool {
+    let text = format!("{}{}", prev.spelling, next.spelling);
+    !matches!(
+        Lexer::new(&text).next(),
+        Some(Ok(token)) if token.span == Span::new(0, prev.spelling.len())
+    )
+}
+
+/// Writes preprocessed tokens as source text, each on the line it was
+/// read from. With `linemarkers`, lines like `# 12 "foo.h" 1` record where
+/// they came from as GCC writes them: flag 1 enters an included file and
+/// flag 2 returns to the file that included it.
+pub fn write(sources: &SourceMap, tokens: &[PpToken], linemarkers: bool) -> String {
+    let mut out = String::new();
+    let mut current = sources.main_file().map(|file| (file, 1));
+    if linemarkers && let Some((file, _)) = current {
+        out.push_str(&format!("# 1 \"{}\"\n", file.name));
+    }
+    // The last token written on the current output line, if any.
+    let mut prev: Option<&PpToken> = None;
+
+    for token in tokens {
+        let Some(file) = sources.file(token.location) else {
+            continue;
+        };
+        let (line, col) = file.line_col(token.location);
+
+        let new_line = match current {
+            Some((current_file, _)) if current_file.base != file.base => {
+                if linemarkers {
+                    let returning = sources
+                        .include_stack(current_file.base)
+                        .iter()
+                        .any(|(name, _)| *name == file.name);
+                    let flag = if returning { " 2" } else { " 1" };
+                    end_line(&mut out, &mut prev);
+                    out.push_str(&format!("# {line} \"{}\"{flag}\n", file.name));
+                }
+                true
+            }
+            Some((_, current_line)) if line > current_line => {
+                let blank_lines = line - current_line - usize::from(prev.is_some());
+                end_line(&mut out, &mut prev);
+                if linemarkers && blank_lines > MAX_BLANK_LINES {
+                    out.push_str(&format!("# {line} \"{}\"\n", file.name));
+                } else if linemarkers {
+                    out.push_str(&"\n".repeat(blank_lines));
+                }
+                true
+            }
+            Some(_) => prev.is_none(),
+            None => true,
+        };
+        current = Some((file, line));
+
+        if new_line {
+            end_line(&mut out, &mut prev);
+            // Keep the indentation of lines read from the source.
+            out.push_str(&" ".repeat(col - 1));
+        } else if let Some(prev) = prev
+            && (token.space_before || would_paste(prev, token))
+        {
+            out.push(' ');
+        }
+        out.push_str(&token.spelling);
+        prev = Some(token);
+    }
+    end_line(&mut out, &mut prev);
+
+    out
+}
+
+/// Ends the current output line, if anything was written on it.
+fn end_line(out: &mut String, prev: &mut Option<&PpToken>) {
+    if prev.take().is_some() {
+        out.push('\n');
+    }
+}
+
+/// Lists the tokens produced by macro expansions, one per line, with the
+/// chain of expansions each came from, innermost first, as
+/// `m.h:1:16: '(' in expansion of MAX at main.c:4:9, F at main.c:5:1`.
+/// The first location is where the token was written.
+pub fn trace(sources: &SourceMap, tokens: &[PpToken]) -> String {
+    let location = |pos| sources.location(pos).unwrap_or_default();
+    let mut out = String::new();
+    for token in tokens {
+        let expansions = sources.expansions(token.token.span.start);
+        if expansions.is_empty() {
+            continue;
+        }
+        let chain = expansions
+            .iter()
+            .map(|expansion| format!("{} at {}", expansion.name, location(expansion.site.start)))
+            .collect::<Vec<_>>();
+        out.push_str(&format!(
+            "{}: '{}' in expansion of {}\n",
+            location(token.token.span.start),
+            token.spelling,
+            chain.join(", ")
+        ));
+    }
+
+    out
+}
+
+/// Quotes a file name for make, in which spaces separate names, `#`
+/// starts a comment and `$` a variable.
+fn make_quote(path: &Path) -> String {
+    let mut quoted = String::new();
+    for ch in path.display().to_string().chars() {
+        match ch {
+            ' ' | '#' => quoted.push('\\'),
+            '$' => quoted.push('$'),
+            _ => {}
+        }
+        quoted.push(ch);
+    }
+
+    quoted
+}
+
+/// Writes a make rule for `targets` on `prerequisites`, wrapped as GCC
+/// wraps it. With `phony`, each prerequisite but the first, the source
+/// file, also gets an empty rule, so that make does not fail once the
+/// header is deleted.
+pub fn make_rule(targets: &[String], prerequisites: &[&Path], phony: bool) -> String {
+    let mut out = String::new();
+    let mut col = 0;
+    let mut write_name = |out: &mut String, name: &str| {
+        if col > 0 {
+            if col + name.len() > MAX_RULE_COLUMNS {
+                out.push_str(" \\\n");
+                col = 0;
+            }
+            out.push(' ');
+            col += 1;
+        }
+        out.push_str(name);
+        col += name.len();
+    };
+
+    for target in targets {
+        write_name(&mut out, target);
+    }
+    out.push(':');
+    for path in prerequisites {
+        write_name(&mut out, &make_quote(path));
+    }
+    out.push('\n');
+
+    if phony {
+        for path in prerequisites.iter().skip(1) {
+            out.push_str(&format!("{}:\n", make_quote(path)));
+        }
+    }
+
+    out
+}
+
+impl<'a> Preprocessor<'a> {
+    /// The macros defined at the end of preprocessing, as the `#define`
+    /// lines that would define them, sorted by name.
+    pub fn macro_definitions(&self) -> String {
+        let mut names = self.macros.keys().collect::<Vec<_>>();
+        names.sort();
+
+        let mut out = String::new();
+        for name in names {
+            let definition = &self.macros[name];
+            out.push_str("#define ");
+            out.push_str(name);
+            if let Some(params) = &definition.params {
+                let params = params
+                    .iter()
+                    .map(|param| match param.as_str() {
+                        "__VA_ARGS__" if definition.variadic => "...",
+                        param => param,
+                    })
+                    .collect::<Vec<_>>();
+                out.push_str(&format!("({})", params.join(",")));
+            }
+            for (i, token) in definition.body.iter().enumerate() {
+                if i == 0 || token.space_before {
+                    out.push(' ');
+                }
+                out.push_str(&token.spelling);
+            }
+            out.push('\n');
+        }
+
+        out
+    }
+}
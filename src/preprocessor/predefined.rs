@@ -0,0 +1,161 @@
+use crate::preprocessor::{PpToken, Preprocessor};
+use crate::token::{Const, Token, TokenKind};
+use crate::types::{Type, TypeKind};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// The macros defined before any file is read, for the x86-64 Linux target
+/// with the LP64 type sizes. `__GNUC__` is left out on purpose: it makes
+/// system headers use extensions the parser does not know. The macros that
+/// describe types are made by [`type_macros`].
+const PREDEFINED: &[(&str, &str)] = &[
+    ("__STDC__", "1"),
+    ("__STDC_VERSION__", "201710L"),
+    ("__STDC_HOSTED__", "1"),
+    ("__STDC_UTF_16__", "1"),
+    ("__STDC_UTF_32__", "1"),
+    ("__x86_64__", "1"),
+    ("__x86_64", "1"),
+    ("__amd64__", "1"),
+    ("__amd64", "1"),
+    ("__linux__", "1"),
+    ("__linux", "1"),
+    ("__gnu_linux__", "1"),
+    ("__unix__", "1"),
+    ("__unix", "1"),
+    ("__ELF__", "1"),
+    ("__LP64__", "1"),
+    ("_LP64", "1"),
+    ("__ORDER_LITTLE_ENDIAN__", "1234"),
+    ("__ORDER_BIG_ENDIAN__", "4321"),
+    ("__ORDER_PDP_ENDIAN__", "3412"),
+    ("__BYTE_ORDER__", "__ORDER_LITTLE_ENDIAN__"),
+    ("__CHAR_BIT__", "8"),
+];
+
+/// The macros that describe the types, made from the types the compiler
+/// gives, so that they agree with it. Those for types that do not exist
+/// yet, such as `size_t` and `long`, are left out.
+fn type_macros() -> Vec<(&'static str, String)> {
+    let (int, ptrdiff, wchar) = (Type::new(TypeKind::Int), Type::ptrdiff(), Type::wchar());
+    let pointer = Type::pointer_to(Type::new(TypeKind::Void));
+    // The largest value of a signed integer type of `size` bytes.
+    let max = |size: i64| format!("{:#x}", (1_i64 << (8 * size - 1)) - 1);
+    vec![
+        ("__SIZEOF_INT__", int.size().to_string()),
+        ("__SIZEOF_POINTER__", pointer.size().to_string()),
+        ("__SIZEOF_PTRDIFF_T__", ptrdiff.size().to_string()),
+        ("__SIZEOF_WCHAR_T__", wchar.size().to_string()),
+        ("__PTRDIFF_TYPE__", ptrdiff.to_string()),
+        ("__WCHAR_TYPE__", wchar.to_string()),
+        ("__SCHAR_MAX__", max(Type::new(TypeKind::Char).size())),
+        ("__INT_MAX__", max(int.size())),
+        ("__PTRDIFF_MAX__", max(ptrdiff.size())),
+        ("__WCHAR_MAX__", max(wchar.size())),
+    ]
+}
+
+/// The macros whose value depends on where they are expanded.
+pub(super) const DYNAMIC: &[&str] = &["__FILE__", "__LINE__", "__COUNTER__"];
+
+const MONTHS: [&str; 12] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+
+/// The `#define` lines for the predefined macros, read as the file
+/// `<built-in>`.
+pub(super) fn builtins() -> String {
+    let (date, time) = date_time(now());
+    let mut src = String::new();
+    for (name, value) in PREDEFINED {
+        src.push_str(&format!("#define {name} {value}\n"));
+    }
+    for (name, value) in type_macros() {
+        src.push_str(&format!("#define {name} {value}\n"));
+    }
+    src.push_str(&format!("#define __DATE__ \"{date}\"\n"));
+    src.push_str(&format!("#define __TIME__ \"{time}\"\n"));
+
+    src
+}
+
+/// The time of translation. As in GCC, `SOURCE_DATE_EPOCH` overrides it
+/// for reproducible builds.
+fn now() -> Duration {
+    std::env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+        })
+}
+
+/// Formats a time since the epoch, in UTC, as `__DATE__` (`Oct 18 2026`)
+/// and `__TIME__` (`14:03:59`) spell it.
+pub(super) fn date_time(since_epoch: Duration) -> (String, String) {
+    let secs = since_epoch.as_secs();
+    let (days, secs) = (secs / 86400, secs % 86400);
+    let time = format!("{:02}:{:02}:{:02}", secs / 3600, secs / 60 % 60, secs % 60);
+
+    // The proleptic Gregorian date, counted in 400-year eras from
+    // 0000-03-01 so that leap days end each year.
+    let days = days + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month + 2) / 5 + 1;
+    let month = if month < 10 { month + 2 } else { month - 10 };
+    let year = era * 400 + year_of_era + u64::from(month < 2);
+    let date = format!("{} {day:>2} {year}", MONTHS[month as usize]);
+
+    (date, time)
+}
+
+impl<'a> Preprocessor<'a> {
+    /// The expansion of `__FILE__`, `__LINE__` or `__COUNTER__`. The file
+    /// and line are those of the last token read from the source, so that
+    /// inside a macro they give where it was invoked.
+    pub(super) fn expand_dynamic(&mut self, name: &str, token: &PpToken) -> PpToken {
+        let file = self.sources.file(self.position);
+        let (kind, spelling) = match name {
+            "__FILE__" => {
+                let name = file.map_or("", |file| file.name.as_str());
+                let spelling = format!("\"{}\"", name.replace('\\', "\\\\").replace('"', "\\\""));
+                (TokenKind::StringLiteral(name.as_bytes().to_vec()), spelling)
+            }
+            "__LINE__" => {
+                let line = file.map_or(0, |file| file.line_col(self.position).0);
+                (
+                    TokenKind::Constant(Const::Int(line as i64)),
+                    line.to_string(),
+                )
+            }
+            _ => {
+                let counter = self.counter;
+                self.counter += 1;
+                (
+                    TokenKind::Constant(Const::Int(counter as i64)),
+                    counter.to_string(),
+                )
+            }
+        };
+
+        let mut expanded = PpToken::new(
+            Token {
+                kind,
+                span: token.token.span,
+            },
+            spelling,
+            false,
+            token.space_before,
+        );
+        expanded.hide_set = token.hide_set.clone();
+
+        expanded
+    }
+}
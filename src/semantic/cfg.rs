@@ -0,0 +1,234 @@
+use crate::ast::visit::{self, Visit};
+use crate::ast::{BinaryOp, Block, BlockItem, Declaration, Exp, ExpKind, Statement, StatementKind};
+use crate::span::{HasSpan, Span};
+use std::collections::HashMap;
+
+/// Something that happens as control passes through a basic block: to a
+/// local variable, by its index in `Cfg::variables`, or to control itself.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Event {
+    /// The block item at the span, which does something, begins.
+    Code(Span),
+    /// Control leaves the function by a `return`.
+    Return,
+    /// The variable's declaration is reached, which gives it an
+    /// indeterminate value.
+    Declare(usize),
+    Assign(usize),
+    /// The variable's value is read by the expression at the span.
+    Use(usize, Span),
+    /// The variable's address is taken, after which it may be written
+    /// through a pointer at any point.
+    Escape(usize),
+}
+
+#[derive(Debug, Default)]
+pub(super) struct BasicBlock {
+    pub(super) events: Vec<Event>,
+    pub(super) successors: Vec<usize>,
+}
+
+/// A local variable whose value the graph follows.
+#[derive(Debug)]
+pub(super) struct Variable {
+    /// Its unique name, as resolution renamed it.
+    pub(super) name: String,
+    /// Its declaration.
+    pub(super) span: Span,
+}
+
+/// The control-flow graph of a function body, whose blocks record what
+/// happens to the body's scalar local variables in evaluation order. The
+/// entry is block 0; a block without successors leaves the function,
+/// by a `return` or, for `end`, at the end of the body. Branches come
+/// from `&&`, `||` and `?:`, which evaluate some operands only on some
+/// paths. There are no loops yet, but the checks over the graph allow for
+/// the back edges they will add.
+#[derive(Debug)]
+pub(super) struct Cfg {
+    pub(super) blocks: Vec<BasicBlock>,
+    pub(super) variables: Vec<Variable>,
+    /// The block that control is in at the end of the body.
+    pub(super) end: usize,
+}
+
+impl Cfg {
+    pub(super) fn build(body: &Block) -> Self {
+        let mut builder = Builder {
+            blocks: vec![BasicBlock::default()],
+            current: 0,
+            variables: Vec::new(),
+            indices: HashMap::new(),
+        };
+        builder.visit_block(body);
+
+        Cfg {
+            blocks: builder.blocks,
+            variables: builder.variables,
+            end: builder.current,
+        }
+    }
+
+    /// Which blocks control can reach from the entry.
+    pub(super) fn reachable(&self) -> Vec<bool> {
+        let mut reachable = vec![false; self.blocks.len()];
+        let mut stack = vec![0];
+        while let Some(i) = stack.pop() {
+            if !std::mem::replace(&mut reachable[i], true) {
+                stack.extend(&self.blocks[i].successors);
+            }
+        }
+        reachable
+    }
+
+    /// The predecessors of each block.
+    pub(super) fn predecessors(&self) -> Vec<Vec<usize>> {
+        let mut predecessors = vec![Vec::new(); self.blocks.len()];
+        for (i, block) in self.blocks.iter().enumerate() {
+            for &successor in &block.successors {
+                predecessors[successor].push(i);
+            }
+        }
+        predecessors
+    }
+}
+
+struct Builder {
+    blocks: Vec<BasicBlock>,
+    /// The block that events are added to.
+    current: usize,
+    variables: Vec<Variable>,
+    /// The index of each followed variable, by unique name.
+    indices: HashMap<String, usize>,
+}
+
+impl Builder {
+    fn new_block(&mut self) -> usize {
+        self.blocks.push(BasicBlock::default());
+        self.blocks.len() - 1
+    }
+
+    fn edge(&mut self, from: usize, to: usize) {
+        self.blocks[from].successors.push(to);
+    }
+
+    fn event(&mut self, event: Event) {
+        self.blocks[self.current].events.push(event);
+    }
+
+    /// The index of a variable the graph follows, if `exp` names one.
+    fn variable(&self, exp: &Exp) -> Option<usize> {
+        match &exp.kind {
+            ExpKind::Var(name) => self.indices.get(name).copied(),
+            _ => None,
+        }
+    }
+
+    /// Evaluates each of `branches` on its own path from the current
+    /// block, continuing in a block where the paths join.
+    fn branch(&mut self, branches: &[&Exp]) {
+        let from = self.current;
+        let join = self.new_block();
+        for exp in branches {
+            let block = self.new_block();
+            self.edge(from, block);
+            self.current = block;
+            self.visit_exp(exp);
+            self.edge(self.current, join);
+        }
+        self.current = join;
+    }
+}
+
+/// Whether an item does anything when control reaches it. A declaration
+/// without an initializer and a null statement do not.
+fn is_code(item: &BlockItem) -> bool {
+    match item {
+        BlockItem::Declaration(Declaration::Variable { init, .. }) => init.is_some(),
+        BlockItem::Declaration(Declaration::Function(_)) => false,
+        BlockItem::Statement(statement) => !matches!(statement.kind, StatementKind::Null),
+    }
+}
+
+impl Visit for Builder {
+    fn visit_block_item(&mut self, item: &BlockItem) {
+        if is_code(item) {
+            self.event(Event::Code(*item.span()));
+        }
+        visit::walk_block_item(self, item);
+    }
+
+    fn visit_declaration(&mut self, declaration: &Declaration) {
+        let Declaration::Variable {
+            name,
+            ty,
+            init,
+            span,
+        } = declaration
+        else {
+            return;
+        };
+        // Arrays are not followed: their elements are written in place.
+        if !ty.is_scalar() {
+            return visit::walk_declaration(self, declaration);
+        }
+
+        let index = self.variables.len();
+        self.variables.push(Variable {
+            name: name.clone(),
+            span: *span,
+        });
+        self.indices.insert(name.clone(), index);
+        self.event(Event::Declare(index));
+        if let Some(init) = init {
+            self.visit_exp(init);
+            self.event(Event::Assign(index));
+        }
+    }
+
+    fn visit_statement(&mut self, statement: &Statement) {
+        visit::walk_statement(self, statement);
+        // What follows a `return` is reached only by other paths, of which
+        // there are none yet.
+        if let StatementKind::Return(_) = statement.kind {
+            self.event(Event::Return);
+            self.current = self.new_block();
+        }
+    }
+
+    fn visit_exp(&mut self, exp: &Exp) {
+        match &exp.kind {
+            ExpKind::Var(_) => {
+                if let Some(index) = self.variable(exp) {
+                    self.event(Event::Use(index, exp.span));
+                }
+            }
+            ExpKind::AddressOf(inner) => match self.variable(inner) {
+                Some(index) => self.event(Event::Escape(index)),
+                None => self.visit_exp(inner),
+            },
+            // The value is stored once both operands are evaluated.
+            ExpKind::Assignment(lhs, rhs) => {
+                self.visit_exp(rhs);
+                match self.variable(lhs) {
+                    Some(index) => self.event(Event::Assign(index)),
+                    None if matches!(lhs.kind, ExpKind::Var(_)) => {}
+                    None => visit::walk_exp(self, lhs),
+                }
+            }
+            // The right operand is evaluated only when the left one does
+            // not decide the result.
+            ExpKind::Binary(BinaryOp::And | BinaryOp::Or, lhs, rhs) => {
+                self.visit_exp(lhs);
+                let from = self.current;
+                self.branch(&[rhs]);
+                self.edge(from, self.current);
+            }
+            ExpKind::Conditional(condition, then, otherwise) => {
+                self.visit_exp(condition);
+                self.branch(&[then, otherwise]);
+            }
+            _ => visit::walk_exp(self, exp),
+        }
+    }
+}
@@ -0,0 +1,90 @@
+use crate::ast::visit::Visit;
+use crate::ast::{Block, Function, Program};
+use crate::error::{SemanticWarning, SemanticWarningKind};
+use crate::semantic::cfg::{Cfg, Event};
+use crate::span::Span;
+
+/// Follows the flow of control through the control-flow graph of each
+/// function body, warning about code that can never run and about non-void
+/// functions that can reach the end of their body without returning a
+/// value.
+#[derive(Default)]
+pub(super) struct FlowChecker {
+    warnings: Vec<SemanticWarning>,
+}
+
+impl FlowChecker {
+    pub(super) fn check(mut self, program: &Program) -> Vec<SemanticWarning> {
+        self.visit_program(program);
+        self.warnings
+    }
+
+    /// The warnings about the body whose graph is `cfg`. Reaching its end
+    /// is warned about at `end`, if it needs a value returned.
+    pub(super) fn check_body(cfg: &Cfg, end: Option<Span>) -> Vec<SemanticWarning> {
+        let reachable = cfg.reachable();
+        let mut warnings = Vec::new();
+
+        // Each run of unreachable blocks, one reached from another, is
+        // warned about once, at the code in it that comes first.
+        let mut seen = reachable.clone();
+        for start in 0..cfg.blocks.len() {
+            let mut first: Option<Span> = None;
+            let mut stack = vec![start];
+            while let Some(i) = stack.pop() {
+                if std::mem::replace(&mut seen[i], true) {
+                    continue;
+                }
+                for event in &cfg.blocks[i].events {
+                    if let Event::Code(span) = *event
+                        && first.is_none_or(|first| span.start < first.start)
+                    {
+                        first = Some(span);
+                    }
+                }
+                stack.extend(&cfg.blocks[i].successors);
+            }
+            if let Some(span) = first {
+                warnings.push(SemanticWarning {
+                    kind: SemanticWarningKind::UnreachableCode,
+                    span,
+                });
+            }
+        }
+
+        if let Some(span) = end
+            && reachable[cfg.end]
+        {
+            let returns = cfg
+                .blocks
+                .iter()
+                .zip(&reachable)
+                .any(|(block, &reachable)| reachable && block.events.contains(&Event::Return));
+            warnings.push(SemanticWarning {
+                kind: SemanticWarningKind::MissingReturn(returns),
+                span,
+            });
+        }
+
+        warnings
+    }
+}
+
+impl Visit for FlowChecker {
+    fn visit_function(&mut self, function: &Function) {
+        let Function::Function {
+            return_type,
+            name,
+            body: Some(body @ Block(_, span)),
+            ..
+        } = function
+        else {
+            return;
+        };
+
+        // Reaching the end of `main` returns 0 (C17 5.1.2.2.3).
+        let end = (!return_type.is_void() && name != "main").then(|| Span::single(span.end - 1));
+        let warnings = Self::check_body(&Cfg::build(body), end);
+        self.warnings.extend(warnings);
+    }
+}
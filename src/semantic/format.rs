@@ -0,0 +1,364 @@
+use crate::ast::{Archetype, Exp, ExpKind, Format};
+use crate::error::{FormatWarning, SemanticWarning, SemanticWarningKind};
+use crate::types::{Type, TypeKind};
+use std::ops::Range;
+
+/// The format attribute of a formatted input or output function of the C
+/// library, which its calls are checked by whether or not it has one.
+pub(super) fn standard(name: &str) -> Option<Format> {
+    let (archetype, string, first) = match name {
+        "printf" => (Archetype::Printf, 1, 2),
+        "fprintf" | "sprintf" | "dprintf" => (Archetype::Printf, 2, 3),
+        "snprintf" => (Archetype::Printf, 3, 4),
+        "vprintf" => (Archetype::Printf, 1, 0),
+        "vfprintf" | "vsprintf" | "vdprintf" => (Archetype::Printf, 2, 0),
+        "vsnprintf" => (Archetype::Printf, 3, 0),
+        "scanf" => (Archetype::Scanf, 1, 2),
+        "fscanf" | "sscanf" => (Archetype::Scanf, 2, 3),
+        "vscanf" => (Archetype::Scanf, 1, 0),
+        "vfscanf" | "vsscanf" => (Archetype::Scanf, 2, 0),
+        _ => return None,
+    };
+
+    Some(Format {
+        archetype,
+        string,
+        first,
+    })
+}
+
+/// The arguments a type of argument can be.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Takes {
+    /// Any integer, which is passed promoted to `int`.
+    Int,
+    CharPointer,
+    IntPointer,
+    /// Any pointer, as `%p` prints.
+    Pointer,
+    VoidPointerPointer,
+    /// A type there is no way to write yet, such as `long` or `double`,
+    /// which no argument has.
+    Unsupported,
+}
+
+impl Takes {
+    fn accepts(self, ty: &Type) -> bool {
+        let pointee = match &ty.kind {
+            TypeKind::Pointer(pointee) => Some(&pointee.kind),
+            _ => None,
+        };
+        match self {
+            Takes::Int => ty.is_integer(),
+            Takes::CharPointer => pointee == Some(&TypeKind::Char),
+            Takes::IntPointer => pointee == Some(&TypeKind::Int),
+            Takes::Pointer => ty.is_pointer(),
+            Takes::VoidPointerPointer => matches!(
+                pointee,
+                Some(TypeKind::Pointer(pointee)) if pointee.is_void()
+            ),
+            Takes::Unsupported => false,
+        }
+    }
+}
+
+/// What a conversion specification does with the arguments.
+enum Argument {
+    /// It takes one, named as its type is written.
+    Takes(String, Takes),
+    /// It takes none, as `%%` does.
+    None,
+    InvalidLength,
+    InvalidConversion,
+}
+
+/// The integer type a length modifier selects.
+fn integer_type(length: &str, signed: bool) -> Option<&'static str> {
+    Some(match (length, signed) {
+        ("", true) => "int",
+        ("", false) => "unsigned int",
+        ("hh", true) => "char",
+        ("hh", false) => "unsigned char",
+        ("h", true) => "short",
+        ("h", false) => "unsigned short",
+        ("l", true) => "long",
+        ("l", false) => "unsigned long",
+        ("ll", true) => "long long",
+        ("ll", false) => "unsigned long long",
+        ("j", true) => "intmax_t",
+        ("j", false) => "uintmax_t",
+        ("z", _) => "size_t",
+        ("t", _) => "ptrdiff_t",
+        _ => return None,
+    })
+}
+
+/// An integer argument of the type `length` selects, passed by value or,
+/// to be stored to, by a pointer to it.
+fn integer(length: &str, signed: bool, pointer: bool) -> Argument {
+    let Some(name) = integer_type(length, signed) else {
+        return Argument::InvalidLength;
+    };
+    match (pointer, length) {
+        (false, "" | "hh" | "h") => Argument::Takes(name.to_owned(), Takes::Int),
+        (true, "") => Argument::Takes(format!("{name} *"), Takes::IntPointer),
+        (true, "hh") => Argument::Takes(format!("{name} *"), Takes::CharPointer),
+        (false, _) => Argument::Takes(name.to_owned(), Takes::Unsupported),
+        (true, _) => Argument::Takes(format!("{name} *"), Takes::Unsupported),
+    }
+}
+
+/// A floating-point argument of the type `length` selects; `printf` takes
+/// a `float` promoted to `double`.
+fn floating(archetype: Archetype, length: &str) -> Argument {
+    let name = match (archetype, length) {
+        (Archetype::Printf, "" | "l") => "double",
+        (Archetype::Scanf, "") => "float *",
+        (Archetype::Scanf, "l") => "double *",
+        (Archetype::Printf, "L") => "long double",
+        (Archetype::Scanf, "L") => "long double *",
+        _ => return Argument::InvalidLength,
+    };
+    Argument::Takes(name.to_owned(), Takes::Unsupported)
+}
+
+/// What the conversion specification with the length modifier `length`
+/// and the conversion specifier `conversion` takes (C17 7.21.6.1, 7.21.6.2).
+fn argument(archetype: Archetype, length: &str, conversion: u8) -> Argument {
+    let scanf = archetype == Archetype::Scanf;
+    let takes = |name: &str, kind| Argument::Takes(name.to_owned(), kind);
+    match (conversion, length) {
+        (b'%', "") => Argument::None,
+        (b'd' | b'i', _) => integer(length, true, scanf),
+        (b'o' | b'u' | b'x' | b'X', _) => integer(length, false, scanf),
+        (b'n', _) => integer(length, true, true),
+        (b'e' | b'E' | b'f' | b'F' | b'g' | b'G' | b'a' | b'A', _) => floating(archetype, length),
+        (b'c', "") if !scanf => takes("int", Takes::Int),
+        (b'c' | b's', "") => takes("char *", Takes::CharPointer),
+        (b'[', "") if scanf => takes("char *", Takes::CharPointer),
+        (b'c' | b's', "l") if !scanf => takes(
+            if conversion == b'c' {
+                "wint_t"
+            } else {
+                "wchar_t *"
+            },
+            Takes::Unsupported,
+        ),
+        (b'c' | b's' | b'[', "l") if scanf => takes("wchar_t *", Takes::Unsupported),
+        (b'p', "") if scanf => takes("void **", Takes::VoidPointerPointer),
+        (b'p', "") => takes("void *", Takes::Pointer),
+        (b'%' | b'c' | b's' | b'p', _) => Argument::InvalidLength,
+        (b'[', _) if scanf => Argument::InvalidLength,
+        _ => Argument::InvalidConversion,
+    }
+}
+
+/// Checks a call to a function with the format attribute `format` whose
+/// arguments, type checked but not yet converted, are `args`. Only a
+/// string literal can be checked; the first argument the format string
+/// does not describe is warned about, but only if it was all understood.
+pub(super) fn check(format: Format, args: &[Exp]) -> Vec<SemanticWarning> {
+    let Some(literal) = format.string.checked_sub(1).and_then(|i| args.get(i)) else {
+        return Vec::new();
+    };
+    let ExpKind::String(bytes) = &literal.kind else {
+        return Vec::new();
+    };
+    // The function stops reading at a null character.
+    let bytes = match bytes.iter().position(|&byte| byte == 0) {
+        Some(end) => &bytes[..end],
+        None => bytes,
+    };
+    let data = match format.first {
+        0 => None,
+        first => Some(args.get(first - 1..).unwrap_or_default()),
+    };
+
+    let mut checker = Checker {
+        archetype: format.archetype,
+        bytes,
+        pos: 0,
+        literal,
+        data,
+        next: 0,
+        warnings: Vec::new(),
+    };
+    if checker.check()
+        && let Some(data) = checker.data
+        && let Some(extra) = data.get(checker.next)
+    {
+        checker.warnings.push(SemanticWarning {
+            kind: SemanticWarningKind::FormatExtraArgument,
+            span: extra.span,
+        });
+    }
+    checker.warnings
+}
+
+/// Reads a format string, matching its conversion specifications to the
+/// data arguments.
+struct Checker<'a> {
+    archetype: Archetype,
+    bytes: &'a [u8],
+    pos: usize,
+    literal: &'a Exp,
+    /// The arguments the format string describes, unless they are passed
+    /// as a `va_list`, which cannot be checked.
+    data: Option<&'a [Exp]>,
+    /// The next of `data` to be described.
+    next: usize,
+    warnings: Vec<SemanticWarning>,
+}
+
+impl<'a> Checker<'a> {
+    /// Checks every conversion specification, unless one is not
+    /// understood, which returns false.
+    fn check(&mut self) -> bool {
+        while self.pos < self.bytes.len() {
+            let start = self.pos;
+            self.pos += 1;
+            if self.bytes[start] != b'%' {
+                continue;
+            }
+            if !self.check_specification(start) {
+                return false;
+            }
+        }
+        true
+    }
+
+    /// Checks the conversion specification whose `%` is at `start`.
+    fn check_specification(&mut self, start: usize) -> bool {
+        let scanf = self.archetype == Archetype::Scanf;
+        let suppressed = scanf && self.eat(b"*");
+        if !scanf {
+            while self.eat(b"-+ #0") {}
+        }
+        // Arguments given by position, as in `%1$d`, are not checked.
+        let digits = self.digits();
+        if self.bytes.get(self.pos) == Some(&b'$') {
+            return false;
+        }
+        if !scanf && digits == 0 && self.bytes.get(self.pos) == Some(&b'*') {
+            self.star(FormatWarning::FieldWidthType);
+        }
+        if !scanf && self.eat(b".") && self.bytes.get(self.pos) == Some(&b'*') {
+            self.star(FormatWarning::PrecisionType);
+        } else {
+            self.digits();
+        }
+
+        let length_start = self.pos;
+        for length in ["hh", "h", "ll", "l", "j", "z", "t", "L"] {
+            if self.bytes[self.pos..].starts_with(length.as_bytes()) {
+                self.pos += length.len();
+                break;
+            }
+        }
+        let length = String::from_utf8_lossy(&self.bytes[length_start..self.pos]).into_owned();
+        let Some(&conversion) = self.bytes.get(self.pos) else {
+            self.warn(FormatWarning::Incomplete, start..self.pos);
+            return false;
+        };
+        self.pos += 1;
+        if scanf && conversion == b'[' && !self.scanset() {
+            self.warn(FormatWarning::Incomplete, start..self.pos);
+            return false;
+        }
+
+        let range = start..self.pos;
+        match argument(self.archetype, &length, conversion) {
+            Argument::None => true,
+            Argument::Takes(..) if suppressed => true,
+            Argument::Takes(name, takes) => {
+                if let Some(arg) = self.take(range.clone()) {
+                    let ty = arg.ty().decay();
+                    if !takes.accepts(&ty) {
+                        self.warn(FormatWarning::ArgumentType(name, ty), range);
+                    }
+                }
+                true
+            }
+            Argument::InvalidLength => {
+                let warning = FormatWarning::InvalidLength(length, char::from(conversion));
+                self.warn(warning, length_start..range.end);
+                if !suppressed {
+                    self.take(range);
+                }
+                true
+            }
+            Argument::InvalidConversion => {
+                let specifier = [conversion].escape_ascii().to_string();
+                self.warn(FormatWarning::InvalidSpecifier(specifier), range);
+                false
+            }
+        }
+    }
+
+    /// Consumes one of `bytes` if it is next.
+    fn eat(&mut self, bytes: &[u8]) -> bool {
+        match self.bytes.get(self.pos) {
+            Some(byte) if bytes.contains(byte) => {
+                self.pos += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Consumes decimal digits, returning how many.
+    fn digits(&mut self) -> usize {
+        let start = self.pos;
+        while self.eat(b"0123456789") {}
+        self.pos - start
+    }
+
+    /// Consumes the `*` of a field width or precision, which takes an
+    /// `int` argument.
+    fn star(&mut self, warning: fn(Type) -> FormatWarning) {
+        let range = self.pos..self.pos + 1;
+        self.pos += 1;
+        if let Some(arg) = self.take(range.clone()) {
+            let ty = arg.ty().decay();
+            if !ty.is_integer() {
+                self.warn(warning(ty), range);
+            }
+        }
+    }
+
+    /// Consumes the rest of a `%[` scanset through its `]`, which may
+    /// also be its first character. Returns false if there is none.
+    fn scanset(&mut self) -> bool {
+        self.eat(b"^");
+        self.eat(b"]");
+        match self.bytes[self.pos..].iter().position(|&byte| byte == b']') {
+            Some(end) => {
+                self.pos += end + 1;
+                true
+            }
+            None => {
+                self.pos = self.bytes.len();
+                false
+            }
+        }
+    }
+
+    /// The next data argument, for the conversion specification at
+    /// `range`. The first without one is warned about.
+    fn take(&mut self, range: Range<usize>) -> Option<&'a Exp> {
+        let data = self.data?;
+        let arg = data.get(self.next);
+        if self.next == data.len() {
+            self.warn(FormatWarning::MissingArgument, range);
+        }
+        self.next += 1;
+        arg
+    }
+
+    fn warn(&mut self, warning: FormatWarning, range: Range<usize>) {
+        self.warnings.push(SemanticWarning {
+            kind: SemanticWarningKind::Format(warning, range),
+            span: self.literal.span,
+        });
+    }
+}
@@ -0,0 +1,925 @@
+mod cfg;
+mod flow;
+mod format;
+mod overflow;
+mod resolve;
+mod typecheck;
+mod uninit;
+
+use crate::ast::Program;
+use crate::error::{SemanticError, SemanticWarning};
+use crate::semantic::flow::FlowChecker;
+use crate::semantic::overflow::OverflowChecker;
+use crate::semantic::resolve::Resolver;
+use crate::semantic::typecheck::TypeChecker;
+use crate::semantic::uninit::UninitializedChecker;
+
+/// Checks a parsed program and prepares it for codegen: every variable
+/// reference is resolved to its declaration, locals are renamed to names
+/// unique in the program, and every expression is annotated with its type.
+/// Warnings are added to `warnings` whether or not the program is valid,
+/// except those about the flow of control and undefined constants, which
+/// need a valid one.
+pub fn analyze(
+    mut program: Program,
+    warnings: &mut Vec<SemanticWarning>,
+) -> Result<Program, SemanticError> {
+    Resolver::default().resolve(&mut program, warnings)?;
+
+    let program = TypeChecker::default().check(program, warnings)?;
+    warnings.extend(OverflowChecker::default().check(&program));
+    warnings.extend(FlowChecker::default().check(&program));
+    warnings.extend(UninitializedChecker::default().check(&program));
+
+    Ok(program)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::visit::{self, Visit};
+    use crate::ast::{Block, BlockItem, Declaration, Exp, ExpKind, Function, StatementKind};
+    use crate::lexer::Lexer;
+    use crate::parser::Parser;
+    use crate::span::{HasSpan, Span};
+
+    fn analyze_src(src: &str) -> Result<Program, SemanticError> {
+        let program = Parser::new(Lexer::new(src))
+            .parse()
+            .expect("the source parses");
+        analyze(program, &mut Vec::new())
+    }
+
+    /// The names declared and used, in source order. Prototype parameters
+    /// are left as written.
+    #[derive(Default)]
+    struct Names(Vec<String>);
+
+    impl Visit for Names {
+        fn visit_function(&mut self, function: &Function) {
+            let Function::Function { params, .. } = function;
+            let params = params.iter().filter_map(|param| param.name.clone());
+            self.0.extend(params);
+            visit::walk_function(self, function);
+        }
+
+        fn visit_declaration(&mut self, declaration: &Declaration) {
+            if let Declaration::Variable { name, .. } = declaration {
+                self.0.push(name.clone());
+            }
+            visit::walk_declaration(self, declaration);
+        }
+
+        fn visit_exp(&mut self, exp: &Exp) {
+            if let ExpKind::Var(name) = &exp.kind {
+                self.0.push(name.clone());
+            }
+            visit::walk_exp(self, exp);
+        }
+    }
+
+    fn names(src: &str) -> Result<Vec<String>, SemanticError> {
+        let mut names = Names::default();
+        names.visit_program(&analyze_src(src)?);
+        Ok(names.0)
+    }
+
+    #[test]
+    fn locals_are_renamed_uniquely() -> Result<(), SemanticError> {
+        let src = "int f(int a);
+            int g(int a) { int b = a; f(b); return b; }
+            int h(int a, int *p) { int (*q)(int a) = g; *p = a; return q(a); }";
+        assert_eq!(
+            names(src)?,
+            [
+                "a", "a.1", "b.2", "a.1", "f", "b.2", "b.2", "a.3", "p.4", "q.5", "g", "p.4",
+                "a.3", "q.5", "a.3"
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn block_scope_function_declarations() -> Result<(), SemanticError> {
+        let src = "int main(void) { int puts(char *s); int puts(char *); return puts(\"hi\"); }";
+        assert_eq!(names(src)?, ["s", "puts"]);
+        Ok(())
+    }
+
+    #[test]
+    fn variable_in_scope_in_its_initializer() -> Result<(), SemanticError> {
+        assert_eq!(
+            names("int main(void) { void *p = &p; return 0; }")?,
+            ["p.1", "p.1"]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn implicit_function_declarations() -> Result<(), SemanticError> {
+        let src = "int main(void) { int x = 1; return undeclared(x); }";
+        assert_eq!(names(src)?, ["x.1", "undeclared", "x.1"]);
+        Ok(())
+    }
+
+    #[test]
+    fn errors() {
+        let cases = [
+            (
+                "int main(void) { return x; }",
+                "use of undeclared identifier 'x'",
+                "x",
+            ),
+            (
+                "int main(void) { int *p = &q; return 0; }",
+                "use of undeclared identifier 'q'",
+                "q",
+            ),
+            (
+                "int main(int a) { int a; return 0; }",
+                "redefinition of 'a'",
+                "a",
+            ),
+            (
+                "int main(void) { int x = 1; int *y, x = 2; }",
+                "redefinition of 'x'",
+                "x = 2",
+            ),
+            ("int f(int a, int a);", "redefinition of 'a'", "int a"),
+            (
+                "int f(void) { return 0; } int f(void) { return 1; }",
+                "redefinition of 'f'",
+                "int f(void) { return 1; }",
+            ),
+            (
+                "int main(void) { int f; int f(void); }",
+                "'f' redeclared as different kind of symbol",
+                "f(void)",
+            ),
+        ];
+        for (src, message, spelled) in cases {
+            let err = analyze_src(src).err().expect(src);
+            let Span { start, end } = *err.span();
+            assert_eq!(err.to_string(), message, "{src}");
+            assert_eq!(&src[start..end], spelled, "{src}");
+        }
+    }
+
+    mod type_errors {
+        use super::*;
+
+        /// Checks that `src` fails to type check with `message`, at the code
+        /// spelled `spelled`.
+        fn assert_error(src: &str, message: &str, spelled: &str) {
+            let err = analyze_src(src).err().expect("the source is invalid");
+            let Span { start, end } = *err.span();
+            assert_eq!(err.to_string(), message);
+            assert_eq!(&src[start..end], spelled);
+        }
+
+        #[test]
+        fn initializing_a_pointer_from_an_int() {
+            assert_error(
+                "int main(void) { int x; int *p = x; return 0; }",
+                "initializing 'int *' with an expression of incompatible type 'int'",
+                "x",
+            );
+        }
+
+        #[test]
+        fn passing_an_incompatible_pointer() {
+            assert_error(
+                "int f(char *s); int main(void) { int x; return f(&x); }",
+                "passing 'int *' to parameter of incompatible type 'char *'",
+                "&x",
+            );
+        }
+
+        #[test]
+        fn returning_a_void_value() {
+            assert_error(
+                "void g(void); int main(void) { return g(); }",
+                "returning 'void' from a function with incompatible result type 'int'",
+                "g()",
+            );
+        }
+
+        #[test]
+        fn too_few_arguments() {
+            assert_error(
+                "int f(int a); int main(void) { return f(); }",
+                "too few arguments to function call, expected 1, have 0",
+                "f()",
+            );
+        }
+
+        #[test]
+        fn too_few_variadic_arguments() {
+            assert_error(
+                "int f(int a, ...); int main(void) { return f(); }",
+                "too few arguments to function call, expected at least 1, have 0",
+                "f()",
+            );
+        }
+
+        #[test]
+        fn too_many_arguments() {
+            assert_error(
+                "int f(int a); int main(void) { return f(1, 2); }",
+                "too many arguments to function call, expected 1, have 2",
+                "f(1, 2)",
+            );
+        }
+
+        #[test]
+        fn calling_a_non_function() {
+            assert_error(
+                "int main(void) { int x = 1; return x(); }",
+                "called object type 'int' is not a function or function pointer",
+                "x()",
+            );
+        }
+
+        #[test]
+        fn dereferencing_a_non_pointer() {
+            assert_error(
+                "int main(void) { int x = 1; return *x; }",
+                "indirection requires pointer operand ('int' invalid)",
+                "x",
+            );
+        }
+
+        #[test]
+        fn subscripting_a_non_pointer() {
+            assert_error(
+                "int main(void) { int x = 1; return x[1]; }",
+                "subscripted value is not an array or pointer",
+                "x[1]",
+            );
+        }
+
+        #[test]
+        fn non_integer_subscript() {
+            assert_error(
+                "int main(void) { int *p = 0; return p[p]; }",
+                "array subscript is not an integer",
+                "p[p]",
+            );
+        }
+
+        #[test]
+        fn address_of_an_rvalue() {
+            assert_error(
+                "int main(void) { return *&1; }",
+                "cannot take the address of an rvalue of type 'int'",
+                "1",
+            );
+        }
+
+        #[test]
+        fn assigning_to_a_function() {
+            assert_error(
+                "int f(void); int main(void) { f = 0; return 0; }",
+                "expression is not assignable",
+                "f",
+            );
+        }
+
+        #[test]
+        fn assigning_to_an_array() {
+            assert_error(
+                "int main(void) { char s[2] = \"a\"; s = 0; return 0; }",
+                "array type 'char [2]' is not assignable",
+                "s",
+            );
+        }
+
+        #[test]
+        fn assigning_to_a_const_variable() {
+            assert_error(
+                "int main(void) { const int x = 1; x = 2; return 0; }",
+                "cannot assign to lvalue with const-qualified type 'const int'",
+                "x",
+            );
+        }
+
+        #[test]
+        fn assigning_through_a_pointer_to_const() {
+            assert_error(
+                "int main(void) { const char *s = \"a\"; s[0] = 98; return 0; }",
+                "cannot assign to lvalue with const-qualified type 'const char'",
+                "s[0]",
+            );
+        }
+
+        #[test]
+        fn void_function_returning_a_value() {
+            assert_error(
+                "void f(void) { return 1; }",
+                "void function 'f' should not return a value",
+                "return 1;",
+            );
+        }
+
+        #[test]
+        fn conflicting_parameter_qualifiers() {
+            assert_error(
+                "int f(int *a); int f(const int *a);",
+                "conflicting types for 'f'",
+                "int f(const int *a);",
+            );
+        }
+
+        #[test]
+        fn conflicting_block_scope_declaration() {
+            assert_error(
+                "int main(void) { char f(void); return f(); } int f(void);",
+                "conflicting types for 'f'",
+                "int f(void);",
+            );
+        }
+
+        #[test]
+        fn void_argument() {
+            assert_error(
+                "void g(void); int main(void) { return h(g()); }",
+                "argument type 'void' is incomplete",
+                "g()",
+            );
+        }
+
+        #[test]
+        fn va_builtin_without_a_va_list() {
+            assert_error(
+                "int main(void) { int x; __builtin_va_end(x); return 0; }",
+                "argument to 'va_end' is of type 'int' and not 'va_list'",
+                "x",
+            );
+        }
+
+        #[test]
+        fn invalid_unary_operand() {
+            assert_error(
+                "int main(void) { int *p = 0; return ~p; }",
+                "invalid argument type 'int *' to unary expression",
+                "~p",
+            );
+        }
+
+        #[test]
+        fn subtracting_unrelated_pointers() {
+            assert_error(
+                "int main(void) { int *p = 0; char *q = 0; return p - q; }",
+                "invalid operands to binary expression ('int *' and 'char *')",
+                "p - q",
+            );
+        }
+
+        #[test]
+        fn incompatible_conditional_operands() {
+            assert_error(
+                "int main(void) { int *p = 0; return 1 ? p : 1; }",
+                "incompatible operand types ('int *' and 'int')",
+                "1 ? p : 1",
+            );
+        }
+
+        #[test]
+        fn void_condition() {
+            assert_error(
+                "void f(void); int main(void) { return f() ? 1 : 2; }",
+                "used type 'void' where arithmetic or pointer type is required",
+                "f()",
+            );
+        }
+    }
+
+    #[test]
+    fn compatible_redeclarations() -> Result<(), SemanticError> {
+        let src = "int f(const int a); int f(int a) { return a; }
+            int g(char s[]); int g(char *s);";
+        analyze_src(src)?;
+        Ok(())
+    }
+
+    /// The value of each variable's initializer, with its conversions.
+    fn initializers(src: &str) -> Result<Vec<String>, SemanticError> {
+        let Program::Program(functions, _) = analyze_src(src)?;
+        let Some(Function::Function {
+            body: Some(Block(items, _)),
+            ..
+        }) = functions.last()
+        else {
+            panic!("the last function is a definition");
+        };
+        let describe = |exp: &Exp| match &exp.kind {
+            ExpKind::Convert(inner) => format!("({}) {}", exp.ty(), inner.ty()),
+            _ => exp.ty().to_string(),
+        };
+
+        Ok(items
+            .iter()
+            .filter_map(|item| match item {
+                BlockItem::Declaration(Declaration::Variable {
+                    init: Some(init), ..
+                }) => Some(describe(init)),
+                BlockItem::Statement(statement) => match &statement.kind {
+                    StatementKind::Return(exp) => Some(describe(exp)),
+                    _ => None,
+                },
+                _ => None,
+            })
+            .collect())
+    }
+
+    #[test]
+    fn implicit_conversions() -> Result<(), SemanticError> {
+        let src = "int f(int); int main(void) {
+            char c = 300; int i = c; char s[3] = \"ab\"; char *p = s;
+            const char *q = s; void *v = &i; int *ip = v; int *n = 0;
+            int (*fp)(int) = f; char r = *q;
+            return c; }";
+        assert_eq!(
+            initializers(src)?,
+            [
+                "(char) int",
+                "(int) char",
+                "char [3]",
+                "char [3]",
+                "(const char *) char [3]",
+                "(void *) int *",
+                "(int *) void *",
+                "(int *) int",
+                "int (int)",
+                "const char",
+                "(int) char",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn operator_types() -> Result<(), SemanticError> {
+        let src = "int main(void) {
+            char c = 1; char s[4] = \"abc\"; void *v = s;
+            char d = -c; char *p = 1 + s; int n = p - s; int b = p && !c;
+            void *q = c ? v : p;
+            return c < 2 ? c : n; }";
+        assert_eq!(
+            initializers(src)?,
+            [
+                "(char) int",
+                "char [4]",
+                "(void *) char [4]",
+                "(char) int",
+                "char *",
+                "int",
+                "int",
+                "void *",
+                "int",
+            ]
+        );
+        Ok(())
+    }
+
+    /// The warnings about `src`, each with the source it is about.
+    fn warnings(src: &str) -> Result<Vec<(String, &str)>, SemanticError> {
+        let program = Parser::new(Lexer::new(src))
+            .parse()
+            .expect("the source parses");
+        let mut warnings = Vec::new();
+        analyze(program, &mut warnings)?;
+
+        Ok(warnings
+            .iter()
+            .map(|warning| {
+                let Span { start, end } = *warning.span();
+                (warning.to_string(), &src[start..end])
+            })
+            .collect())
+    }
+
+    #[test]
+    fn discarded_qualifiers_warn() -> Result<(), SemanticError> {
+        let src = "int f(char *s);
+            int main(void) { const char *c = \"x\"; char *p = c; p = c; return f(c); }";
+        let warnings = warnings(src)?
+            .into_iter()
+            .map(|(warning, _)| warning)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "warning: initializing 'char *' with an expression of type 'const char *' discards qualifiers",
+                "warning: assigning to 'char *' from 'const char *' discards qualifiers",
+                "warning: passing 'const char *' to parameter of type 'char *' discards qualifiers",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn unused_declarations_and_missing_returns_warn() -> Result<(), SemanticError> {
+        let src = "int f(int a, int b) { int x = a; int *p = &x; }
+            int g(int c);
+            int main(void) { int y, z; return undeclared(1) + undeclared(2); }";
+        assert_eq!(
+            warnings(src)?,
+            [
+                (String::from("warning: unused parameter 'b'"), "int b"),
+                (String::from("warning: unused variable 'p'"), "*p = &x"),
+                (
+                    String::from(
+                        "warning: call to undeclared function 'undeclared'; ISO C99 and later do not support implicit function declarations"
+                    ),
+                    "undeclared"
+                ),
+                (String::from("warning: unused variable 'y'"), "y"),
+                (String::from("warning: unused variable 'z'"), "z"),
+                (
+                    String::from("warning: non-void function does not return a value"),
+                    "}"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn flow_of_control() -> Result<(), SemanticError> {
+        let src = "int f(int a) { return a; int b; ; a = 1; return 2; }
+            int g(int a) { a = 1; }
+            int main(void) { g(1); }";
+        assert_eq!(
+            warnings(src)?,
+            [
+                (String::from("warning: unused variable 'b'"), "b"),
+                (
+                    String::from("warning: code will never be executed"),
+                    "a = 1;"
+                ),
+                (
+                    String::from("warning: non-void function does not return a value"),
+                    "}"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn flow_of_control_in_a_graph() {
+        use crate::semantic::cfg::{BasicBlock, Cfg, Event};
+        use crate::semantic::flow::FlowChecker;
+
+        // A body that returns on one branch and reaches its end on the
+        // other, as `if` will give, with a run of two unreachable blocks.
+        let block = |events, successors| BasicBlock { events, successors };
+        let cfg = Cfg {
+            blocks: vec![
+                block(vec![], vec![1, 2]),
+                block(vec![Event::Return], vec![]),
+                block(vec![], vec![]),
+                block(vec![Event::Code(Span::new(4, 5))], vec![4]),
+                block(vec![Event::Code(Span::new(2, 3))], vec![]),
+            ],
+            variables: Vec::new(),
+            end: 2,
+        };
+        let warnings = FlowChecker::check_body(&cfg, Some(Span::single(9)))
+            .into_iter()
+            .map(|warning| (warning.to_string(), *warning.span()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                (
+                    String::from("warning: code will never be executed"),
+                    Span::new(2, 3)
+                ),
+                (
+                    String::from(
+                        "warning: non-void function does not return a value in all control paths"
+                    ),
+                    Span::single(9)
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn main_returns_zero_at_its_end() -> Result<(), SemanticError> {
+        let src = "int f(int a) { a = 1; }
+            int main(void) { int x = 1; x = f(x); }";
+        assert_eq!(
+            warnings(src)?,
+            [(
+                String::from("warning: non-void function does not return a value"),
+                "}"
+            )]
+        );
+        assert_eq!(warnings("int main(void) { }")?, []);
+        Ok(())
+    }
+
+    #[test]
+    fn uninitialized_uses_warn() -> Result<(), SemanticError> {
+        let src = "int f(int c) {
+                int x; int y; int z; int w; int a = 1; int b;
+                c && (y = 1);
+                int *p = &z;
+                c ? (w = 1) : (w = 2);
+                return x + x + y + z + w + a + *p + (b = 3) + b;
+            }
+            int g(void) { int u; return 0; return u; }";
+        assert_eq!(
+            warnings(src)?,
+            [
+                (
+                    String::from("warning: code will never be executed"),
+                    "return u;"
+                ),
+                (
+                    String::from("warning: variable 'x' is uninitialized when used here"),
+                    "x"
+                ),
+                (
+                    String::from("warning: variable 'y' may be uninitialized when used here"),
+                    "y"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn uninitialized_uses_note_the_declaration() -> Result<(), SemanticError> {
+        use crate::diagnostics::Warn;
+
+        let src = "int main(void) { int x; return x; }";
+        let program = Parser::new(Lexer::new(src))
+            .parse()
+            .expect("the source parses");
+        let mut warnings = Vec::new();
+        analyze(program, &mut warnings)?;
+        let notes: Vec<_> = warnings[0]
+            .notes()
+            .into_iter()
+            .map(|(note, span)| (note, &src[span.start..span.end]))
+            .collect();
+        assert_eq!(
+            notes,
+            [(String::from("variable 'x' is declared here"), "x")]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn uninitialized_uses_around_a_loop() {
+        use crate::semantic::cfg::{BasicBlock, Cfg, Event, Variable};
+        use crate::semantic::uninit::UninitializedChecker;
+
+        // `int x; while (c) { x; x = 1; }`, as a loop will give it: the
+        // header tests the condition, and the body goes back to it.
+        let block = |events, successors| BasicBlock { events, successors };
+        let loop_cfg = |assigned_before| Cfg {
+            blocks: vec![
+                block(
+                    if assigned_before {
+                        vec![Event::Declare(0), Event::Assign(0)]
+                    } else {
+                        vec![Event::Declare(0)]
+                    },
+                    vec![1],
+                ),
+                block(vec![], vec![2, 3]),
+                block(
+                    vec![Event::Use(0, Span::new(2, 3)), Event::Assign(0)],
+                    vec![1],
+                ),
+                block(vec![], vec![]),
+            ],
+            variables: vec![Variable {
+                name: String::from("x.1"),
+                span: Span::new(0, 1),
+            }],
+            end: 3,
+        };
+
+        let warnings = UninitializedChecker::check_body(&loop_cfg(false));
+        assert_eq!(
+            warnings.iter().map(ToString::to_string).collect::<Vec<_>>(),
+            ["warning: variable 'x' may be uninitialized when used here"]
+        );
+        assert!(UninitializedChecker::check_body(&loop_cfg(true)).is_empty());
+    }
+
+    #[test]
+    fn narrowing_conversions_warn() -> Result<(), SemanticError> {
+        let src = "char g(char c);
+            char f(int i) { char a = 65; char b = 'b'; char c = 300; c = i; g(a + b); return c; }
+            char h(void) { return 1 + 2147483647 * 2; }";
+        assert_eq!(
+            warnings(src)?,
+            [
+                (
+                    String::from(
+                        "warning: implicit conversion from 'int' to 'char' changes value from 300 to 44"
+                    ),
+                    "300"
+                ),
+                (
+                    String::from(
+                        "warning: implicit conversion loses integer precision: 'int' to 'char'"
+                    ),
+                    "i"
+                ),
+                (
+                    String::from(
+                        "warning: implicit conversion loses integer precision: 'int' to 'char'"
+                    ),
+                    "a + b"
+                ),
+                (
+                    String::from("warning: overflow in expression; result is -2 with type 'int'"),
+                    "2147483647 * 2"
+                ),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn undefined_constants_warn_on_their_own() -> Result<(), SemanticError> {
+        use crate::diagnostics::{Warn, Warning};
+
+        let src = "int f(int x) { x = 1 - 2147483647 * 2; f(1 / 0); return x && (0 && 1 % 0); }";
+        let program = Parser::new(Lexer::new(src))
+            .parse()
+            .expect("the source parses");
+        let mut warnings = Vec::new();
+        analyze(program, &mut warnings)?;
+        let warnings = warnings
+            .iter()
+            .map(|warning| {
+                let Span { start, end } = *warning.span();
+                (warning.warning(), &src[start..end])
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                (Warning::Overflow, "2147483647 * 2"),
+                (Warning::DivisionByZero, "1 / 0"),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_strings_are_checked() -> Result<(), SemanticError> {
+        let src = "int printf(const char *fmt, ...);
+            int scanf(const char *fmt, ...);
+            int log(int level, char *fmt, ...) __attribute__((format(printf, 2, 3)));
+            int main(void) {
+                char *s = \"s\"; int n = 0;
+                printf(\"%d %-5s %%\\n\", n, s);
+                printf(\"%s|%*d\", n, s, n);
+                printf(\"%ld %c\", n);
+                printf(\"%d\", n, n);
+                printf(\"%q %d\", n, n);
+                scanf(\"%d %*s %[^]a]\", n, s);
+                log(1, \"%p %\", s);
+                return 0;
+            }";
+        let warnings = warnings(src)?
+            .into_iter()
+            .map(|(message, _)| message)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            warnings,
+            [
+                "warning: format specifies type 'char *' but the argument has type 'int'",
+                "warning: field width should have type 'int', but argument has type 'char *'",
+                "warning: format specifies type 'long' but the argument has type 'int'",
+                "warning: more '%' conversions than data arguments",
+                "warning: data argument not used by format string",
+                "warning: invalid conversion specifier 'q'",
+                "warning: format specifies type 'int *' but the argument has type 'int'",
+                "warning: incomplete format specifier",
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_options_control_the_group() -> Result<(), SemanticError> {
+        use crate::diagnostics::{Diagnostics, Severity, Warn, WarningOptions};
+        use crate::source_map::SourceMap;
+
+        let src = "int printf(const char *fmt, ...);
+            int main(void) { printf(\"%s\", 1); printf(\"%q\"); printf(\"\", 1); return 0; }";
+        let program = Parser::new(Lexer::new(src))
+            .parse()
+            .expect("the source parses");
+        let mut warnings = Vec::new();
+        analyze(program, &mut warnings)?;
+        let severities = |options: &[&str]| {
+            let mut warning_options = WarningOptions::default();
+            for option in options {
+                assert!(warning_options.apply(option));
+            }
+            let diagnostics = Diagnostics::new(warning_options);
+            let sources = SourceMap::default();
+            warnings
+                .iter()
+                .map(|warning| {
+                    let severity = diagnostics.severity(&sources, warning.warning(), 0);
+                    (warning.warning().name(), severity)
+                })
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(
+            severities(&[]),
+            [
+                ("format", Severity::Warning),
+                ("format-invalid-specifier", Severity::Warning),
+                ("format-extra-args", Severity::Warning),
+            ]
+        );
+        assert_eq!(
+            severities(&["no-format"]),
+            [
+                ("format", Severity::Ignored),
+                ("format-invalid-specifier", Severity::Ignored),
+                ("format-extra-args", Severity::Ignored),
+            ]
+        );
+        assert_eq!(
+            severities(&["error=format"]),
+            [
+                ("format", Severity::Error),
+                ("format-invalid-specifier", Severity::Error),
+                ("format-extra-args", Severity::Error),
+            ]
+        );
+        assert_eq!(
+            severities(&["error", "no-format", "format-extra-args"]),
+            [
+                ("format", Severity::Ignored),
+                ("format-invalid-specifier", Severity::Ignored),
+                ("format-extra-args", Severity::Error),
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn format_warnings_point_into_the_literal() -> Result<(), SemanticError> {
+        use crate::diagnostics::Warn;
+        use crate::preprocessor::{IncludePaths, PpToken, Preprocessor};
+        use crate::source_map::SourceMap;
+        use std::path::Path;
+
+        let src = "#define FMT \"%d %s\"
+            int printf(const char *fmt, ...);
+            int main(void) {
+                int n = 0;
+                printf(\"%d\" \" %s\", n, n);
+                printf(\"\\x25s\", n);
+                printf(FMT, n, n);
+                printf(\"a\" FMT, n, n);
+                return 0;
+            }";
+        let mut sources = SourceMap::default();
+        let tokens = Preprocessor::new(&mut sources, IncludePaths::default())
+            .run(Path::new("test.c"), src.to_string())
+            .expect("the source preprocesses");
+        let program = Parser::new(tokens.into_iter().map(PpToken::into_token))
+            .parse()
+            .expect("the source parses");
+        let mut warnings = Vec::new();
+        analyze(program, &mut warnings)?;
+
+        let located = warnings
+            .iter()
+            .map(|warning| {
+                let span = warning.locate(&sources);
+                (sources.location(span.start).unwrap(), sources.snippet(span))
+            })
+            .collect::<Vec<_>>();
+        // A literal partly from a macro is not lexed again; the warning
+        // points at all of it.
+        assert_eq!(
+            located,
+            [
+                (String::from("test.c:5:31"), "%s"),
+                (String::from("test.c:6:25"), "\\x25s"),
+                (String::from("test.c:1:17"), "%s"),
+                (String::from("test.c:8:24"), "\"a\" FMT"),
+            ]
+        );
+        Ok(())
+    }
+}
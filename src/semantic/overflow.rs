@@ -0,0 +1,36 @@
+use crate::ast::constant;
+use crate::ast::visit::{self, Visit};
+use crate::ast::{Exp, Program};
+use crate::error::{ConstantError, SemanticWarning, SemanticWarningKind};
+use crate::span::HasSpan;
+
+/// Warns about constant subexpressions whose evaluation is undefined, as
+/// by overflowing or dividing by zero, where the program does not need
+/// them to be constant and so does not reject them.
+#[derive(Default)]
+pub(super) struct OverflowChecker {
+    warnings: Vec<SemanticWarning>,
+}
+
+impl OverflowChecker {
+    pub(super) fn check(mut self, program: &Program) -> Vec<SemanticWarning> {
+        self.visit_program(program);
+        self.warnings
+    }
+}
+
+impl Visit for OverflowChecker {
+    /// Evaluates the largest constant subexpressions, so that an operand
+    /// which is not evaluated, as in `0 && 1 / 0`, is not warned about,
+    /// and each error is warned about once.
+    fn visit_exp(&mut self, exp: &Exp) {
+        match constant::evaluate(exp) {
+            Ok(_) => {}
+            Err(ConstantError::NotConstant(_)) => visit::walk_exp(self, exp),
+            Err(error) => self.warnings.push(SemanticWarning {
+                span: *error.span(),
+                kind: SemanticWarningKind::UndefinedConstant(error),
+            }),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::ast::visit_mut::{self, VisitMut};
+use crate::ast::{Block, Declaration, Exp, ExpKind, Function, Param, Program};
+use crate::error::{SemanticError, SemanticWarning, SemanticWarningKind};
+use crate::span::{HasSpan, Span};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Clone, Copy, PartialEq)]
+enum Kind {
+    Variable { parameter: bool },
+    Function { defined: bool },
+}
+
+/// What a name in scope refers to.
+struct Entry {
+    /// The name references are rewritten to: unique for a local variable,
+    /// and the name itself for a function, which has linkage.
+    unique: String,
+    kind: Kind,
+    /// Where the name was declared.
+    span: Span,
+    /// Whether an expression has referred to the name.
+    used: bool,
+}
+
+/// Resolves each variable reference to its declaration, renaming locals
+/// so that every one has a unique name and codegen need not know about
+/// scopes.
+#[derive(Default)]
+pub(super) struct Resolver {
+    /// The scopes open at the current point, file scope first.
+    scopes: Vec<HashMap<String, Entry>>,
+    /// The number of locals renamed so far, which numbers the next one.
+    renamed: usize,
+    /// The functions called without a declaration so far, each of which
+    /// is warned about once.
+    implicit: HashSet<String>,
+    warnings: Vec<SemanticWarning>,
+    /// The first error found; the traversal carries on regardless.
+    error: Option<SemanticError>,
+}
+
+impl Resolver {
+    pub(super) fn resolve(
+        mut self,
+        program: &mut Program,
+        warnings: &mut Vec<SemanticWarning>,
+    ) -> Result<(), SemanticError> {
+        self.visit_program_mut(program);
+        warnings.append(&mut self.warnings);
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    fn report(&mut self, err: SemanticError) {
+        self.error.get_or_insert(err);
+    }
+
+    fn lookup(&mut self, name: &str) -> Option<&mut Entry> {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+    }
+
+    /// Closes the innermost scope, warning about the variables declared
+    /// in it that were never used.
+    fn pop_scope(&mut self) {
+        let scope = self.scopes.pop().expect("a scope is open");
+        let mut unused = scope
+            .into_iter()
+            .filter(|(_, entry)| !entry.used)
+            .filter_map(|(name, entry)| match entry.kind {
+                Kind::Variable { parameter: true } => {
+                    Some((SemanticWarningKind::UnusedParameter(name), entry.span))
+                }
+                Kind::Variable { parameter: false } => {
+                    Some((SemanticWarningKind::UnusedVariable(name), entry.span))
+                }
+                Kind::Function { .. } => None,
+            })
+            .collect::<Vec<_>>();
+        unused.sort_by_key(|(_, span)| span.start);
+        self.warnings.extend(
+            unused
+                .into_iter()
+                .map(|(kind, span)| SemanticWarning { kind, span }),
+        );
+    }
+
+    /// Declares `name` in the innermost scope, where it may only have been
+    /// declared before as a function.
+    fn declare(&mut self, name: &str, kind: Kind, span: Span) {
+        let scope = self.scopes.last_mut().expect("declarations are in a scope");
+        if let Some(previous) = scope.get_mut(name) {
+            let err = match (previous.kind, kind) {
+                (Kind::Function { defined: true }, Kind::Function { defined: true }) => {
+                    SemanticError::Redefinition(name.to_string(), span)
+                }
+                (Kind::Function { defined }, Kind::Function { defined: now }) => {
+                    previous.kind = Kind::Function {
+                        defined: defined || now,
+                    };
+                    return;
+                }
+                (Kind::Variable { .. }, Kind::Variable { .. }) => {
+                    SemanticError::Redefinition(name.to_string(), span)
+                }
+                _ => SemanticError::RedeclaredAsDifferentKind(name.to_string(), span),
+            };
+            self.report(err);
+            return;
+        }
+
+        let unique = match kind {
+            Kind::Variable { .. } => {
+                self.renamed += 1;
+                format!("{name}.{}", self.renamed)
+            }
+            Kind::Function { .. } => name.to_string(),
+        };
+        scope.insert(
+            name.to_string(),
+            Entry {
+                unique,
+                kind,
+                span,
+                used: false,
+            },
+        );
+    }
+
+    /// Declares a local variable or parameter and renames it to its unique
+    /// name.
+    fn declare_variable(&mut self, name: &mut String, parameter: bool, span: Span) {
+        self.declare(name, Kind::Variable { parameter }, span);
+        if let Some(entry) = self
+            .scopes
+            .last()
+            .and_then(|scope| scope.get(name.as_str()))
+        {
+            *name = entry.unique.clone();
+        }
+    }
+
+    /// Checks that the named parameters of a declaration are distinct. Their
+    /// scope ends with the declaration, so they are not renamed.
+    fn check_params(&mut self, params: &[Param]) {
+        let mut seen = HashSet::new();
+        for param in params {
+            if let Some(name) = &param.name
+                && !seen.insert(name)
+            {
+                self.report(SemanticError::Redefinition(name.clone(), param.span));
+            }
+        }
+    }
+}
+
+impl VisitMut for Resolver {
+    fn visit_program_mut(&mut self, program: &mut Program) {
+        self.scopes.push(HashMap::new());
+        visit_mut::walk_program_mut(self, program);
+        self.pop_scope();
+    }
+
+    fn visit_function_mut(&mut self, function: &mut Function) {
+        let span = *function.span();
+        let Function::Function {
+            name, params, body, ..
+        } = function;
+        let defined = body.is_some();
+        self.declare(name, Kind::Function { defined }, span);
+
+        // The parameters and the outermost block of the body share a scope.
+        match body {
+            Some(body) => {
+                self.scopes.push(HashMap::new());
+                for param in params {
+                    if let Some(name) = &mut param.name {
+                        self.declare_variable(name, true, param.span);
+                    }
+                }
+                visit_mut::walk_block_mut(self, body);
+                self.pop_scope();
+            }
+            None => self.check_params(params),
+        }
+    }
+
+    fn visit_block_mut(&mut self, block: &mut Block) {
+        self.scopes.push(HashMap::new());
+        visit_mut::walk_block_mut(self, block);
+        self.pop_scope();
+    }
+
+    fn visit_declaration_mut(&mut self, declaration: &mut Declaration) {
+        match declaration {
+            // A variable is in scope in its own initializer.
+            Declaration::Variable {
+                name, init, span, ..
+            } => {
+                self.declare_variable(name, false, *span);
+                if let Some(init) = init {
+                    self.visit_exp_mut(init);
+                }
+            }
+            Declaration::Function(function) => self.visit_function_mut(function),
+        }
+    }
+
+    fn visit_exp_mut(&mut self, exp: &mut Exp) {
+        match &mut exp.kind {
+            ExpKind::Var(name) => match self.lookup(name) {
+                Some(entry) => {
+                    entry.used = true;
+                    *name = entry.unique.clone();
+                }
+                None => self.report(SemanticError::UndeclaredIdentifier(name.clone(), exp.span)),
+            },
+            // Calling an undeclared function declares it implicitly.
+            ExpKind::FunctionCall(callee, args) if matches!(&callee.kind, ExpKind::Var(name) if self.lookup(name).is_none()) =>
+            {
+                if let ExpKind::Var(name) = &callee.kind
+                    && self.implicit.insert(name.clone())
+                {
+                    self.warnings.push(SemanticWarning {
+                        kind: SemanticWarningKind::ImplicitFunctionDeclaration(name.clone()),
+                        span: callee.span,
+                    });
+                }
+                for arg in args {
+                    self.visit_exp_mut(arg);
+                }
+            }
+            _ => visit_mut::walk_exp_mut(self, exp),
+        }
+    }
+}
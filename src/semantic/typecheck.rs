@@ -0,0 +1,567 @@
+use crate::ast::constant;
+use crate::ast::fold::{self, Fold};
+use crate::ast::{
+    BinaryOp, Declaration, Exp, ExpKind, Format, Function, Program, Statement, StatementKind,
+    UnaryOp,
+};
+use crate::error::{
+    ConstantError, Conversion, SemanticError, SemanticWarning, SemanticWarningKind,
+};
+use crate::semantic::format;
+use crate::span::Span;
+use crate::token::CharPrefix;
+use crate::types::symbols::SymbolTable;
+use crate::types::{Type, TypeKind};
+use std::collections::HashMap;
+
+/// The type assumed for a function called without a declaration. Its
+/// parameters are unknown, so it takes any arguments, promoted.
+fn implicit_function() -> Type {
+    Type::new(TypeKind::Function {
+        params: Vec::new(),
+        variadic: true,
+        ret: Box::new(Type::new(TypeKind::Int)),
+    })
+}
+
+fn int() -> Type {
+    Type::new(TypeKind::Int)
+}
+
+/// Whether `exp` designates an object, which it must to be assigned to or
+/// have its address taken.
+fn is_lvalue(exp: &Exp) -> bool {
+    match &exp.kind {
+        ExpKind::Var(_) => !exp.ty().is_function(),
+        ExpKind::String(_) | ExpKind::Subscript(..) => true,
+        ExpKind::Dereference(_) => !exp.ty().is_function(),
+        _ => false,
+    }
+}
+
+/// An integer constant with the value 0, which converts to any pointer.
+fn is_null_pointer_constant(exp: &Exp) -> bool {
+    matches!(
+        exp.kind,
+        ExpKind::ConstantInt(0) | ExpKind::ConstantChar(0, _)
+    )
+}
+
+/// Converts an operand of type `char` to `int`, the type arithmetic is done
+/// in.
+fn integer_promotion(exp: Exp) -> Exp {
+    let from = exp.ty().decay();
+    match from.kind {
+        TypeKind::Char => convert_to(exp, from, int()),
+        _ => exp,
+    }
+}
+
+/// Whether two pointer types point to compatible types, ignoring their
+/// qualifiers.
+fn same_pointee(a: &Type, b: &Type) -> bool {
+    match (&a.kind, &b.kind) {
+        (TypeKind::Pointer(a), TypeKind::Pointer(b)) => {
+            a.unqualified().is_compatible(&b.unqualified())
+        }
+        _ => false,
+    }
+}
+
+/// Whether one pointer type points to `void` and the other to an object.
+fn void_and_object_pointer(a: &Type, b: &Type) -> bool {
+    let is_void_pointer =
+        |ty: &Type| matches!(&ty.kind, TypeKind::Pointer(pointee) if pointee.is_void());
+    (is_void_pointer(a) && (b.is_object_pointer() || is_void_pointer(b)))
+        || (is_void_pointer(b) && a.is_object_pointer())
+}
+
+/// Annotates every expression with its type, checking that each operation
+/// applies to its operands, and makes implicit conversions explicit as
+/// `Convert` nodes for codegen.
+#[derive(Default)]
+pub(super) struct TypeChecker {
+    symbols: SymbolTable,
+    /// The name and return type of the function whose body is checked.
+    function: Option<(String, Type)>,
+    /// The format attribute of each function declared with one.
+    formats: HashMap<String, Format>,
+    warnings: Vec<SemanticWarning>,
+    /// The first error found; the traversal carries on regardless.
+    error: Option<SemanticError>,
+}
+
+impl TypeChecker {
+    pub(super) fn check(
+        mut self,
+        program: Program,
+        warnings: &mut Vec<SemanticWarning>,
+    ) -> Result<Program, SemanticError> {
+        let program = self.fold_program(program);
+        warnings.append(&mut self.warnings);
+        match self.error {
+            Some(err) => Err(err),
+            None => Ok(program),
+        }
+    }
+
+    fn report(&mut self, err: SemanticError) {
+        self.error.get_or_insert(err);
+    }
+
+    /// Converts `exp` as if by assignment to an object of type `to`.
+    fn convert(&mut self, exp: Exp, to: &Type, conversion: Conversion) -> Exp {
+        let from = exp.ty().decay();
+        let to = to.unqualified();
+
+        let compatible = match (&from.kind, &to.kind) {
+            _ if from.is_integer() && to.is_integer() => true,
+            (_, TypeKind::Pointer(_)) if is_null_pointer_constant(&exp) => true,
+            (TypeKind::Pointer(a), TypeKind::Pointer(b)) => {
+                let compatible = a.unqualified().is_compatible(&b.unqualified())
+                    || (a.is_void() && !b.is_function())
+                    || (b.is_void() && !a.is_function());
+                if compatible && !b.qualifiers.contains(a.qualifiers) {
+                    self.warnings.push(SemanticWarning {
+                        kind: SemanticWarningKind::DiscardsQualifiers(
+                            conversion,
+                            from.clone(),
+                            to.clone(),
+                        ),
+                        span: exp.span,
+                    });
+                }
+                compatible
+            }
+            _ => false,
+        };
+        if from.kind == TypeKind::Int && to.kind == TypeKind::Char {
+            self.check_narrowing(&exp, &from, &to);
+        }
+        if !compatible {
+            self.report(SemanticError::IncompatibleTypes(
+                conversion,
+                exp.ty().clone(),
+                to.clone(),
+                exp.span,
+            ));
+        }
+
+        convert_to(exp, from, to)
+    }
+
+    /// Warns about an implicit conversion of `exp` from `int` to `char`,
+    /// which may lose its value, unless it is a constant that fits.
+    fn check_narrowing(&mut self, exp: &Exp, from: &Type, to: &Type) {
+        let (kind, span) = match constant::evaluate(exp) {
+            Ok(value) if i64::from(value as i8) == value => return,
+            Ok(value) => {
+                let change = Some((value, i64::from(value as i8)));
+                let kind =
+                    SemanticWarningKind::ImplicitConversion(from.clone(), to.clone(), change);
+                (kind, exp.span)
+            }
+            Err(ConstantError::NotConstant(_)) => {
+                let kind = SemanticWarningKind::ImplicitConversion(from.clone(), to.clone(), None);
+                (kind, exp.span)
+            }
+            // The value is undefined, which is warned about on its own.
+            Err(_) => return,
+        };
+        self.warnings.push(SemanticWarning { kind, span });
+    }
+
+    /// Applies the default argument promotions to an argument with no
+    /// parameter type to convert to.
+    fn promote(&mut self, exp: Exp) -> Exp {
+        if exp.ty().is_void() {
+            self.report(SemanticError::VoidArgument(exp.span));
+        }
+
+        integer_promotion(exp)
+    }
+
+    fn check_unary(&mut self, op: UnaryOp, exp: Exp, span: Span) -> (ExpKind, Type) {
+        let exp = self.fold_exp(exp);
+        let ty = exp.ty().decay();
+        let valid = match op {
+            UnaryOp::Not => ty.is_scalar(),
+            _ => ty.is_integer(),
+        };
+        if !valid {
+            self.report(SemanticError::InvalidUnaryOperand(exp.ty().clone(), span));
+        }
+
+        (ExpKind::Unary(op, Box::new(integer_promotion(exp))), int())
+    }
+
+    fn check_binary(&mut self, op: BinaryOp, lhs: Exp, rhs: Exp, span: Span) -> (ExpKind, Type) {
+        let lhs = self.fold_exp(lhs);
+        let rhs = self.fold_exp(rhs);
+        let (a, b) = (lhs.ty().decay(), rhs.ty().decay());
+
+        let ty = match op {
+            BinaryOp::And | BinaryOp::Or => (a.is_scalar() && b.is_scalar()).then(int),
+            _ if a.is_integer() && b.is_integer() => Some(int()),
+            BinaryOp::Add if a.is_object_pointer() && b.is_integer() => Some(a.clone()),
+            BinaryOp::Add if a.is_integer() && b.is_object_pointer() => Some(b.clone()),
+            BinaryOp::Subtract if a.is_object_pointer() && b.is_integer() => Some(a.clone()),
+            BinaryOp::Subtract if a.is_object_pointer() && same_pointee(&a, &b) => {
+                Some(Type::ptrdiff())
+            }
+            BinaryOp::Equal | BinaryOp::NotEqual
+                if (a.is_pointer() && is_null_pointer_constant(&rhs))
+                    || (b.is_pointer() && is_null_pointer_constant(&lhs))
+                    || void_and_object_pointer(&a, &b) =>
+            {
+                Some(int())
+            }
+            _ if op.is_comparison() && a.is_pointer() && same_pointee(&a, &b) => Some(int()),
+            _ => None,
+        };
+        let ty = ty.unwrap_or_else(|| {
+            self.report(SemanticError::InvalidOperands(
+                lhs.ty().clone(),
+                rhs.ty().clone(),
+                span,
+            ));
+            int()
+        });
+
+        let lhs = integer_promotion(lhs);
+        let rhs = integer_promotion(rhs);
+        (ExpKind::Binary(op, Box::new(lhs), Box::new(rhs)), ty)
+    }
+
+    fn check_conditional(
+        &mut self,
+        condition: Exp,
+        then: Exp,
+        otherwise: Exp,
+        span: Span,
+    ) -> (ExpKind, Type) {
+        let condition = self.fold_exp(condition);
+        let then = self.fold_exp(then);
+        let otherwise = self.fold_exp(otherwise);
+        if !condition.ty().decay().is_scalar() {
+            self.report(SemanticError::NotScalar(
+                condition.ty().clone(),
+                condition.span,
+            ));
+        }
+
+        let (a, b) = (then.ty().decay(), otherwise.ty().decay());
+        let ty = if a.is_integer() && b.is_integer() {
+            int()
+        } else if (a.is_void() && b.is_void())
+            || same_pointee(&a, &b)
+            || (a.is_pointer() && is_null_pointer_constant(&otherwise))
+        {
+            a
+        } else if b.is_pointer() && is_null_pointer_constant(&then) {
+            b
+        } else if void_and_object_pointer(&a, &b) {
+            Type::pointer_to(Type::new(TypeKind::Void))
+        } else {
+            self.report(SemanticError::IncompatibleOperands(
+                then.ty().clone(),
+                otherwise.ty().clone(),
+                span,
+            ));
+            int()
+        };
+
+        let then = integer_promotion(then);
+        let otherwise = integer_promotion(otherwise);
+        let kind = ExpKind::Conditional(Box::new(condition), Box::new(then), Box::new(otherwise));
+        (kind, ty)
+    }
+
+    fn check_assignable(&mut self, exp: &Exp) {
+        let ty = exp.ty();
+        if !is_lvalue(exp) {
+            self.report(SemanticError::NotAssignable(exp.span));
+        } else if ty.is_array() {
+            self.report(SemanticError::ArrayAssignment(ty.clone(), exp.span));
+        } else if ty.qualifiers.is_const {
+            self.report(SemanticError::ConstAssignment(ty.clone(), exp.span));
+        }
+    }
+
+    fn check_va_list(&mut self, builtin: &'static str, exp: &Exp) {
+        match &exp.ty().decay().kind {
+            TypeKind::Pointer(tag) if tag.kind == TypeKind::VaListTag => {}
+            _ => self.report(SemanticError::NotVaList(
+                builtin,
+                exp.ty().clone(),
+                exp.span,
+            )),
+        }
+    }
+
+    fn check_call(&mut self, callee: Exp, args: Vec<Exp>, span: Span) -> (ExpKind, Type) {
+        let callee = self.fold_exp(callee);
+        let args = args
+            .into_iter()
+            .map(|arg| self.fold_exp(arg))
+            .collect::<Vec<_>>();
+
+        let TypeKind::Pointer(function) = callee.ty().decay().kind else {
+            return self.call_error(callee, args, span);
+        };
+        let TypeKind::Function {
+            params,
+            variadic,
+            ret,
+        } = function.kind
+        else {
+            return self.call_error(callee, args, span);
+        };
+
+        let format = match &callee.kind {
+            ExpKind::Var(name) => self.formats.get(name).copied().or(format::standard(name)),
+            _ => None,
+        };
+        if let Some(format) = format {
+            self.warnings.extend(format::check(format, &args));
+        }
+
+        if args.len() < params.len() || (!variadic && args.len() > params.len()) {
+            self.report(SemanticError::ArgumentCount(
+                params.len(),
+                args.len(),
+                variadic,
+                span,
+            ));
+        }
+        let mut params = params.iter();
+        let args = args
+            .into_iter()
+            .map(|arg| match params.next() {
+                Some(param) => self.convert(arg, param, Conversion::Passing),
+                None => self.promote(arg),
+            })
+            .collect();
+
+        (ExpKind::FunctionCall(Box::new(callee), args), *ret)
+    }
+
+    fn call_error(&mut self, callee: Exp, args: Vec<Exp>, span: Span) -> (ExpKind, Type) {
+        self.report(SemanticError::NotAFunction(callee.ty().clone(), span));
+
+        (ExpKind::FunctionCall(Box::new(callee), args), int())
+    }
+}
+
+/// Wraps `exp`, whose value has type `from`, in a conversion to `to` unless
+/// the types are the same.
+fn convert_to(exp: Exp, from: Type, to: Type) -> Exp {
+    if from == to {
+        return exp;
+    }
+
+    Exp {
+        span: exp.span,
+        kind: ExpKind::Convert(Box::new(exp)),
+        ty: Some(to),
+    }
+}
+
+impl Fold for TypeChecker {
+    fn fold_function(&mut self, function: Function) -> Function {
+        let Function::Function {
+            return_type,
+            name,
+            params,
+            format,
+            body,
+            span,
+            ..
+        } = &function;
+        if !self.symbols.declare(name, function.ty()) {
+            self.report(SemanticError::ConflictingTypes(name.clone(), *span));
+        }
+        if let Some(format) = format {
+            self.formats.insert(name.clone(), *format);
+        }
+        if body.is_some() {
+            for param in params {
+                if let Some(name) = &param.name {
+                    self.symbols.declare(name, param.ty.clone());
+                }
+            }
+            self.function = Some((name.clone(), return_type.clone()));
+        }
+
+        fold::walk_function(self, function)
+    }
+
+    fn fold_declaration(&mut self, declaration: Declaration) -> Declaration {
+        match declaration {
+            Declaration::Variable {
+                name,
+                ty,
+                init,
+                span,
+            } => {
+                self.symbols.declare(&name, ty.clone());
+                // The parser has checked that arrays are initialized from
+                // a string literal of the right size.
+                let init = init.map(|init| match self.fold_exp(init) {
+                    init if ty.is_array() => init,
+                    init => self.convert(init, &ty, Conversion::Initializing),
+                });
+
+                Declaration::Variable {
+                    name,
+                    ty,
+                    init,
+                    span,
+                }
+            }
+            Declaration::Function(function) => Declaration::Function(self.fold_function(function)),
+        }
+    }
+
+    fn fold_statement(&mut self, statement: Statement) -> Statement {
+        let Statement {
+            kind: StatementKind::Return(exp),
+            span,
+        } = statement
+        else {
+            return fold::walk_statement(self, statement);
+        };
+
+        let exp = self.fold_exp(exp);
+        let (name, ret) = self
+            .function
+            .clone()
+            .expect("return statements are in a function body");
+        let exp = if ret.is_void() {
+            self.report(SemanticError::VoidFunctionReturnsValue(name, span));
+            exp
+        } else {
+            self.convert(exp, &ret, Conversion::Returning)
+        };
+
+        Statement {
+            kind: StatementKind::Return(exp),
+            span,
+        }
+    }
+
+    fn fold_exp(&mut self, exp: Exp) -> Exp {
+        let Exp { kind, span, ty } = exp;
+        let (kind, ty) = match kind {
+            ExpKind::ConstantChar(_, CharPrefix::Wide) => (kind, Type::wchar()),
+            ExpKind::ConstantInt(_) | ExpKind::ConstantChar(..) => (kind, int()),
+            ExpKind::String(bytes) => {
+                let ty = Type::new(TypeKind::Array {
+                    element: Box::new(Type::new(TypeKind::Char)),
+                    size: Some(bytes.len() as i64 + 1),
+                });
+                (ExpKind::String(bytes), ty)
+            }
+            // Resolution has let through only the callees of calls to
+            // undeclared functions.
+            ExpKind::Var(name) => {
+                let ty = self
+                    .symbols
+                    .get(&name)
+                    .cloned()
+                    .unwrap_or_else(implicit_function);
+                (ExpKind::Var(name), ty)
+            }
+            ExpKind::Unary(op, exp) => self.check_unary(op, *exp, span),
+            ExpKind::Binary(op, lhs, rhs) => self.check_binary(op, *lhs, *rhs, span),
+            ExpKind::Conditional(condition, then, otherwise) => {
+                self.check_conditional(*condition, *then, *otherwise, span)
+            }
+            ExpKind::Assignment(lhs, rhs) => {
+                let lhs = self.fold_exp(*lhs);
+                let rhs = self.fold_exp(*rhs);
+                self.check_assignable(&lhs);
+                let ty = lhs.ty().unqualified();
+                let rhs = self.convert(rhs, &ty, Conversion::Assigning);
+                (ExpKind::Assignment(Box::new(lhs), Box::new(rhs)), ty)
+            }
+            ExpKind::AddressOf(exp) => {
+                let exp = self.fold_exp(*exp);
+                if !is_lvalue(&exp) && !exp.ty().is_function() {
+                    self.report(SemanticError::AddressOfRvalue(exp.ty().clone(), exp.span));
+                }
+                let ty = Type::pointer_to(exp.ty().clone());
+                (ExpKind::AddressOf(Box::new(exp)), ty)
+            }
+            ExpKind::Dereference(exp) => {
+                let exp = self.fold_exp(*exp);
+                let ty = match exp.ty().decay().kind {
+                    TypeKind::Pointer(pointee) => *pointee,
+                    _ => {
+                        self.report(SemanticError::InvalidIndirection(
+                            exp.ty().clone(),
+                            exp.span,
+                        ));
+                        int()
+                    }
+                };
+                (ExpKind::Dereference(Box::new(exp)), ty)
+            }
+            ExpKind::Subscript(a, b) => {
+                let a = self.fold_exp(*a);
+                let b = self.fold_exp(*b);
+                let (ta, tb) = (a.ty().decay(), b.ty().decay());
+                let integers = (ta.is_integer(), tb.is_integer());
+                // C allows `2[a]` as well as `a[2]`.
+                let ty = match (ta.kind, tb.kind) {
+                    (TypeKind::Pointer(element), _) if integers.1 => *element,
+                    (_, TypeKind::Pointer(element)) if integers.0 => *element,
+                    (TypeKind::Pointer(_), _) | (_, TypeKind::Pointer(_)) => {
+                        self.report(SemanticError::NonIntegerSubscript(span));
+                        int()
+                    }
+                    _ => {
+                        self.report(SemanticError::InvalidSubscript(span));
+                        int()
+                    }
+                };
+                (ExpKind::Subscript(Box::new(a), Box::new(b)), ty)
+            }
+            ExpKind::FunctionCall(callee, args) => self.check_call(*callee, args, span),
+            ExpKind::VaStart(ap) => {
+                let ap = self.fold_exp(*ap);
+                self.check_va_list("va_start", &ap);
+                (ExpKind::VaStart(Box::new(ap)), Type::new(TypeKind::Void))
+            }
+            ExpKind::VaArg(ap, ty) => {
+                let ap = self.fold_exp(*ap);
+                self.check_va_list("va_arg", &ap);
+                (ExpKind::VaArg(Box::new(ap), ty.clone()), ty)
+            }
+            ExpKind::VaEnd(ap) => {
+                let ap = self.fold_exp(*ap);
+                self.check_va_list("va_end", &ap);
+                (ExpKind::VaEnd(Box::new(ap)), Type::new(TypeKind::Void))
+            }
+            ExpKind::VaCopy(dst, src) => {
+                let dst = self.fold_exp(*dst);
+                let src = self.fold_exp(*src);
+                self.check_va_list("va_copy", &dst);
+                self.check_va_list("va_copy", &src);
+                let kind = ExpKind::VaCopy(Box::new(dst), Box::new(src));
+                (kind, Type::new(TypeKind::Void))
+            }
+            ExpKind::Convert(exp) => {
+                let exp = self.fold_exp(*exp);
+                let ty = ty.expect("conversions are inserted with their type");
+                (ExpKind::Convert(Box::new(exp)), ty)
+            }
+        };
+
+        Exp {
+            kind,
+            span,
+            ty: Some(ty),
+        }
+    }
+}
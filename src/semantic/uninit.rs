@@ -0,0 +1,121 @@
+use crate::ast::visit::Visit;
+use crate::ast::{Function, Program};
+use crate::error::{SemanticWarning, SemanticWarningKind};
+use crate::semantic::cfg::{Cfg, Event};
+
+/// Warns about local variables that may be read before they are written,
+/// by definite-assignment analysis over the control-flow graph of each
+/// function body.
+#[derive(Default)]
+pub(super) struct UninitializedChecker {
+    warnings: Vec<SemanticWarning>,
+}
+
+impl UninitializedChecker {
+    pub(super) fn check(mut self, program: &Program) -> Vec<SemanticWarning> {
+        self.visit_program(program);
+        self.warnings
+    }
+
+    /// The warnings about the body whose graph is `cfg`. The graph may have
+    /// cycles, as loops will give it: what holds around one is iterated to a
+    /// fixed point.
+    pub(super) fn check_body(cfg: &Cfg) -> Vec<SemanticWarning> {
+        let mut warnings = Vec::new();
+        let reachable = cfg.reachable();
+        let predecessors = cfg.predecessors();
+        let count = cfg.variables.len();
+
+        // What holds on leaving each block: which variables are assigned
+        // on every path to there, and which on some path. A block not yet
+        // reached by the iteration leaves every variable assigned on every
+        // path and on none, so that it does not change what its successors
+        // are entered with.
+        let mut must = vec![vec![true; count]; cfg.blocks.len()];
+        let mut maybe = vec![vec![false; count]; cfg.blocks.len()];
+        let mut entry = vec![(Vec::new(), Vec::new()); cfg.blocks.len()];
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for (i, block) in cfg.blocks.iter().enumerate() {
+                if !reachable[i] {
+                    continue;
+                }
+                let (mut must_in, mut maybe_in) = match i {
+                    0 => (vec![false; count], vec![false; count]),
+                    _ => (vec![true; count], vec![false; count]),
+                };
+                for &p in predecessors[i].iter().filter(|&&p| reachable[p]) {
+                    for v in 0..count {
+                        must_in[v] &= must[p][v];
+                        maybe_in[v] |= maybe[p][v];
+                    }
+                }
+
+                let (mut must_out, mut maybe_out) = (must_in.clone(), maybe_in.clone());
+                for event in &block.events {
+                    transfer(*event, &mut must_out, &mut maybe_out);
+                }
+                changed |= must_out != must[i] || maybe_out != maybe[i];
+                (must[i], maybe[i]) = (must_out, maybe_out);
+                entry[i] = (must_in, maybe_in);
+            }
+        }
+
+        // Each variable is warned about at its first bad use only.
+        let mut warned = vec![false; count];
+        for (i, block) in cfg.blocks.iter().enumerate() {
+            if !reachable[i] {
+                continue;
+            }
+            let (must, maybe) = &mut entry[i];
+            for event in &block.events {
+                transfer(*event, must, maybe);
+                match *event {
+                    Event::Use(v, span) if !must[v] && !warned[v] => {
+                        let variable = &cfg.variables[v];
+                        let name = original_name(&variable.name).to_owned();
+                        let kind = if maybe[v] {
+                            SemanticWarningKind::SometimesUninitialized(name, variable.span)
+                        } else {
+                            SemanticWarningKind::Uninitialized(name, variable.span)
+                        };
+                        warnings.push(SemanticWarning { kind, span });
+                        warned[v] = true;
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        warnings
+    }
+}
+
+/// Updates which variables are assigned on every path and on some path
+/// for what `event` does.
+fn transfer(event: Event, must: &mut [bool], maybe: &mut [bool]) {
+    match event {
+        Event::Declare(v) => (must[v], maybe[v]) = (false, false),
+        // A variable whose address is taken may be written through it at
+        // any point, so it is taken to be assigned from there on.
+        Event::Assign(v) | Event::Escape(v) => (must[v], maybe[v]) = (true, true),
+        Event::Use(..) | Event::Code(_) | Event::Return => {}
+    }
+}
+
+/// The name a local was declared with, before resolution made it unique.
+fn original_name(name: &str) -> &str {
+    name.rsplit_once('.').map_or(name, |(name, _)| name)
+}
+
+impl Visit for UninitializedChecker {
+    fn visit_function(&mut self, function: &Function) {
+        if let Function::Function {
+            body: Some(body), ..
+        } = function
+        {
+            self.warnings.extend(Self::check_body(&Cfg::build(body)));
+        }
+    }
+}
@@ -0,0 +1,244 @@
+use crate::span::Span;
+
+/// A file read during compilation. Its bytes occupy the global offsets
+/// `base..base + src.len()`, so a `Span` alone identifies both the file and
+/// the position within it.
+pub struct SourceFile {
+    pub name: String,
+    pub src: String,
+    pub base: usize,
+    /// The global offset of the `#include` that read the file, if any.
+    pub included_from: Option<usize>,
+    /// The local offsets at which lines start.
+    lines: Vec<usize>,
+}
+
+impl SourceFile {
+    /// Converts a global offset to one within this file.
+    pub fn local(&self, pos: usize) -> usize {
+        (pos - self.base).min(self.src.len())
+    }
+
+    /// The 1-based line and column of the global offset `pos`. Columns
+    /// count characters.
+    pub fn line_col(&self, pos: usize) -> (usize, usize) {
+        let pos = self.local(pos);
+        let line = self.lines.partition_point(|&start| start <= pos);
+        let line_start = self.lines[line - 1];
+        let col = self.src[line_start..pos].chars().count() + 1;
+
+        (line, col)
+    }
+}
+
+/// A macro expansion: the macro's name and where it was invoked, from
+/// its name through the `)` closing its arguments. The site may itself
+/// lie in another expansion.
+pub struct Expansion {
+    pub name: String,
+    pub site: Span,
+}
+
+/// A token produced by a macro expansion. Its span is a range of offsets
+/// of its own, past those of the files read so far, which stand for the
+/// offsets of `spelling`, where the token was written before the
+/// expansion.
+struct Virtual {
+    base: usize,
+    spelling: Span,
+    expansion: usize,
+}
+
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    expansions: Vec<Expansion>,
+    virtuals: Vec<Virtual>,
+    /// The first global offset not yet given to a file or token.
+    end: usize,
+}
+
+impl SourceMap {
+    /// Adds a file, read by the `#include` at `included_from` if any, and
+    /// returns the global offset of its first byte.
+    pub fn add(&mut self, name: String, src: String, included_from: Option<usize>) -> usize {
+        // One offset of padding keeps a span just past the end of a file,
+        // as for an unexpected end of input, out of the next file.
+        let base = self.end;
+        self.end += src.len() + 1;
+        let lines = std::iter::once(0)
+            .chain(src.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        self.files.push(SourceFile {
+            name,
+            src,
+            base,
+            included_from,
+            lines,
+        });
+
+        base
+    }
+
+    /// The first file added, which is the translation unit.
+    pub fn main_file(&self) -> Option<&SourceFile> {
+        self.files.first()
+    }
+
+    /// The file containing the global offset `pos`, which must not be
+    /// that of a token produced by a macro expansion.
+    pub fn file(&self, pos: usize) -> Option<&SourceFile> {
+        let index = self.files.partition_point(|file| file.base <= pos);
+        self.files
+            .get(index.checked_sub(1)?)
+            .filter(|file| pos <= file.base + file.src.len())
+    }
+
+    /// Records the expansion of the macro `name`, invoked at `site`, and
+    /// returns its index.
+    pub fn add_expansion(&mut self, name: String, site: Span) -> usize {
+        self.expansions.push(Expansion { name, site });
+        self.expansions.len() - 1
+    }
+
+    /// Gives a token spelled at `spelling` the span it has as produced by
+    /// the expansion `expansion`.
+    pub fn expanded_span(&mut self, spelling: Span, expansion: usize) -> Span {
+        let base = self.end;
+        let len = spelling.end.saturating_sub(spelling.start);
+        self.end += len + 1;
+        self.virtuals.push(Virtual {
+            base,
+            spelling,
+            expansion,
+        });
+
+        Span::new(base, base + len)
+    }
+
+    /// The token produced by a macro expansion that `pos` is in, if any.
+    fn virtual_at(&self, pos: usize) -> Option<&Virtual> {
+        let index = self.virtuals.partition_point(|token| token.base <= pos);
+        let token = self.virtuals.get(index.checked_sub(1)?)?;
+        let len = token.spelling.end.saturating_sub(token.spelling.start);
+
+        (pos <= token.base + len).then_some(token)
+    }
+
+    /// The offset in a file where `pos` was written, following tokens
+    /// produced by macro expansions back to their spelling.
+    pub fn spelling(&self, mut pos: usize) -> usize {
+        while let Some(token) = self.virtual_at(pos) {
+            pos = token.spelling.start + (pos - token.base);
+        }
+
+        pos
+    }
+
+    /// The indices of the expansions that produced the token at `pos`,
+    /// innermost first: each was invoked from the one after it.
+    fn expansion_chain(&self, mut pos: usize) -> Vec<usize> {
+        let mut chain = Vec::new();
+        while let Some(token) = self.virtual_at(pos) {
+            chain.push(token.expansion);
+            pos = self.expansions[token.expansion].site.start;
+        }
+
+        chain
+    }
+
+    /// The expansions that produced the token at `pos`, innermost first.
+    pub fn expansions(&self, pos: usize) -> Vec<&Expansion> {
+        self.expansion_chain(pos)
+            .into_iter()
+            .map(|index| &self.expansions[index])
+            .collect()
+    }
+
+    /// The span in a file to show for `span`. Ends produced by different
+    /// expansions are taken back to the invocations until they share one,
+    /// then to where they were written. If that does not give a range
+    /// within one file, as for an argument and a token of the replacement
+    /// list, both are taken back to the invocation.
+    pub fn spelling_span(&self, span: Span) -> Span {
+        let last = span.end.max(span.start + 1) - 1;
+        let (mut start, mut start_chain) = (span.start, self.expansion_chain(span.start));
+        let (mut last, mut last_chain) = (last, self.expansion_chain(last));
+        loop {
+            if start_chain.first() == last_chain.first() {
+                let (start, last) = (self.spelling(start), self.spelling(last));
+                match (self.file(start), self.file(last)) {
+                    (Some(a), Some(b)) if a.base == b.base && start <= last => {
+                        return Span::new(start, last + 1);
+                    }
+                    _ if start_chain.is_empty() => return Span::single(start),
+                    _ => {}
+                }
+            }
+
+            if start_chain.len() >= last_chain.len() {
+                start = self.expansions[start_chain.remove(0)].site.start;
+            }
+            if last_chain.len() > start_chain.len() {
+                let site = self.expansions[last_chain.remove(0)].site;
+                last = site.end.max(site.start + 1) - 1;
+            }
+        }
+    }
+
+    /// The source text a span covers.
+    pub fn snippet(&self, span: Span) -> &str {
+        if span.start == span.end {
+            return "";
+        }
+        let span = self.spelling_span(span);
+        match self.file(span.start) {
+            Some(file) => &file.src[file.local(span.start)..file.local(span.end)],
+            None => "",
+        }
+    }
+
+    /// Where `pos` is written, as `file.c:12:5`.
+    pub fn location(&self, pos: usize) -> Option<String> {
+        let pos = self.spelling(pos);
+        let file = self.file(pos)?;
+        let (line, col) = file.line_col(pos);
+        Some(format!("{}:{line}:{col}", file.name))
+    }
+
+    /// A key that orders positions as the preprocessor read them: the
+    /// offsets of the `#include`s that led to the file containing `pos`,
+    /// outermost first, then `pos` itself. A token produced by a macro
+    /// expansion comes where the outermost expansion was invoked.
+    pub fn translation_order(&self, mut pos: usize) -> Vec<usize> {
+        while let Some(token) = self.virtual_at(pos) {
+            pos = self.expansions[token.expansion].site.start;
+        }
+        let mut key = vec![pos];
+        let mut from = self.file(pos).and_then(|file| file.included_from);
+        while let Some(pos) = from {
+            key.push(pos);
+            from = self.file(pos).and_then(|file| file.included_from);
+        }
+        key.reverse();
+
+        key
+    }
+
+    /// The `#include` directives that led to the file containing `pos`,
+    /// innermost first, as file names and lines.
+    pub fn include_stack(&self, pos: usize) -> Vec<(&str, usize)> {
+        let mut stack = Vec::new();
+        let mut from = self
+            .file(self.spelling(pos))
+            .and_then(|file| file.included_from);
+        while let Some(pos) = from
+            && let Some(file) = self.file(pos)
+        {
+            stack.push((file.name.as_str(), file.line_col(pos).0));
+            from = file.included_from;
+        }
+
+        stack
+    }
+}
@@ -1,35 +1,116 @@
 use core::fmt;
 
+use crate::error::LexerErrorKind;
 use crate::span::Span;
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Keyword {
     Int,
+    Char,
     Void,
     Return,
+    Const,
+    Volatile,
+    Restrict,
+    BuiltinVaList,
+    StaticAssert,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum Const {
     Int(i64),
+    Char(i64, CharPrefix),
 }
 
-#[derive(Debug, PartialEq)]
+/// The encoding prefix of a character constant, which decides its type:
+/// `int` when unprefixed, `wchar_t` (`int`) for `L`, `char16_t`
+/// (`unsigned short`) for `u`, `char32_t` (`unsigned int`) for `U` and
+/// `unsigned char` for `u8`. Without unsigned types, the parser rejects the
+/// last three, which only `#if` can evaluate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CharPrefix {
+    None,
+    Wide,
+    Utf16,
+    Utf32,
+    Utf8,
+}
+
+impl CharPrefix {
+    /// The largest code unit a constant with this prefix can hold.
+    pub fn max(&self) -> u32 {
+        match self {
+            Self::None | Self::Utf8 => 0xff,
+            Self::Utf16 => 0xffff,
+            Self::Wide | Self::Utf32 => 0xffff_ffff,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub enum TokenKind {
     Identifier(String),
     Constant(Const),
+    /// The bytes of a plain or `u8` string literal, without the terminator.
+    StringLiteral(Vec<u8>),
+    /// The `<stdio.h>` or `"foo.h"` operand of `#include`, as spelled.
+    HeaderName(String),
     Keyword(Keyword),
     OpenParenthesis,
     CloseParenthesis,
     OpenBrace,
     CloseBrace,
+    OpenBracket,
+    CloseBracket,
     Semicolon,
+    Comma,
+    Ellipsis,
+    Dot,
+    Arrow,
+    Asterisk,
+    Ampersand,
+    Plus,
+    Assign,
     Complement,
     Negation,
+    Increment,
     Decrement,
+    Exclamation,
+    Slash,
+    Percent,
+    ShiftLeft,
+    ShiftRight,
+    Less,
+    Greater,
+    LessEqual,
+    GreaterEqual,
+    Equal,
+    NotEqual,
+    Caret,
+    Pipe,
+    LogicalAnd,
+    LogicalOr,
+    Question,
+    Colon,
+    MultiplyAssign,
+    DivideAssign,
+    RemainderAssign,
+    AddAssign,
+    SubtractAssign,
+    ShiftLeftAssign,
+    ShiftRightAssign,
+    AndAssign,
+    XorAssign,
+    OrAssign,
+    Hash,
+    HashHash,
+    /// A preprocessing token that is no valid token, such as a stray `@` or
+    /// the number `1.5`. It is only an error if it survives preprocessing,
+    /// rather than being stringified or skipped.
+    Invalid(LexerErrorKind),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Token {
     pub kind: TokenKind,
     pub span: Span,
@@ -39,8 +120,26 @@ impl fmt::Display for Keyword {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int => write!(f, "int"),
+            Self::Char => write!(f, "char"),
             Self::Return => write!(f, "return"),
+            Self::StaticAssert => write!(f, "_Static_assert"),
             Self::Void => write!(f, "void"),
+            Self::Const => write!(f, "const"),
+            Self::Volatile => write!(f, "volatile"),
+            Self::Restrict => write!(f, "restrict"),
+            Self::BuiltinVaList => write!(f, "__builtin_va_list"),
+        }
+    }
+}
+
+impl fmt::Display for CharPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::None => Ok(()),
+            Self::Wide => write!(f, "L"),
+            Self::Utf16 => write!(f, "u"),
+            Self::Utf32 => write!(f, "U"),
+            Self::Utf8 => write!(f, "u8"),
         }
     }
 }
@@ -49,6 +148,12 @@ impl fmt::Display for Const {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Int(int) => write!(f, "{int}"),
+            Self::Char(value, prefix) => match u8::try_from(*value) {
+                Ok(c) if c.is_ascii_graphic() && c != b'\\' && c != b'\'' => {
+                    write!(f, "{prefix}'{}'", c as char)
+                }
+                _ => write!(f, "{prefix}'\\x{value:x}'"),
+            },
         }
     }
 }
@@ -58,15 +163,59 @@ impl fmt::Display for TokenKind {
         match self {
             Self::Identifier(identifier) => write!(f, "{}", identifier),
             Self::Constant(constant) => write!(f, "{constant}"),
+            Self::StringLiteral(bytes) => write!(f, "\"{}\"", bytes.escape_ascii()),
+            Self::HeaderName(name) => write!(f, "{name}"),
             Self::Keyword(keyword) => write!(f, "{keyword}"),
             Self::OpenParenthesis => write!(f, "("),
             Self::CloseParenthesis => write!(f, ")"),
             Self::OpenBrace => write!(f, "{{"),
             Self::CloseBrace => write!(f, "}}"),
+            Self::OpenBracket => write!(f, "["),
+            Self::CloseBracket => write!(f, "]"),
             Self::Semicolon => write!(f, ";"),
+            Self::Comma => write!(f, ","),
+            Self::Ellipsis => write!(f, "..."),
+            Self::Dot => write!(f, "."),
+            Self::Arrow => write!(f, "->"),
+            Self::Asterisk => write!(f, "*"),
+            Self::Ampersand => write!(f, "&"),
+            Self::Plus => write!(f, "+"),
+            Self::Assign => write!(f, "="),
             Self::Complement => write!(f, "~"),
             Self::Negation => write!(f, "-"),
+            Self::Increment => write!(f, "++"),
             Self::Decrement => write!(f, "--"),
+            Self::Exclamation => write!(f, "!"),
+            Self::Slash => write!(f, "/"),
+            Self::Percent => write!(f, "%"),
+            Self::ShiftLeft => write!(f, "<<"),
+            Self::ShiftRight => write!(f, ">>"),
+            Self::Less => write!(f, "<"),
+            Self::Greater => write!(f, ">"),
+            Self::LessEqual => write!(f, "<="),
+            Self::GreaterEqual => write!(f, ">="),
+            Self::Equal => write!(f, "=="),
+            Self::NotEqual => write!(f, "!="),
+            Self::Caret => write!(f, "^"),
+            Self::Pipe => write!(f, "|"),
+            Self::LogicalAnd => write!(f, "&&"),
+            Self::LogicalOr => write!(f, "||"),
+            Self::Question => write!(f, "?"),
+            Self::Colon => write!(f, ":"),
+            Self::MultiplyAssign => write!(f, "*="),
+            Self::DivideAssign => write!(f, "/="),
+            Self::RemainderAssign => write!(f, "%="),
+            Self::AddAssign => write!(f, "+="),
+            Self::SubtractAssign => write!(f, "-="),
+            Self::ShiftLeftAssign => write!(f, "<<="),
+            Self::ShiftRightAssign => write!(f, ">>="),
+            Self::AndAssign => write!(f, "&="),
+            Self::XorAssign => write!(f, "^="),
+            Self::OrAssign => write!(f, "|="),
+            Self::Hash => write!(f, "#"),
+            Self::HashHash => write!(f, "##"),
+            Self::Invalid(LexerErrorKind::UnexpectedCharacter(ch)) => write!(f, "{ch}"),
+            Self::Invalid(_) => write!(f, "<invalid>"),
         }
     }
 }
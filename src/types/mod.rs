@@ -0,0 +1,273 @@
+pub mod symbols;
+
+use std::fmt;
+
+/// Qualifiers attached to one level of a type.
+///
+/// Accesses through a `volatile` lvalue are observable: any optimizer must
+/// keep volatile loads and stores in program order, one for one.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub struct Qualifiers {
+    pub is_const: bool,
+    pub is_volatile: bool,
+    pub is_restrict: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Type {
+    pub kind: TypeKind,
+    pub qualifiers: Qualifiers,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum TypeKind {
+    Int,
+    Char,
+    Void,
+    Pointer(Box<Type>),
+    Array {
+        element: Box<Type>,
+        size: Option<i64>,
+    },
+    Function {
+        params: Vec<Type>,
+        variadic: bool,
+        ret: Box<Type>,
+    },
+    /// The SysV `__va_list_tag`; `__builtin_va_list` is an array of one.
+    VaListTag,
+}
+
+impl Qualifiers {
+    pub fn is_empty(&self) -> bool {
+        !(self.is_const || self.is_volatile || self.is_restrict)
+    }
+
+    /// Whether every qualifier in `other` is also in these.
+    pub fn contains(&self, other: Qualifiers) -> bool {
+        (self.is_const || !other.is_const)
+            && (self.is_volatile || !other.is_volatile)
+            && (self.is_restrict || !other.is_restrict)
+    }
+}
+
+impl Type {
+    pub fn new(kind: TypeKind) -> Self {
+        Self {
+            kind,
+            qualifiers: Qualifiers::default(),
+        }
+    }
+
+    pub fn pointer_to(pointee: Type) -> Self {
+        Self::new(TypeKind::Pointer(Box::new(pointee)))
+    }
+
+    /// The type of the difference of two pointers, `ptrdiff_t`. There is
+    /// no `long` yet, so it is `int`, narrower than a pointer.
+    pub fn ptrdiff() -> Self {
+        Self::new(TypeKind::Int)
+    }
+
+    /// The type of a wide character constant, `wchar_t`.
+    pub fn wchar() -> Self {
+        Self::new(TypeKind::Int)
+    }
+
+    /// The size of an object of this type in bytes, on x86-64. `void` and
+    /// functions have the size 1, as GCC gives them for pointer arithmetic.
+    pub fn size(&self) -> i64 {
+        match &self.kind {
+            TypeKind::Int => 4,
+            TypeKind::Char => 1,
+            TypeKind::Pointer(_) => 8,
+            TypeKind::Array { element, size } => element.size() * size.unwrap_or(1),
+            TypeKind::VaListTag => 24,
+            TypeKind::Void | TypeKind::Function { .. } => 1,
+        }
+    }
+
+    pub fn is_function(&self) -> bool {
+        matches!(self.kind, TypeKind::Function { .. })
+    }
+
+    pub fn is_array(&self) -> bool {
+        matches!(self.kind, TypeKind::Array { .. })
+    }
+
+    pub fn is_pointer(&self) -> bool {
+        matches!(self.kind, TypeKind::Pointer(_))
+    }
+
+    /// Whether this is `int` or `char`, the arithmetic types supported.
+    pub fn is_integer(&self) -> bool {
+        matches!(self.kind, TypeKind::Int | TypeKind::Char)
+    }
+
+    /// Whether values of this type can be tested against zero.
+    pub fn is_scalar(&self) -> bool {
+        self.is_integer() || self.is_pointer()
+    }
+
+    /// Whether this is a pointer to an object type, as pointer arithmetic
+    /// needs to know the size of what is pointed to.
+    pub fn is_object_pointer(&self) -> bool {
+        matches!(&self.kind, TypeKind::Pointer(pointee) if !pointee.is_void() && !pointee.is_function())
+    }
+
+    pub fn is_void(&self) -> bool {
+        self.kind == TypeKind::Void
+    }
+
+    /// This type without its top-level qualifiers, as the value of an
+    /// lvalue of this type has.
+    pub fn unqualified(&self) -> Self {
+        Self::new(self.kind.clone())
+    }
+
+    /// The type of the value of an expression of this type: arrays decay to
+    /// pointers to their first element, functions to function pointers, and
+    /// top-level qualifiers are dropped.
+    pub fn decay(&self) -> Self {
+        match &self.kind {
+            TypeKind::Array { element, .. } => Self::pointer_to(element.as_ref().clone()),
+            TypeKind::Function { .. } => Self::pointer_to(self.clone()),
+            _ => self.unqualified(),
+        }
+    }
+
+    /// Whether two declarations of one entity may have these types: they
+    /// must agree but for array sizes one of them leaves out, and for the
+    /// top-level qualifiers of parameters, which are not part of a function's
+    /// type.
+    pub fn is_compatible(&self, other: &Type) -> bool {
+        if self.qualifiers != other.qualifiers {
+            return false;
+        }
+
+        match (&self.kind, &other.kind) {
+            (TypeKind::Pointer(a), TypeKind::Pointer(b)) => a.is_compatible(b),
+            (
+                TypeKind::Array {
+                    element: a,
+                    size: m,
+                },
+                TypeKind::Array {
+                    element: b,
+                    size: n,
+                },
+            ) => a.is_compatible(b) && (m.is_none() || n.is_none() || m == n),
+            (
+                TypeKind::Function {
+                    params: p,
+                    variadic: v,
+                    ret: r,
+                },
+                TypeKind::Function {
+                    params: q,
+                    variadic: w,
+                    ret: s,
+                },
+            ) => {
+                v == w
+                    && r.is_compatible(s)
+                    && p.len() == q.len()
+                    && p.iter()
+                        .zip(q)
+                        .all(|(a, b)| a.unqualified().is_compatible(&b.unqualified()))
+            }
+            (a, b) => a == b,
+        }
+    }
+
+    /// Adjusts a declared parameter type: arrays become pointers to their
+    /// element type and functions become pointers to functions.
+    pub fn adjust_parameter(self) -> Self {
+        match self.kind {
+            TypeKind::Array { element, .. } => Self::pointer_to(*element),
+            TypeKind::Function { .. } => Self::pointer_to(self),
+            _ => self,
+        }
+    }
+
+    /// Spells a declaration of `name` with this type, e.g. `int (*name)(int)`.
+    pub fn declaration(&self, name: &str) -> String {
+        self.spell(name.to_string())
+    }
+
+    fn spell(&self, inner: String) -> String {
+        match &self.kind {
+            TypeKind::Pointer(pointee) => {
+                let mut decl = format!("*{}", self.qualifiers);
+                if !self.qualifiers.is_empty() && !inner.is_empty() {
+                    decl.push(' ');
+                }
+                decl.push_str(&inner);
+                if pointee.is_array() || pointee.is_function() {
+                    decl = format!("({decl})");
+                }
+                pointee.spell(decl)
+            }
+            TypeKind::Array { element, size } => {
+                let size = size.map(|n| n.to_string()).unwrap_or_default();
+                element.spell(format!("{inner}[{size}]"))
+            }
+            TypeKind::Function {
+                params,
+                variadic,
+                ret,
+            } => {
+                let mut params = params.iter().map(|p| p.to_string()).collect::<Vec<_>>();
+                if *variadic {
+                    params.push(String::from("..."));
+                }
+                let params = if params.is_empty() {
+                    String::from("void")
+                } else {
+                    params.join(", ")
+                };
+                ret.spell(format!("{inner}({params})"))
+            }
+            TypeKind::Int | TypeKind::Char | TypeKind::Void | TypeKind::VaListTag => {
+                let mut out = String::new();
+                if !self.qualifiers.is_empty() {
+                    out.push_str(&format!("{} ", self.qualifiers));
+                }
+                out.push_str(match self.kind {
+                    TypeKind::Int => "int",
+                    TypeKind::Char => "char",
+                    TypeKind::Void => "void",
+                    _ => "__va_list_tag",
+                });
+                if !inner.is_empty() {
+                    out.push(' ');
+                    out.push_str(&inner);
+                }
+                out
+            }
+        }
+    }
+}
+
+impl fmt::Display for Qualifiers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = [
+            (self.is_const, "const"),
+            (self.is_volatile, "volatile"),
+            (self.is_restrict, "restrict"),
+        ];
+        let words = names
+            .iter()
+            .filter(|(set, _)| *set)
+            .map(|(_, name)| *name)
+            .collect::<Vec<_>>();
+
+        write!(f, "{}", words.join(" "))
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.spell(String::new()))
+    }
+}
@@ -0,0 +1,28 @@
+use crate::types::Type;
+use std::collections::HashMap;
+
+/// The type of every name in a program. Resolution has already given each
+/// local a unique name, so one table covers all scopes; functions are
+/// entered under their own names, as they have linkage.
+#[derive(Default)]
+pub struct SymbolTable {
+    symbols: HashMap<String, Type>,
+}
+
+impl SymbolTable {
+    pub fn get(&self, name: &str) -> Option<&Type> {
+        self.symbols.get(name)
+    }
+
+    /// Records a declaration of `name`, returning false if an earlier one
+    /// gave it an incompatible type. The earlier type is kept either way.
+    pub fn declare(&mut self, name: &str, ty: Type) -> bool {
+        match self.symbols.get(name) {
+            Some(previous) => previous.is_compatible(&ty),
+            None => {
+                self.symbols.insert(name.to_string(), ty);
+                true
+            }
+        }
+    }
+}